@@ -0,0 +1,110 @@
+use crate::iso::utils::LOGIC_SIZE_U32;
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+/// The Apple Partition Map uses the same block size as the ISO image.
+pub const APM_BLOCK_SIZE: u32 = LOGIC_SIZE_U32;
+
+#[derive(Debug, Clone)]
+pub struct ApmPartition {
+    pub name: String,
+    pub partition_type: String,
+    pub start_block: u32,
+    pub block_count: u32,
+}
+
+fn write_padded_str<T>(output_writter: &mut T, value: &str, size: usize) -> std::io::Result<()>
+where
+    T: Write,
+{
+    let mut data = vec![0u8; size];
+    let len = std::cmp::min(value.len(), size);
+    data[..len].copy_from_slice(&value.as_bytes()[..len]);
+    output_writter.write_all(&data)
+}
+
+impl ApmPartition {
+    fn write_entry<T>(&self, output_writter: &mut T, map_block_count: u32) -> std::io::Result<()>
+    where
+        T: Write,
+    {
+        // Signature + padding
+        output_writter.write_all(b"PM")?;
+        output_writter.write_u16::<BigEndian>(0x0)?;
+
+        // Number of blocks in the partition map
+        output_writter.write_u32::<BigEndian>(map_block_count)?;
+
+        output_writter.write_u32::<BigEndian>(self.start_block)?;
+        output_writter.write_u32::<BigEndian>(self.block_count)?;
+
+        write_padded_str(output_writter, &self.name, 0x20)?;
+        write_padded_str(output_writter, &self.partition_type, 0x20)?;
+
+        // Data area (the whole partition)
+        output_writter.write_u32::<BigEndian>(0x0)?;
+        output_writter.write_u32::<BigEndian>(self.block_count)?;
+
+        // Status (valid, allocated, in use, readable)
+        output_writter.write_u32::<BigEndian>(0x33)?;
+
+        // Boot code informations (unused) and processor type
+        let unused: [u8; 0x2C] = [0x0; 0x2C];
+        output_writter.write_all(&unused)?;
+
+        // Pad to block size
+        let padding = vec![0u8; (APM_BLOCK_SIZE - 0x88) as usize];
+        output_writter.write_all(&padding)?;
+
+        Ok(())
+    }
+}
+
+/// Count of blocks used by the Driver Descriptor Map and the partition map for the given partitions.
+pub fn get_block_count(partitions: &[ApmPartition]) -> u32 {
+    // DDM + partition map entry + partitions entries
+    1 + 1 + partitions.len() as u32
+}
+
+/// Write the Driver Descriptor Map and the Apple Partition Map at the start of the system area.
+///
+/// NOTE: The signature of the DDM overwrites the first bytes of the MBR boot code.
+pub fn write_apm<T>(
+    output_writter: &mut T,
+    partitions: &[ApmPartition],
+    disk_block_count: u32,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let current_pos = output_writter.stream_position()?;
+
+    // Driver Descriptor Map (without any driver)
+    output_writter.seek(SeekFrom::Start(0))?;
+    output_writter.write_all(b"ER")?;
+    output_writter.write_u16::<BigEndian>(APM_BLOCK_SIZE as u16)?;
+    output_writter.write_u32::<BigEndian>(disk_block_count)?;
+
+    // The partition map starts at block 1 and describes itself first
+    let map_block_count = get_block_count(partitions) - 1;
+    let partition_map = ApmPartition {
+        name: String::from("Apple"),
+        partition_type: String::from("Apple_partition_map"),
+        start_block: 1,
+        block_count: map_block_count,
+    };
+
+    output_writter.seek(SeekFrom::Start(u64::from(APM_BLOCK_SIZE)))?;
+    partition_map.write_entry(output_writter, map_block_count)?;
+
+    for partition in partitions {
+        partition.write_entry(output_writter, map_block_count)?;
+    }
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use crate::iso::gpt;
+use crate::iso::interval::IntervalSource;
+use crate::iso::utils;
+use crate::iso::utils::LOGIC_SIZE;
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+
+const LINUX_FILESYSTEM_TYPE_GUID: &str = "0FC63DAF-8483-4772-8E79-3D69D8477DE4";
+pub const PREP_BOOT_TYPE_GUID: &str = "9E1A2D38-C612-4316-AA26-8B49521E5A8B";
+
+#[derive(Debug, Clone)]
+pub struct AppendedPartition {
+    pub number: u8,
+    pub partition_type: u8,
+    pub path: PathBuf,
+    pub offset: u64,
+    pub size: u64,
+    pub lba: u32,
+}
+
+impl AppendedPartition {
+    pub fn new(number: u8, partition_type: u8, path: PathBuf) -> std::io::Result<Self> {
+        let size = path.metadata()?.len();
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("appended partition file \"{}\" is empty", path.display()),
+            ));
+        }
+
+        Ok(AppendedPartition {
+            number,
+            partition_type,
+            path,
+            offset: 0,
+            size,
+            lba: 0,
+        })
+    }
+
+    /// Partition whose content is the given byte range of a disk file.
+    pub fn from_range(
+        number: u8,
+        partition_type: u8,
+        path: PathBuf,
+        offset: u64,
+        size: u64,
+    ) -> std::io::Result<Self> {
+        let file_size = path.metadata()?.len();
+        if size == 0 || offset.checked_add(size).is_none_or(|end| end > file_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid range {}-{} of appended partition file \"{}\"",
+                    offset,
+                    offset.saturating_add(size),
+                    path.display()
+                ),
+            ));
+        }
+
+        Ok(AppendedPartition {
+            number,
+            partition_type,
+            path,
+            offset,
+            size,
+            lba: 0,
+        })
+    }
+
+    /// Partition whose content is a disk file or an interval of it.
+    pub fn from_source(number: u8, partition_type: u8, value: &str) -> std::io::Result<Self> {
+        if !IntervalSource::is_interval(value) {
+            return AppendedPartition::new(number, partition_type, PathBuf::from(value));
+        }
+
+        let interval = IntervalSource::parse(value)?;
+
+        // The partition content is copied straight from the disk file while writing
+        if interval.has_zeroizers() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "zeroizers are not supported for the appended partition \"{}\"",
+                    value
+                ),
+            ));
+        }
+
+        AppendedPartition::from_range(
+            number,
+            partition_type,
+            interval.path,
+            interval.offset,
+            interval.size,
+        )
+    }
+
+    pub fn get_lb_count(&self) -> u32 {
+        self.size.div_ceil(LOGIC_SIZE as u64) as u32
+    }
+
+    pub fn get_start_sector(&self) -> u64 {
+        utils::get_sector_count(self.lba)
+    }
+
+    pub fn get_sector_count(&self) -> u64 {
+        utils::get_sector_count(self.get_lb_count())
+    }
+
+    pub fn get_gpt_type_guid(&self) -> &'static str {
+        match self.partition_type {
+            0xEF => gpt::EFI_SYSTEM_TYPE_GUID,
+            0x41 => PREP_BOOT_TYPE_GUID,
+            0x01 | 0x04 | 0x06 | 0x07 | 0x0B | 0x0C | 0x0E => gpt::BASIC_DATA_TYPE_GUID,
+            _ => LINUX_FILESYSTEM_TYPE_GUID,
+        }
+    }
+
+    pub fn write_content<T>(&self, output_writter: &mut T) -> std::io::Result<()>
+    where
+        T: Write + Seek,
+    {
+        let old_pos = output_writter.stream_position()?;
+
+        // Seek to the correct LBA
+        output_writter.seek(SeekFrom::Start(u64::from(self.lba) * LOGIC_SIZE as u64))?;
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        io::copy(&mut file.take(self.size), output_writter)?;
+
+        // Pad to LBA size
+        let diff_size = (self.size % LOGIC_SIZE as u64) as usize;
+        if diff_size != 0 {
+            let padding = vec![0u8; LOGIC_SIZE - diff_size];
+            output_writter.write_all(&padding)?;
+        }
+
+        output_writter.seek(SeekFrom::Start(old_pos))?;
+
+        Ok(())
+    }
+}
+
+fn parse_partition_type(value: &str) -> Option<u8> {
+    match value.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+/// Parse the "N TYPE FILE" triplets given to --append-partition.
+pub fn parse_appended_partitions(values: &[String]) -> std::io::Result<Vec<AppendedPartition>> {
+    let mut res: Vec<AppendedPartition> = Vec::new();
+
+    for value in values.chunks(3) {
+        let invalid_input = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        let number: u8 = match value[0].parse() {
+            Ok(number @ 1..=4) => number,
+            _ => {
+                return Err(invalid_input(format!(
+                    "invalid appended partition number \"{}\" (expected 1 to 4)",
+                    value[0]
+                )))
+            }
+        };
+
+        if res.iter().any(|partition| partition.number == number) {
+            return Err(invalid_input(format!(
+                "appended partition {} is defined more than once",
+                number
+            )));
+        }
+
+        let partition_type = parse_partition_type(&value[1]).ok_or_else(|| {
+            invalid_input(format!("invalid appended partition type \"{}\"", value[1]))
+        })?;
+
+        res.push(AppendedPartition::from_source(
+            number,
+            partition_type,
+            &value[2],
+        )?);
+    }
+
+    Ok(res)
+}
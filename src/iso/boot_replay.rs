@@ -0,0 +1,493 @@
+use crate::iso::appended_partition;
+use crate::iso::appended_partition::AppendedPartition;
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::FileType;
+use crate::iso::gpt;
+use crate::iso::option::{BootLoadSize, Opt};
+use crate::iso::reader;
+use crate::iso::reader::{BootEntryInfo, IsoEntry, IsoReader, Namespace, VolumeDescriptorKind};
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32, SECTOR_SIZE};
+use crate::iso::{get_free_partition_number, BootCatalogEntry};
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Patch applied to the replayed MBR boot code, pointing it to the new location of the boot image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MbrPatch {
+    None,
+    Grub2,
+    Isohybrid,
+}
+
+struct ReplayedBootEntry {
+    info: BootEntryInfo,
+    path: String,
+}
+
+/// Partition after the ISO image of the source, copied as an appended partition.
+struct ReplayedPartition {
+    number: Option<u8>,
+    partition_type: u8,
+    offset: u64,
+    size: u64,
+}
+
+/// Boot setup of an existing image: El Torito catalog, System Area and partition layout.
+pub struct BootReplay {
+    image: PathBuf,
+    catalog_lba: u32,
+    catalog: Option<String>,
+    entries: Vec<ReplayedBootEntry>,
+    boot_info_table: bool,
+    grub2_boot_info: bool,
+    pub system_area: Option<Vec<u8>>,
+    pub mbr_patch: MbrPatch,
+    protective_msdos_label: bool,
+    isohybrid_gpt_basdat: bool,
+    chrp_boot: bool,
+    efi_boot_part: bool,
+    hfsplus: bool,
+    partitions: Vec<ReplayedPartition>,
+}
+
+type Reader = IsoReader<BufReader<File>>;
+
+/// Map the first block of the regular files of the tree to their path.
+fn collect_file_paths(
+    reader: &mut Reader,
+    directory: &IsoEntry,
+    namespace: Namespace,
+    prefix: &str,
+    res: &mut HashMap<u32, String>,
+    visited: &mut HashSet<u32>,
+) -> io::Result<()> {
+    reader::mark_directory_visited(visited, directory)?;
+
+    for entry in reader.read_directory(directory, namespace)? {
+        let path = format!("{}{}", prefix, entry.name);
+
+        if entry.get_symlink().is_none() && entry.is_directory() {
+            collect_file_paths(
+                reader,
+                &entry,
+                namespace,
+                &format!("{}/", path),
+                res,
+                visited,
+            )?;
+        } else if entry.is_regular_file() && !entry.extents.is_empty() {
+            res.entry(entry.extents[0].0).or_insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the path of the file of the tree copied from the given block of the image.
+fn find_copied_file(tree: &DirectoryEntry, image: &Path, lba: u32) -> Option<String> {
+    for file in &tree.files_childs {
+        if let FileType::Slice { path, offset, .. } = &file.file_type {
+            if path == image && *offset == u64::from(lba) * u64::from(LOGIC_SIZE_U32) {
+                return Some(file.get_file_name());
+            }
+        }
+    }
+
+    for child in &tree.dir_childs {
+        if let Some(path) = find_copied_file(child, image, lba) {
+            return Some(format!("{}/{}", child.get_file_name(), path));
+        }
+    }
+
+    None
+}
+
+fn get_partition_type(type_guid: &[u8; 16]) -> u8 {
+    match utils::format_guid(type_guid).as_str() {
+        gpt::EFI_SYSTEM_TYPE_GUID => 0xEF,
+        gpt::BASIC_DATA_TYPE_GUID => 0x0C,
+        appended_partition::PREP_BOOT_TYPE_GUID => 0x41,
+        _ => 0x83,
+    }
+}
+
+impl BootReplay {
+    pub fn read(image: &Path) -> io::Result<BootReplay> {
+        let mut reader = IsoReader::new(BufReader::new(File::open(image)?))?;
+
+        let (primary_volume_lba, iso_lb_count) = reader
+            .volume_descriptors
+            .iter()
+            .find_map(|volume| match &volume.kind {
+                VolumeDescriptorKind::Primary(info) => Some((volume.lba, info.volume_space_size)),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no Primary Volume Descriptor found!",
+                )
+            })?;
+        let iso_sector_count = u64::from(iso_lb_count) * u64::from(LOGIC_SIZE_U32 / SECTOR_SIZE);
+
+        let mut res = BootReplay {
+            image: image.to_path_buf(),
+            catalog_lba: 0,
+            catalog: None,
+            entries: Vec::new(),
+            boot_info_table: false,
+            grub2_boot_info: false,
+            system_area: None,
+            mbr_patch: MbrPatch::None,
+            protective_msdos_label: false,
+            isohybrid_gpt_basdat: false,
+            chrp_boot: false,
+            efi_boot_part: false,
+            hfsplus: false,
+            partitions: Vec::new(),
+        };
+
+        let namespace = reader.get_default_namespace();
+        let root = reader.get_root(namespace)?;
+        let mut file_paths = HashMap::new();
+        collect_file_paths(
+            &mut reader,
+            &root,
+            namespace,
+            "",
+            &mut file_paths,
+            &mut HashSet::new(),
+        )?;
+
+        if let Some(boot_catalog) = reader.read_boot_catalog()? {
+            res.catalog_lba = boot_catalog.lba;
+            res.catalog = file_paths.get(&boot_catalog.lba).cloned();
+
+            for entry in boot_catalog.entries {
+                let path = file_paths.get(&entry.lba).cloned().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!(
+                            "boot image at LBA {} isn't part of the directory tree!",
+                            entry.lba
+                        ),
+                    )
+                })?;
+
+                res.entries.push(ReplayedBootEntry { info: entry, path });
+            }
+        }
+
+        let bios_lba = res
+            .entries
+            .iter()
+            .find(|entry| entry.info.platform_id == 0x0)
+            .map(|entry| entry.info.lba);
+
+        // Boot info tables and GRUB 2 patches hold the location of the boot image
+        if let Some(bios_lba) = bios_lba {
+            let data = reader.read_blocks(bios_lba, 2)?;
+
+            res.boot_info_table = LittleEndian::read_u32(&data[0x8..]) == primary_volume_lba
+                && LittleEndian::read_u32(&data[0xC..]) == bios_lba;
+            res.grub2_boot_info =
+                LittleEndian::read_u64(&data[0x9F4..]) == u64::from(bios_lba) * 4 + 5;
+        }
+
+        let mut system_area = reader.read_data(0, LOGIC_SIZE * 0x10)?;
+
+        if let Some(bios_lba) = bios_lba {
+            if u64::from(LittleEndian::read_u32(&system_area[0x1B0..])) == u64::from(bios_lba) * 4
+                && LittleEndian::read_u32(&system_area[0x1B4..]) == 0x0
+            {
+                res.mbr_patch = MbrPatch::Isohybrid;
+            } else if LittleEndian::read_u64(&system_area[0x1B0..]) == u64::from(bios_lba) * 4 + 4 {
+                res.mbr_patch = MbrPatch::Grub2;
+            }
+        }
+
+        let gpt = reader.read_gpt_at(1)?;
+        if let Some(gpt) = &gpt {
+            res.isohybrid_gpt_basdat = true;
+
+            for (number, partition) in &gpt.partitions {
+                if partition.start_sector >= iso_sector_count {
+                    let range = partition
+                        .end_sector
+                        .checked_sub(partition.start_sector)
+                        .and_then(|sector_count| {
+                            Some((
+                                partition.start_sector.checked_mul(u64::from(SECTOR_SIZE))?,
+                                sector_count
+                                    .checked_add(1)?
+                                    .checked_mul(u64::from(SECTOR_SIZE))?,
+                            ))
+                        });
+                    let (offset, size) = range.ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "invalid sectors {} to {} of GPT partition {}",
+                                partition.start_sector, partition.end_sector, number
+                            ),
+                        )
+                    })?;
+
+                    res.partitions.push(ReplayedPartition {
+                        number: None,
+                        partition_type: get_partition_type(&partition.type_guid),
+                        offset,
+                        size,
+                    });
+                } else if utils::format_guid(&partition.type_guid) == gpt::APPLE_HFS_TYPE_GUID {
+                    res.hfsplus = true;
+                } else if ![gpt::BASIC_DATA_TYPE_GUID, gpt::EFI_SYSTEM_TYPE_GUID]
+                    .contains(&utils::format_guid(&partition.type_guid).as_str())
+                {
+                    eprintln!(
+                        "warning: GPT partition {} ({}) isn't replayed",
+                        number, partition.name
+                    );
+                }
+            }
+
+            // The GPT is written again
+            let entries_end = (u64::from(gpt.entry_count) * u64::from(gpt.entry_size))
+                .div_ceil(u64::from(SECTOR_SIZE))
+                .saturating_add(gpt.entries_sector)
+                .saturating_mul(u64::from(SECTOR_SIZE))
+                .clamp(u64::from(SECTOR_SIZE), system_area.len() as u64)
+                as usize;
+            system_area[SECTOR_SIZE as usize..entries_end].fill(0x0);
+        }
+
+        if let Some(mbr) = reader.read_mbr()? {
+            for (index, partition) in mbr.partitions.iter().enumerate() {
+                let partition = match partition {
+                    Some(partition) => partition,
+                    None => continue,
+                };
+
+                if u64::from(partition.start_sector) >= iso_sector_count {
+                    if gpt.is_none() {
+                        res.partitions.push(ReplayedPartition {
+                            number: Some(index as u8 + 1),
+                            partition_type: partition.partition_type,
+                            offset: u64::from(partition.start_sector) * u64::from(SECTOR_SIZE),
+                            size: u64::from(partition.sector_count) * u64::from(SECTOR_SIZE),
+                        });
+                    }
+                    continue;
+                }
+
+                match partition.partition_type {
+                    0xEE => {}
+                    0xEF => res.efi_boot_part = true,
+                    0x96 => res.chrp_boot = true,
+                    _ if index == 0 && res.mbr_patch == MbrPatch::Isohybrid => {}
+                    0x0 | 0x17 | 0x83 | 0xCD if index == 0 => res.protective_msdos_label = true,
+                    _ => eprintln!(
+                        "warning: MBR partition {} (type 0x{:02x}) isn't replayed",
+                        index + 1,
+                        partition.partition_type
+                    ),
+                }
+            }
+
+            // The partition table is written again
+            system_area[0x1BE..0x1FE].fill(0x0);
+        }
+
+        if reader
+            .read_apm()?
+            .iter()
+            .any(|partition| partition.partition_type == "Apple_HFS")
+        {
+            res.hfsplus = true;
+        }
+
+        if reader.read_sun_label()?.is_some()
+            || reader.read_sgi_volume_header()?.is_some()
+            || reader.read_dec_boot_block()?.is_some()
+        {
+            eprintln!(
+                "warning: Sun disk label, SGI volume header and DEC boot block aren't replayed"
+            );
+        }
+
+        if system_area.iter().any(|byte| *byte != 0x0) {
+            res.system_area = Some(system_area);
+        }
+
+        Ok(res)
+    }
+
+    /// Set the options needed to write the boot setup again.
+    pub fn apply_options(&self, opt: &mut Opt) -> io::Result<()> {
+        let conflicting_options = opt.eltorito_opt.has_boot_catalog()
+            || (self.system_area.is_some()
+                && (opt.embedded_boot.is_some()
+                    || opt.grub2_mbr.is_some()
+                    || opt.isohybrid_mbr.is_some()));
+        if conflicting_options {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "El Torito boot images and System Area files can't be combined with a boot replay!",
+            ));
+        }
+
+        let bios_entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.info.platform_id == 0x0);
+        let efi_entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.info.platform_id == 0xEF);
+
+        if !self.entries.is_empty() && bios_entry.is_none() && efi_entry.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "boot catalogs without x86 or EFI boot entry can't be replayed!",
+            ));
+        }
+
+        if let Some(entry) = bios_entry {
+            opt.eltorito_opt.eltorito_boot = Some(entry.path.clone());
+            opt.eltorito_opt.no_emu_boot = entry.info.media_type == 0x0;
+            opt.eltorito_opt.no_boot = !entry.info.bootable;
+            opt.eltorito_opt.boot_load_seg = entry.info.load_segment;
+            opt.eltorito_opt.boot_info_table = self.boot_info_table;
+            opt.eltorito_opt.grub2_boot_info = self.grub2_boot_info;
+            opt.boot_load_size = BootLoadSize::Sectors(entry.info.sector_count);
+        }
+
+        if let Some(entry) = efi_entry {
+            opt.eltorito_opt.efi_boot = Some(entry.path.clone());
+        }
+
+        match &self.catalog {
+            Some(catalog) => opt.eltorito_opt.eltorito_catalog = catalog.clone(),
+            None => opt.eltorito_opt.hide_boot_catalog = true,
+        }
+
+        opt.protective_msdos_label |= self.protective_msdos_label;
+        opt.isohybrid_gpt_basdat |= self.isohybrid_gpt_basdat;
+        opt.chrp_boot |= self.chrp_boot;
+        opt.hfsplus |= self.hfsplus;
+        opt.efi_boot_part |= self.efi_boot_part;
+
+        Ok(())
+    }
+
+    /// Find the boot images in the new tree and drop the old boot catalog from it.
+    pub fn locate_boot_images(
+        &mut self,
+        opt: &mut Opt,
+        tree: &mut DirectoryEntry,
+    ) -> io::Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+
+        let catalog_path =
+            find_copied_file(tree, &self.image, self.catalog_lba).or_else(|| self.catalog.clone());
+        if let Some(catalog_path) = catalog_path {
+            tree.remove_file(&catalog_path);
+        }
+
+        for entry in &mut self.entries {
+            // Boot images copied from the source follow their renames, the others are found by path
+            let path = find_copied_file(tree, &self.image, entry.info.lba)
+                .or_else(|| tree.get_file(&entry.path).map(|_| entry.path.clone()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("boot image \"{}\" not found in the ISO tree!", entry.path),
+                    )
+                })?;
+
+            if opt.eltorito_opt.eltorito_boot.as_ref() == Some(&entry.path) {
+                opt.eltorito_opt.eltorito_boot = Some(path.clone());
+            }
+            if opt.eltorito_opt.efi_boot.as_ref() == Some(&entry.path) {
+                opt.eltorito_opt.efi_boot = Some(path.clone());
+            }
+            entry.path = path;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_boot_catalog_entries(
+        &self,
+        tree: &mut DirectoryEntry,
+    ) -> io::Result<Vec<BootCatalogEntry>> {
+        let mut res = Vec::new();
+
+        for entry in &self.entries {
+            let file = tree.get_file(&entry.path).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("boot image \"{}\" not found in the ISO tree!", entry.path),
+                )
+            })?;
+
+            res.push(BootCatalogEntry {
+                platform_id: entry.info.platform_id,
+                bootable: entry.info.bootable,
+                media_type: entry.info.media_type,
+                load_segment: entry.info.load_segment,
+                system_type: entry.info.system_type,
+                sector_count: entry.info.sector_count,
+                lba: file.lba,
+            });
+        }
+
+        Ok(res)
+    }
+
+    /// Get the partitions of the source after its ISO image, to append them again.
+    pub fn get_appended_partitions(
+        &self,
+        appended_partitions: &[AppendedPartition],
+    ) -> io::Result<Vec<AppendedPartition>> {
+        let mut res: Vec<AppendedPartition> = Vec::new();
+
+        for partition in &self.partitions {
+            let used_partitions: Vec<AppendedPartition> = appended_partitions
+                .iter()
+                .chain(res.iter())
+                .cloned()
+                .collect();
+
+            let number = match partition.number {
+                Some(number) if used_partitions.iter().any(|used| used.number == number) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("replayed partition {} is already used", number),
+                    ))
+                }
+                Some(number) => number,
+                None => get_free_partition_number(&used_partitions, "replayed")?,
+            };
+
+            res.push(AppendedPartition::from_range(
+                number,
+                partition.partition_type,
+                self.image.clone(),
+                partition.offset,
+                partition.size,
+            )?);
+        }
+
+        Ok(res)
+    }
+}
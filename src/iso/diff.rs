@@ -0,0 +1,440 @@
+use crate::iso::option::DiffOpt;
+use crate::iso::reader;
+use crate::iso::reader::{
+    BootCatalogInfo, IsoEntry, IsoReader, Namespace, VolumeDescriptorKind, VolumeInfo,
+};
+use crate::iso::utils::LOGIC_SIZE;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+/// Size of the chunks compared when checking the content of two files.
+const COMPARE_CHUNK_SIZE: u64 = 0x10_0000;
+
+type Reader = IsoReader<BufReader<File>>;
+
+fn open_image(path: &std::path::Path) -> io::Result<Reader> {
+    IsoReader::new(BufReader::new(File::open(path)?))
+}
+
+/// Collect all the entries of a tree by path.
+fn read_tree(
+    reader: &mut Reader,
+    directory: &IsoEntry,
+    path: &str,
+    namespace: Namespace,
+    res: &mut BTreeMap<String, IsoEntry>,
+    visited: &mut HashSet<u32>,
+) -> io::Result<()> {
+    reader::mark_directory_visited(visited, directory)?;
+
+    for entry in reader.read_directory(directory, namespace)? {
+        let entry_path = format!("{}/{}", path, entry.name);
+
+        if entry.is_directory() {
+            read_tree(reader, &entry, &entry_path, namespace, res, visited)?;
+        }
+        res.insert(entry_path, entry);
+    }
+
+    Ok(())
+}
+
+/// Get the position and size of the data of a file in the image.
+fn get_ranges(entry: &IsoEntry) -> Vec<(u64, u64)> {
+    entry
+        .extents
+        .iter()
+        .map(|(lba, size)| (u64::from(*lba) * LOGIC_SIZE as u64, u64::from(*size)))
+        .collect()
+}
+
+fn is_content_equal(
+    old_reader: &mut Reader,
+    old_entry: &IsoEntry,
+    new_reader: &mut Reader,
+    new_entry: &IsoEntry,
+) -> io::Result<bool> {
+    if old_entry.get_size() != new_entry.get_size() {
+        return Ok(false);
+    }
+
+    let mut old_ranges = get_ranges(old_entry).into_iter();
+    let mut new_ranges = get_ranges(new_entry).into_iter();
+    let mut old_range = old_ranges.next();
+    let mut new_range = new_ranges.next();
+
+    while let (Some((old_offset, old_size)), Some((new_offset, new_size))) = (old_range, new_range)
+    {
+        let size = old_size.min(new_size).min(COMPARE_CHUNK_SIZE);
+
+        if size != 0
+            && old_reader.read_data(old_offset, size as usize)?
+                != new_reader.read_data(new_offset, size as usize)?
+        {
+            return Ok(false);
+        }
+
+        old_range = if old_size == size {
+            old_ranges.next()
+        } else {
+            Some((old_offset + size, old_size - size))
+        };
+        new_range = if new_size == size {
+            new_ranges.next()
+        } else {
+            Some((new_offset + size, new_size - size))
+        };
+    }
+
+    Ok(true)
+}
+
+fn format_entry_type(entry: &IsoEntry) -> &'static str {
+    if entry.get_symlink().is_some() {
+        "symbolic link"
+    } else if entry.is_directory() {
+        "directory"
+    } else if entry.is_regular_file() {
+        "file"
+    } else {
+        "special file"
+    }
+}
+
+fn format_timestamp(timestamp: Option<i64>) -> String {
+    timestamp.map_or_else(|| String::from("-"), |timestamp| timestamp.to_string())
+}
+
+/// Get the Rock Ridge attributes of an entry that are compared.
+fn get_attributes(entry: &IsoEntry) -> Vec<(&'static str, String)> {
+    let rock_ridge = entry.rock_ridge.clone().unwrap_or_default();
+    let format_optional = |value: Option<u32>, is_octal: bool| match (value, is_octal) {
+        (Some(value), true) => format!("{:o}", value),
+        (Some(value), false) => value.to_string(),
+        (None, _) => String::from("-"),
+    };
+
+    vec![
+        ("mode", format_optional(rock_ridge.mode, true)),
+        ("links", format_optional(rock_ridge.links, false)),
+        ("uid", format_optional(rock_ridge.uid, false)),
+        ("gid", format_optional(rock_ridge.gid, false)),
+        (
+            "symbolic link target",
+            rock_ridge.symlink.unwrap_or_else(|| String::from("-")),
+        ),
+        (
+            "modification time",
+            format_timestamp(rock_ridge.modification_time),
+        ),
+        ("access time", format_timestamp(rock_ridge.access_time)),
+    ]
+}
+
+fn get_volume_fields(volume_info: &VolumeInfo) -> Vec<(&'static str, String)> {
+    vec![
+        ("system id", volume_info.system_id.clone()),
+        ("volume id", volume_info.volume_id.clone()),
+        (
+            "volume space size",
+            volume_info.volume_space_size.to_string(),
+        ),
+        ("volume set size", volume_info.volume_set_size.to_string()),
+        (
+            "volume sequence number",
+            volume_info.volume_sequence_number.to_string(),
+        ),
+        ("path table size", volume_info.path_table_size.to_string()),
+        ("volume set id", volume_info.volume_set_id.clone()),
+        ("publisher id", volume_info.publisher_id.clone()),
+        ("data preparer id", volume_info.data_preparer_id.clone()),
+        ("application id", volume_info.application_id.clone()),
+        ("creation date", volume_info.creation_date.clone()),
+        ("modification date", volume_info.modification_date.clone()),
+        ("expiration date", volume_info.expiration_date.clone()),
+        ("effective date", volume_info.effective_date.clone()),
+    ]
+}
+
+fn get_volume_layout_fields(volume_info: &VolumeInfo) -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "type L path table LBA",
+            volume_info.l_path_table_lba.to_string(),
+        ),
+        (
+            "type M path table LBA",
+            volume_info.m_path_table_lba.to_string(),
+        ),
+        (
+            "root directory LBA",
+            volume_info.root_directory.lba.to_string(),
+        ),
+    ]
+}
+
+fn get_volume_descriptor_name(kind: &VolumeDescriptorKind) -> String {
+    match kind {
+        VolumeDescriptorKind::Boot { system_id, .. } => format!("Boot Record ({})", system_id),
+        VolumeDescriptorKind::Primary(_) => String::from("Primary Volume Descriptor"),
+        VolumeDescriptorKind::Supplementary(volume_info) if volume_info.is_joliet => {
+            String::from("Joliet Supplementary Volume Descriptor")
+        }
+        VolumeDescriptorKind::Supplementary(_) => String::from("Supplementary Volume Descriptor"),
+        VolumeDescriptorKind::Partition => String::from("Volume Partition Descriptor"),
+        VolumeDescriptorKind::End => String::from("Volume Descriptor Set Terminator"),
+        VolumeDescriptorKind::Unknown(volume_type) => {
+            format!("unknown volume descriptor type {}", volume_type)
+        }
+    }
+}
+
+fn get_boot_catalog_fields(catalog: &Option<BootCatalogInfo>, layout: bool) -> Vec<String> {
+    let catalog = match catalog {
+        Some(catalog) => catalog,
+        None => return Vec::new(),
+    };
+
+    let mut res = vec![format!(
+        "validation entry: platform 0x{:02X}, id \"{}\"",
+        catalog.platform_id, catalog.id_string
+    )];
+    if layout {
+        res.push(format!("catalog LBA {}", catalog.lba));
+    }
+
+    for (index, entry) in catalog.entries.iter().enumerate() {
+        res.push(format!(
+            "entry {}: platform 0x{:02X}, {}, media type 0x{:02X}, load segment 0x{:04X}, system type 0x{:02X}, {} virtual sectors",
+            index,
+            entry.platform_id,
+            if entry.bootable { "bootable" } else { "not bootable" },
+            entry.media_type,
+            entry.load_segment,
+            entry.system_type,
+            entry.sector_count
+        ));
+        if layout {
+            res.push(format!("entry {}: image LBA {}", index, entry.lba));
+        }
+    }
+
+    res
+}
+
+/// Print the differences between two lists of named values.
+fn print_field_changes(
+    prefix: &str,
+    old_fields: &[(&'static str, String)],
+    new_fields: &[(&'static str, String)],
+) -> bool {
+    let mut res = false;
+
+    for ((name, old_value), (_, new_value)) in old_fields.iter().zip(new_fields) {
+        if old_value != new_value {
+            println!(
+                "~ {}: {} \"{}\" -> \"{}\"",
+                prefix, name, old_value, new_value
+            );
+            res = true;
+        }
+    }
+
+    res
+}
+
+fn diff_volume_descriptors(old_reader: &Reader, new_reader: &Reader, layout: bool) -> bool {
+    let mut res = false;
+
+    let old_names: Vec<String> = old_reader
+        .volume_descriptors
+        .iter()
+        .map(|volume| get_volume_descriptor_name(&volume.kind))
+        .collect();
+    let new_names: Vec<String> = new_reader
+        .volume_descriptors
+        .iter()
+        .map(|volume| get_volume_descriptor_name(&volume.kind))
+        .collect();
+
+    if old_names != new_names {
+        println!(
+            "~ volume descriptor set: [{}] -> [{}]",
+            old_names.join(", "),
+            new_names.join(", ")
+        );
+        res = true;
+    }
+
+    for namespace in [Namespace::Iso, Namespace::Joliet] {
+        let prefix = match namespace {
+            Namespace::Joliet => "Joliet Supplementary Volume Descriptor",
+            _ => "Primary Volume Descriptor",
+        };
+
+        if let (Some(old_volume), Some(new_volume)) = (
+            old_reader.get_volume(namespace),
+            new_reader.get_volume(namespace),
+        ) {
+            res |= print_field_changes(
+                prefix,
+                &get_volume_fields(old_volume),
+                &get_volume_fields(new_volume),
+            );
+
+            if layout {
+                res |= print_field_changes(
+                    prefix,
+                    &get_volume_layout_fields(old_volume),
+                    &get_volume_layout_fields(new_volume),
+                );
+            }
+        }
+    }
+
+    res
+}
+
+fn diff_boot_catalogs(
+    old_reader: &mut Reader,
+    new_reader: &mut Reader,
+    layout: bool,
+) -> io::Result<bool> {
+    let old_fields = get_boot_catalog_fields(&old_reader.read_boot_catalog()?, layout);
+    let new_fields = get_boot_catalog_fields(&new_reader.read_boot_catalog()?, layout);
+
+    let mut res = false;
+    for field in old_fields
+        .iter()
+        .filter(|field| !new_fields.contains(field))
+    {
+        println!("- boot catalog {}", field);
+        res = true;
+    }
+    for field in new_fields
+        .iter()
+        .filter(|field| !old_fields.contains(field))
+    {
+        println!("+ boot catalog {}", field);
+        res = true;
+    }
+
+    Ok(res)
+}
+
+fn diff_trees(old_reader: &mut Reader, new_reader: &mut Reader, layout: bool) -> io::Result<bool> {
+    // Compare the richest namespace available in both images
+    let namespace = [Namespace::RockRidge, Namespace::Joliet]
+        .iter()
+        .copied()
+        .find(|namespace| {
+            old_reader.has_namespace(*namespace) && new_reader.has_namespace(*namespace)
+        })
+        .unwrap_or(Namespace::Iso);
+
+    let mut old_tree = BTreeMap::new();
+    let old_root = old_reader.get_root(namespace)?;
+    read_tree(
+        old_reader,
+        &old_root,
+        "",
+        namespace,
+        &mut old_tree,
+        &mut HashSet::new(),
+    )?;
+
+    let mut new_tree = BTreeMap::new();
+    let new_root = new_reader.get_root(namespace)?;
+    read_tree(
+        new_reader,
+        &new_root,
+        "",
+        namespace,
+        &mut new_tree,
+        &mut HashSet::new(),
+    )?;
+
+    let mut res = false;
+
+    for path in old_tree.keys().filter(|path| !new_tree.contains_key(*path)) {
+        println!("- {}", path);
+        res = true;
+    }
+
+    for (path, new_entry) in &new_tree {
+        let old_entry = match old_tree.get(path) {
+            Some(old_entry) => old_entry,
+            None => {
+                println!("+ {}", path);
+                res = true;
+                continue;
+            }
+        };
+
+        if format_entry_type(old_entry) != format_entry_type(new_entry) {
+            println!(
+                "~ {}: {} -> {}",
+                path,
+                format_entry_type(old_entry),
+                format_entry_type(new_entry)
+            );
+            res = true;
+            continue;
+        }
+
+        if new_entry.is_regular_file()
+            && new_entry.get_symlink().is_none()
+            && !is_content_equal(old_reader, old_entry, new_reader, new_entry)?
+        {
+            println!(
+                "~ {}: content differs ({} -> {} bytes)",
+                path,
+                old_entry.get_size(),
+                new_entry.get_size()
+            );
+            res = true;
+        }
+
+        res |= print_field_changes(path, &get_attributes(old_entry), &get_attributes(new_entry));
+
+        let get_lbas =
+            |entry: &IsoEntry| -> Vec<u32> { entry.extents.iter().map(|(lba, _)| *lba).collect() };
+        if layout && get_lbas(old_entry) != get_lbas(new_entry) {
+            let format_lbas = |lbas: Vec<u32>| -> String {
+                lbas.iter()
+                    .map(|lba| lba.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+
+            println!(
+                "~ {}: moved from LBA {} to LBA {}",
+                path,
+                format_lbas(get_lbas(old_entry)),
+                format_lbas(get_lbas(new_entry))
+            );
+            res = true;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Compare two ISO images at the filesystem level and optionally at the layout level, like "diff -r".
+pub fn diff_iso(diff_opt: &DiffOpt) -> io::Result<()> {
+    let mut old_reader = open_image(&diff_opt.old)?;
+    let mut new_reader = open_image(&diff_opt.new)?;
+
+    let mut has_differences = diff_volume_descriptors(&old_reader, &new_reader, diff_opt.layout);
+    has_differences |= diff_boot_catalogs(&mut old_reader, &mut new_reader, diff_opt.layout)?;
+    has_differences |= diff_trees(&mut old_reader, &mut new_reader, diff_opt.layout)?;
+
+    if !has_differences {
+        println!("no difference");
+    }
+
+    Ok(())
+}
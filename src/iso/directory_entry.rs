@@ -12,6 +12,12 @@ use std::io::Cursor;
 use std::io::SeekFrom;
 use std::path::PathBuf;
 
+/// Child of a directory having a directory record, by its index in the directory or file children.
+enum ChildRecord {
+    Directory(usize),
+    File(usize),
+}
+
 #[derive(Debug, Clone)]
 pub struct DirectoryEntry {
     pub path_table_index: u32,
@@ -206,6 +212,11 @@ impl DirectoryEntry {
         res
     }
 
+    /// Get the number of LB of each path table, the M path table following the L one.
+    pub fn get_path_table_lb_count(&self) -> u32 {
+        self.get_path_table_size().div_ceil(LOGIC_SIZE_U32)
+    }
+
     pub fn get_extent_size_in_lb(&self) -> u32 {
         let mut res = 1u32;
         let mut size = 0u32;
@@ -213,8 +224,11 @@ impl DirectoryEntry {
         size += self.get_entry_size(Some(3)); // '.'
         size += self.get_entry_size(Some(2)); // '..'
 
-        for entry in &self.dir_childs {
-            let entry_size = entry.get_entry_size(Some(0)) as i32;
+        for child in self.get_child_records() {
+            let entry_size = match child {
+                ChildRecord::Directory(index) => self.dir_childs[index].get_entry_size(Some(0)),
+                ChildRecord::File(index) => self.files_childs[index].get_entry_size(),
+            } as i32;
             let expected_aligned_size = utils::align_up(size as i32, LOGIC_SIZE_U32 as i32);
             let available_size_in_lb = expected_aligned_size - size as i32;
 
@@ -226,20 +240,31 @@ impl DirectoryEntry {
             size += entry_size as u32;
         }
 
-        for entry in &self.files_childs {
-            let entry_size = entry.get_entry_size() as i32;
-            let expected_aligned_size = utils::align_up(size as i32, LOGIC_SIZE_U32 as i32);
-            let available_size_in_lb = expected_aligned_size - size as i32;
-
-            if entry_size > available_size_in_lb && available_size_in_lb != 0 {
-                size = 0;
-                res += 1;
-            }
+        res
+    }
 
-            size += entry_size as u32;
-        }
+    /// Get the children having a directory record, sorted by identifier (ECMA-119 9.3).
+    fn get_child_records(&self) -> Vec<ChildRecord> {
+        let directories = self.dir_childs.iter().enumerate().map(|(index, child)| {
+            let identifier = utils::convert_name(&child.get_file_name());
+            (identifier, ChildRecord::Directory(index))
+        });
 
-        res
+        let files = self
+            .files_childs
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| !child.hidden)
+            .map(|(index, child)| {
+                let mut identifier = utils::convert_name(&child.get_file_name());
+                identifier.extend_from_slice(b";1");
+                (identifier, ChildRecord::File(index))
+            });
+
+        let mut res: Vec<(Vec<u8>, ChildRecord)> = directories.chain(files).collect();
+        res.sort_by(|(a, _), (b, _)| utils::compare_identifiers(a, b));
+
+        res.into_iter().map(|(_, child)| child).collect()
     }
 
     pub fn get_entry_size(&self, directory_type: Option<u32>) -> u32 {
@@ -285,22 +310,29 @@ impl DirectoryEntry {
         Ok(())
     }
 
-    fn write_path_table_childs<T, Order: ByteOrder>(
-        &mut self,
+    /// Write the path table records of the directories found `depth` levels below this one, returning false when there is none.
+    fn write_path_table_level<T, Order: ByteOrder>(
+        &self,
         output_writter: &mut T,
-    ) -> std::io::Result<()>
+        depth: u32,
+    ) -> std::io::Result<bool>
     where
         T: Write,
     {
-        for entry in &mut self.dir_childs {
-            DirectoryEntry::write_path_table_entry::<T, Order>(entry, output_writter, 0)?;
+        if depth == 0 {
+            for entry in &self.dir_childs {
+                DirectoryEntry::write_path_table_entry::<T, Order>(entry, output_writter, 0)?;
+            }
+
+            return Ok(!self.dir_childs.is_empty());
         }
 
-        for entry in &mut self.dir_childs {
-            entry.write_path_table_childs::<T, Order>(output_writter)?;
+        let mut res = false;
+        for entry in &self.dir_childs {
+            res |= entry.write_path_table_level::<T, Order>(output_writter, depth - 1)?;
         }
 
-        Ok(())
+        Ok(res)
     }
 
     pub fn write_path_table<T, Order: ByteOrder>(
@@ -321,7 +353,11 @@ impl DirectoryEntry {
         // Write root
         DirectoryEntry::write_path_table_entry::<T, Order>(self, output_writter, 1)?;
 
-        self.write_path_table_childs::<T, Order>(output_writter)?;
+        // Records are sorted by level (ECMA-119 9.4.3)
+        let mut depth = 0;
+        while self.write_path_table_level::<T, Order>(output_writter, depth)? {
+            depth += 1;
+        }
 
         // Pad to LBA size
         let current_pos = output_writter.seek(SeekFrom::Current(0))? as usize;
@@ -383,13 +419,15 @@ impl DirectoryEntry {
         // FIXME: dirty
         let self_clone = self.clone();
 
-        for child_directory in &mut self.dir_childs {
-            child_directory.write_one(output_writter)?;
-            child_directory.write_extent(output_writter, Some(&self_clone))?;
-        }
-
-        for child_file in &mut self.files_childs {
-            child_file.write_entry(output_writter)?;
+        for child in self.get_child_records() {
+            match child {
+                ChildRecord::Directory(index) => {
+                    let child_directory = &mut self.dir_childs[index];
+                    child_directory.write_one(output_writter)?;
+                    child_directory.write_extent(output_writter, Some(&self_clone))?;
+                }
+                ChildRecord::File(index) => self.files_childs[index].write_entry(output_writter)?,
+            }
         }
 
         // Pad to LBA size
@@ -500,8 +538,53 @@ impl DirectoryEntry {
         res
     }
 
+    /// Move the extents of the tree, used to address it relatively to another origin.
+    pub fn shift_lba(&mut self, directory_shift: i64, file_shift: i64) {
+        self.lba = (i64::from(self.lba) + directory_shift) as u32;
+
+        for child_file in &mut self.files_childs {
+            child_file.lba = (i64::from(child_file.lba) + file_shift) as u32;
+        }
+
+        for child_directory in &mut self.dir_childs {
+            child_directory.shift_lba(directory_shift, file_shift);
+        }
+    }
+
+    pub fn get_or_create_directory(
+        &mut self,
+        dir_name: &str,
+    ) -> std::io::Result<&mut DirectoryEntry> {
+        let position = self
+            .dir_childs
+            .iter()
+            .position(|child| child.get_file_name() == dir_name);
+
+        let index = match position {
+            Some(index) => index,
+            None => {
+                let mut new_dir = DirectoryEntry::new()?;
+                new_dir.path = PathBuf::from(dir_name);
+                self.add_directory(new_dir)
+            }
+        };
+
+        Ok(&mut self.dir_childs[index])
+    }
+
+    /// Insert the directory at its sorted position (ECMA-119 9.3), returning its index.
+    pub fn add_directory(&mut self, directory: DirectoryEntry) -> usize {
+        let dir_name = directory.get_file_name();
+        let index = self
+            .dir_childs
+            .partition_point(|child| child.get_file_name() < dir_name);
+
+        self.dir_childs.insert(index, directory);
+        index
+    }
+
     pub fn get_file(&mut self, path: &str) -> Option<&mut FileEntry> {
-        let mut cut_path: Vec<&str> = path.split('/').collect();
+        let mut cut_path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
 
         let mut directory_entry: Option<&mut DirectoryEntry> = Some(self);
 
@@ -534,9 +617,61 @@ impl DirectoryEntry {
         res
     }
 
+    pub fn remove_file(&mut self, path: &str) -> Option<FileEntry> {
+        let mut cut_path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        let file_name = cut_path.pop()?;
+
+        let mut directory_entry = self;
+        for dir_name in cut_path {
+            directory_entry = directory_entry.get_directory(dir_name)?;
+        }
+
+        let index = directory_entry
+            .files_childs
+            .iter()
+            .position(|child| child.get_file_name() == file_name)?;
+        Some(directory_entry.files_childs.remove(index))
+    }
+
+    /// Insert the file at its sorted position (ECMA-119 9.3), replacing the file of the same name.
     pub fn add_file(&mut self, file: FileEntry) -> &FileEntry {
-        self.files_childs.push(file);
-        self.files_childs.last().unwrap()
+        let file_name = file.get_file_name();
+        let index = self
+            .files_childs
+            .partition_point(|child| child.get_file_name() < file_name);
+
+        if self
+            .files_childs
+            .get(index)
+            .is_some_and(|child| child.get_file_name() == file_name)
+        {
+            self.files_childs[index] = file;
+        } else {
+            self.files_childs.insert(index, file);
+        }
+
+        &self.files_childs[index]
+    }
+
+    pub fn add_file_at(&mut self, path: &str, mut file: FileEntry) -> std::io::Result<&FileEntry> {
+        let mut cut_path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        let file_name = cut_path.pop().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid file path \"{}\"!", path),
+            )
+        })?;
+
+        let mut directory_entry = self;
+        for dir_name in cut_path {
+            directory_entry = directory_entry.get_or_create_directory(dir_name)?;
+        }
+
+        if let FileType::Buffer { name, .. } = &mut file.file_type {
+            *name = String::from(file_name);
+        }
+
+        Ok(directory_entry.add_file(file))
     }
 
     fn add_and_merge_childs_directories(
@@ -601,8 +736,9 @@ impl DirectoryEntry {
                     file_type: FileType::Regular { path: entry.path() },
                     size: entry_meta.len() as usize,
                     lba: 0,
-                    aligned_size: utils::align_up(entry_meta.len() as i32, LOGIC_SIZE_U32 as i32)
-                        as usize,
+                    aligned_size: entry_meta.len().div_ceil(LOGIC_SIZE as u64) as usize
+                        * LOGIC_SIZE,
+                    hidden: false,
                 })
             }
         }
@@ -0,0 +1,172 @@
+use crate::iso;
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::{FileEntry, FileType};
+use crate::iso::import;
+use crate::iso::interval::IntervalSource;
+use crate::iso::option::{EditOpt, Opt};
+use crate::iso::utils::LOGIC_SIZE;
+
+use structopt::StructOpt;
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+enum TreeEntry {
+    File(FileEntry),
+    Directory(DirectoryEntry),
+}
+
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/').filter(|part| !part.is_empty()).collect()
+}
+
+fn parse_path_pair<'a>(value: &'a str, option_name: &str) -> io::Result<(&'a str, &'a str)> {
+    value
+        .split_once('=')
+        .filter(|(first, second)| !split_path(first).is_empty() && !second.is_empty())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid value \"{}\" for --{}", value, option_name),
+            )
+        })
+}
+
+fn take_entry(tree: &mut DirectoryEntry, path: &str) -> Option<TreeEntry> {
+    let mut cut_path = split_path(path);
+    let name = cut_path.pop()?;
+
+    let mut directory = tree;
+    for dir_name in cut_path {
+        directory = directory.get_directory(dir_name)?;
+    }
+
+    if let Some(index) = directory
+        .files_childs
+        .iter()
+        .position(|file| file.get_file_name() == name)
+    {
+        return Some(TreeEntry::File(directory.files_childs.remove(index)));
+    }
+
+    directory
+        .dir_childs
+        .iter()
+        .position(|child| child.get_file_name() == name)
+        .map(|index| TreeEntry::Directory(directory.dir_childs.remove(index)))
+}
+
+/// Put the entry at the given path of the tree, replacing what is already there.
+fn insert_entry(tree: &mut DirectoryEntry, path: &str, entry: TreeEntry) -> io::Result<()> {
+    take_entry(tree, path);
+
+    let mut cut_path = split_path(path);
+    let name = cut_path.pop().unwrap();
+
+    let mut directory = tree;
+    for dir_name in cut_path {
+        directory = directory.get_or_create_directory(dir_name)?;
+    }
+
+    match entry {
+        TreeEntry::File(mut file) => {
+            file.set_file_name(name);
+            directory.add_file(file);
+        }
+        TreeEntry::Directory(mut child) => {
+            child.path = PathBuf::from(name);
+            directory.add_directory(child);
+        }
+    }
+
+    Ok(())
+}
+
+fn load_disk_entry(disk_path: &Path) -> io::Result<TreeEntry> {
+    if let Some(value) = disk_path
+        .to_str()
+        .filter(|value| IntervalSource::is_interval(value))
+    {
+        let interval = IntervalSource::parse(value)?;
+        return Ok(TreeEntry::File(interval.to_file_entry(value)?));
+    }
+
+    let metadata = fs::metadata(disk_path)?;
+
+    if metadata.is_dir() {
+        let mut res = DirectoryEntry::new()?;
+        res.set_path(&[disk_path.to_path_buf()])?;
+        Ok(TreeEntry::Directory(res))
+    } else {
+        Ok(TreeEntry::File(FileEntry {
+            file_type: FileType::Regular {
+                path: disk_path.to_path_buf(),
+            },
+            size: metadata.len() as usize,
+            lba: 0,
+            aligned_size: metadata.len().div_ceil(LOGIC_SIZE as u64) as usize * LOGIC_SIZE,
+            hidden: false,
+        }))
+    }
+}
+
+fn apply_edits(tree: &mut DirectoryEntry, edit_opt: &EditOpt) -> io::Result<()> {
+    let not_found = |path: &str| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("\"{}\" not found in the ISO tree!", path),
+        )
+    };
+
+    for path in &edit_opt.remove {
+        take_entry(tree, path).ok_or_else(|| not_found(path))?;
+    }
+
+    for value in &edit_opt.rename {
+        let (old_path, new_path) = parse_path_pair(value, "rename")?;
+        let entry = take_entry(tree, old_path).ok_or_else(|| not_found(old_path))?;
+        insert_entry(tree, new_path, entry)?;
+    }
+
+    for value in &edit_opt.add {
+        let (iso_path, disk_path) = parse_path_pair(value, "add")?;
+        let entry = load_disk_entry(Path::new(disk_path))?;
+        insert_entry(tree, iso_path, entry)?;
+    }
+
+    Ok(())
+}
+
+pub fn edit_iso(edit_opt: &EditOpt) -> io::Result<()> {
+    // The content of the unchanged files is read from the source image while writing
+    if edit_opt.output.exists()
+        && fs::canonicalize(&edit_opt.output)? == fs::canonicalize(&edit_opt.input)?
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the output file can't be the ISO image to modify!",
+        ));
+    }
+
+    // Like the input directory of a new image, the root gets the path of its source
+    let mut tree = DirectoryEntry::new()?;
+    tree.path = edit_opt.input.clone();
+    import::import_tree(
+        &mut tree,
+        &edit_opt.input,
+        0,
+        import::ImportMode::CopyExtents,
+    )?;
+
+    apply_edits(&mut tree, edit_opt)?;
+
+    // The boot setup of the source is replayed on the new tree
+    let mut args: Vec<OsString> = vec!["mkisofs-rs".into(), "-o".into()];
+    args.push(edit_opt.output.clone().into_os_string());
+
+    let mut opt = Opt::from_iter(args);
+    opt.boot_replay = Some(edit_opt.input.clone());
+    iso::write_image(&mut opt, tree)
+}
@@ -0,0 +1,366 @@
+use crate::iso::reader;
+use crate::iso::reader::{IsoEntry, IsoReader, Namespace};
+use crate::iso::utils;
+
+use std::collections::HashSet;
+use std::fs;
+use std::fs::{File, FileTimes};
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+fn get_system_time(timestamp: i64) -> SystemTime {
+    if timestamp >= 0 {
+        UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+    }
+}
+
+/// Check if files created in `directory` can be given away, which requires running as root.
+#[cfg(unix)]
+fn can_restore_ownership(directory: &Path) -> io::Result<bool> {
+    let probe_path = directory.join(format!(
+        ".mkisofs-rs-probe-{:016x}",
+        utils::generate_random_u64()
+    ));
+
+    File::create(&probe_path)?;
+    let uid = fs::metadata(&probe_path).map(|metadata| metadata.uid());
+    fs::remove_file(&probe_path)?;
+
+    Ok(uid? == 0)
+}
+
+#[cfg(not(unix))]
+fn can_restore_ownership(_directory: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Reject names that would escape the output directory.
+fn check_entry_name(name: &str) -> io::Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') || name.contains('\0') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid file name \"{}\" in the ISO image", name),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Create the directory `path`, replacing what an earlier entry of the same name left there.
+///
+/// A symbolic link is removed instead of being followed, which could escape the output directory.
+fn create_directory(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => return Ok(()),
+        Ok(_) => fs::remove_file(path)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    fs::create_dir(path)
+}
+
+/// Create the parent directories of `relative_path` in `output_directory`, refusing to follow symbolic links.
+fn create_parent_directories(output_directory: &Path, relative_path: &Path) -> io::Result<()> {
+    let mut path = output_directory.to_path_buf();
+
+    for component in relative_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+    {
+        let name = component.as_os_str().to_string_lossy();
+        check_entry_name(&name)?;
+        path.push(component);
+
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => {}
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "refusing to extract through the symbolic link \"{}\"",
+                        path.display()
+                    ),
+                ))
+            }
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("\"{}\" exists and isn't a directory", path.display()),
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => fs::create_dir(&path)?,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+struct Extractor<R> {
+    reader: IsoReader<R>,
+    namespace: Namespace,
+    restore_ownership: bool,
+    visited: HashSet<u32>,
+}
+
+impl<R> Extractor<R>
+where
+    R: io::Read + io::Seek,
+{
+    /// Restore the ownership, timestamps and mode of an extracted file or directory.
+    fn restore_attributes(&self, entry: &IsoEntry, path: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        if let (true, Some(rock_ridge)) = (self.restore_ownership, &entry.rock_ridge) {
+            std::os::unix::fs::chown(path, rock_ridge.uid, rock_ridge.gid)?;
+        }
+
+        // Set the timestamps before the mode as it may make the file unreadable
+        let times = FileTimes::new()
+            .set_accessed(get_system_time(entry.get_access_time()))
+            .set_modified(get_system_time(entry.get_modification_time()));
+        File::open(path)?.set_times(times)?;
+
+        #[cfg(unix)]
+        if let Some(mode) = entry
+            .rock_ridge
+            .as_ref()
+            .and_then(|rock_ridge| rock_ridge.mode)
+        {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))?;
+        }
+
+        Ok(())
+    }
+
+    fn extract_symlink(&self, entry: &IsoEntry, target: &str, path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path).is_ok() {
+            fs::remove_file(path)?;
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(target, path)?;
+
+            if let (true, Some(rock_ridge)) = (self.restore_ownership, &entry.rock_ridge) {
+                std::os::unix::fs::lchown(path, rock_ridge.uid, rock_ridge.gid)?;
+            }
+        }
+
+        #[cfg(not(unix))]
+        eprintln!(
+            "warning: skipping symbolic link \"{}\" -> \"{}\" ({})",
+            path.display(),
+            target,
+            entry.name
+        );
+
+        Ok(())
+    }
+
+    fn extract_children(&mut self, directory: &IsoEntry, path: &Path) -> io::Result<()> {
+        reader::mark_directory_visited(&mut self.visited, directory)?;
+
+        for child in self.reader.read_directory(directory, self.namespace)? {
+            check_entry_name(&child.name)?;
+            self.extract_entry(&child, &path.join(&child.name))?;
+        }
+
+        Ok(())
+    }
+
+    fn extract_entry(&mut self, entry: &IsoEntry, path: &Path) -> io::Result<()> {
+        if let Some(target) = entry.get_symlink() {
+            let target = target.to_string();
+            return self.extract_symlink(entry, &target, path);
+        }
+
+        if entry.is_directory() {
+            create_directory(path)?;
+            self.extract_children(entry, path)?;
+        } else if entry.is_regular_file() {
+            if fs::symlink_metadata(path).is_ok() {
+                fs::remove_file(path)?;
+            }
+
+            let mut output_writter = BufWriter::new(File::create(path)?);
+            self.reader.read_content(entry, &mut output_writter)?;
+            output_writter.into_inner()?.sync_all()?;
+        } else {
+            eprintln!("warning: skipping special file \"{}\"", path.display());
+            return Ok(());
+        }
+
+        self.restore_attributes(entry, path)
+    }
+}
+
+/// Extract the given paths of an ISO image (all of it if empty) to `output_directory`.
+///
+/// Rock Ridge names and attributes are used when present, otherwise Joliet or plain ISO 9660 names.
+pub fn extract_iso(input: &Path, output_directory: &Path, paths: &[String]) -> io::Result<()> {
+    let file = File::open(input)?;
+    let reader = IsoReader::new(BufReader::new(file))?;
+
+    fs::create_dir_all(output_directory)?;
+
+    let mut extractor = Extractor {
+        namespace: reader.get_default_namespace(),
+        reader,
+        restore_ownership: can_restore_ownership(output_directory)?,
+        visited: HashSet::new(),
+    };
+
+    if paths.is_empty() {
+        // The output directory itself keeps its attributes
+        let root = extractor.reader.get_root(extractor.namespace)?;
+        return extractor.extract_children(&root, output_directory);
+    }
+
+    for path in paths {
+        // The given paths may overlap, only a directory met twice in the same walk is an error
+        extractor.visited.clear();
+
+        let entry = extractor
+            .reader
+            .find_entry(path, extractor.namespace)?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("\"{}\" not found in the ISO image", path),
+                )
+            })?;
+
+        let relative_path = path.trim_matches('/');
+        let destination = output_directory.join(relative_path);
+        create_parent_directories(output_directory, Path::new(relative_path))?;
+
+        if relative_path.is_empty() {
+            extractor.extract_children(&entry, &destination)?;
+        } else {
+            extractor.extract_entry(&entry, &destination)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::iso;
+    use crate::iso::option::Opt;
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use structopt::StructOpt;
+
+    fn create_source_tree(source_directory: &Path) {
+        fs::create_dir_all(source_directory.join("boot/grub")).unwrap();
+        fs::create_dir_all(source_directory.join("empty")).unwrap();
+        fs::write(source_directory.join("readme.txt"), b"hello").unwrap();
+        fs::write(
+            source_directory.join("boot/grub/grub.cfg"),
+            b"set timeout=5\n",
+        )
+        .unwrap();
+        fs::write(
+            source_directory.join("a long file name.data"),
+            vec![0x42; 5000],
+        )
+        .unwrap();
+        fs::write(source_directory.join("zero"), b"").unwrap();
+    }
+
+    fn check_same_tree(expected_directory: &Path, directory: &Path) {
+        let mut expected_names: Vec<_> = fs::read_dir(expected_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        let mut names: Vec<_> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        expected_names.sort();
+        names.sort();
+        assert_eq!(expected_names, names);
+
+        for name in names {
+            let expected_path = expected_directory.join(&name);
+            let path = directory.join(&name);
+            if expected_path.is_dir() {
+                check_same_tree(&expected_path, &path);
+            } else {
+                assert_eq!(fs::read(expected_path).unwrap(), fs::read(path).unwrap());
+            }
+        }
+    }
+
+    fn create_image(test_directory: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+        let source_directory = test_directory.join("source");
+        let image = test_directory.join("test.iso");
+        create_source_tree(&source_directory);
+
+        let mut opt = Opt::from_iter(vec![
+            "mkisofs-rs",
+            "-o",
+            image.to_str().unwrap(),
+            source_directory.to_str().unwrap(),
+        ]);
+        iso::create_iso(&mut opt).unwrap();
+
+        (source_directory, image)
+    }
+
+    #[test]
+    fn extract_created_image() {
+        let test_directory = utils::create_test_directory("extract");
+        let (source_directory, image) = create_image(&test_directory);
+
+        let output_directory = test_directory.join("all");
+        extract_iso(&image, &output_directory, &[]).unwrap();
+        check_same_tree(&source_directory, &output_directory);
+
+        let output_directory = test_directory.join("some");
+        extract_iso(&image, &output_directory, &[String::from("/boot/grub/")]).unwrap();
+        check_same_tree(
+            &source_directory.join("boot"),
+            &output_directory.join("boot"),
+        );
+        assert!(extract_iso(&image, &output_directory, &[String::from("missing")]).is_err());
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+
+    #[test]
+    fn reject_looping_directories() {
+        let test_directory = utils::create_test_directory("extract-loop");
+        let (_, image) = create_image(&test_directory);
+
+        // Make the "boot" directory record point to the root directory
+        let mut data = fs::read(&image).unwrap();
+        let root_lba = LittleEndian::read_u32(&data[0x8000 + 156 + 2..]);
+
+        let mut offset = root_lba as usize * utils::LOGIC_SIZE;
+        while &data[offset + 33..offset + 33 + usize::from(data[offset + 32])] != b"boot" {
+            offset += usize::from(data[offset]);
+            assert!(data[offset] != 0x0);
+        }
+        data[offset + 2..offset + 6].copy_from_slice(&root_lba.to_le_bytes());
+        data[offset + 6..offset + 10].copy_from_slice(&root_lba.to_be_bytes());
+        fs::write(&image, data).unwrap();
+
+        let error = extract_iso(&image, &test_directory.join("output"), &[]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+}
@@ -0,0 +1,481 @@
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, SECTOR_SIZE};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::prelude::*;
+
+use std::io;
+use std::io::prelude::*;
+
+/// Clusters are the size of a LB.
+const SECTORS_PER_CLUSTER: u32 = (LOGIC_SIZE as u32) / SECTOR_SIZE;
+const CLUSTER_SIZE: usize = LOGIC_SIZE;
+
+const RESERVED_SECTOR_COUNT: u32 = 1;
+const FAT_COUNT: u32 = 2;
+const ROOT_ENTRY_COUNT: u32 = 512;
+const DIRECTORY_ENTRY_SIZE: usize = 0x20;
+
+const FAT12_MAX_CLUSTER_COUNT: u32 = 4084;
+const FAT16_MAX_CLUSTER_COUNT: u32 = 65524;
+
+// Directory entry attributes
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+
+enum FatNode {
+    Directory { name: String, childs: Vec<FatNode> },
+    File { name: String, data: Vec<u8> },
+}
+
+impl FatNode {
+    fn get_name(&self) -> &str {
+        match self {
+            FatNode::Directory { name, .. } => name,
+            FatNode::File { name, .. } => name,
+        }
+    }
+
+    fn get_cluster_count(&self) -> u32 {
+        match self {
+            FatNode::Directory { childs, .. } => {
+                get_directory_cluster_count(childs)
+                    + childs
+                        .iter()
+                        .map(|child| child.get_cluster_count())
+                        .sum::<u32>()
+            }
+            FatNode::File { data, .. } => data.len().div_ceil(CLUSTER_SIZE) as u32,
+        }
+    }
+}
+
+/// Count of clusters used by the entries of a sub directory (including "." and "..").
+fn get_directory_cluster_count(childs: &[FatNode]) -> u32 {
+    ((childs.len() + 2) * DIRECTORY_ENTRY_SIZE).div_ceil(CLUSTER_SIZE) as u32
+}
+
+fn add_node(childs: &mut Vec<FatNode>, path: &[&str], data: Vec<u8>) {
+    if path.len() == 1 {
+        childs.push(FatNode::File {
+            name: String::from(path[0]),
+            data,
+        });
+        return;
+    }
+
+    let position = childs.iter().position(|child| match child {
+        FatNode::Directory { name, .. } => name.eq_ignore_ascii_case(path[0]),
+        _ => false,
+    });
+
+    let index = match position {
+        Some(index) => index,
+        None => {
+            childs.push(FatNode::Directory {
+                name: String::from(path[0]),
+                childs: Vec::new(),
+            });
+            childs.len() - 1
+        }
+    };
+
+    if let FatNode::Directory { childs, .. } = &mut childs[index] {
+        add_node(childs, &path[1..], data);
+    }
+}
+
+/// Convert a name to a 8.3 short name, long file names aren't supported.
+fn get_short_name(name: &str) -> io::Result<[u8; 11]> {
+    let (base, extension) = match name.rfind('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, ""),
+    };
+
+    let is_valid_char = |c: char| c.is_ascii_alphanumeric() || "$%'-_@~`!(){}^#&".contains(c);
+
+    if base.is_empty()
+        || base.len() > 8
+        || extension.len() > 3
+        || !base.chars().chain(extension.chars()).all(is_valid_char)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("\"{}\" isn't a valid FAT 8.3 file name!", name),
+        ));
+    }
+
+    let mut res: [u8; 11] = [b' '; 11];
+    res[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    res[8..8 + extension.len()].copy_from_slice(extension.to_ascii_uppercase().as_bytes());
+
+    Ok(res)
+}
+
+fn write_directory_entry(
+    output_writter: &mut Vec<u8>,
+    short_name: &[u8; 11],
+    attributes: u8,
+    cluster: u32,
+    size: u32,
+) -> io::Result<()> {
+    output_writter.write_all(short_name)?;
+    output_writter.write_u8(attributes)?;
+
+    // Reserved + creation time (tenths of second)
+    output_writter.write_u8(0x0)?;
+    output_writter.write_u8(0x0)?;
+
+    let now = Local::now();
+    let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+    let date = (((now.year() - 1980) << 9) as u32 | (now.month() << 5) | now.day()) as u16;
+
+    // Creation time and date, last access date
+    output_writter.write_u16::<LittleEndian>(time)?;
+    output_writter.write_u16::<LittleEndian>(date)?;
+    output_writter.write_u16::<LittleEndian>(date)?;
+
+    // High word of the first cluster (always 0 on FAT12/16)
+    output_writter.write_u16::<LittleEndian>(0x0)?;
+
+    // Modification time and date
+    output_writter.write_u16::<LittleEndian>(time)?;
+    output_writter.write_u16::<LittleEndian>(date)?;
+
+    output_writter.write_u16::<LittleEndian>(cluster as u16)?;
+    output_writter.write_u32::<LittleEndian>(size)?;
+
+    Ok(())
+}
+
+struct FatBuilder {
+    fat: Vec<u32>,
+    data: Vec<u8>,
+}
+
+impl FatBuilder {
+    /// Allocate a contiguous cluster chain and return its first cluster (0 if empty).
+    fn allocate(&mut self, cluster_count: u32) -> u32 {
+        if cluster_count == 0 {
+            return 0;
+        }
+
+        let start_cluster = self.fat.len() as u32;
+        for cluster in start_cluster..start_cluster + cluster_count - 1 {
+            self.fat.push(cluster + 1);
+        }
+
+        // End of chain
+        self.fat.push(0xFFFF_FFFF);
+
+        self.data
+            .resize(self.data.len() + cluster_count as usize * CLUSTER_SIZE, 0u8);
+
+        start_cluster
+    }
+
+    fn write_cluster_data(&mut self, cluster: u32, data: &[u8]) {
+        let offset = (cluster as usize - 2) * CLUSTER_SIZE;
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    /// Write the content of the directory childs and return the directory entries.
+    fn write_directory(
+        &mut self,
+        childs: &[FatNode],
+        cluster: u32,
+        parent_cluster: u32,
+    ) -> io::Result<Vec<u8>> {
+        let mut res: Vec<u8> = Vec::new();
+
+        // Sub directories start with "." and ".."
+        if cluster != 0 {
+            write_directory_entry(&mut res, b".          ", ATTRIBUTE_DIRECTORY, cluster, 0)?;
+            write_directory_entry(
+                &mut res,
+                b"..         ",
+                ATTRIBUTE_DIRECTORY,
+                parent_cluster,
+                0,
+            )?;
+        }
+
+        for child in childs {
+            let short_name = get_short_name(child.get_name())?;
+
+            match child {
+                FatNode::Directory { childs, .. } => {
+                    let child_cluster = self.allocate(get_directory_cluster_count(childs));
+                    let entries = self.write_directory(childs, child_cluster, cluster)?;
+                    self.write_cluster_data(child_cluster, &entries);
+
+                    write_directory_entry(
+                        &mut res,
+                        &short_name,
+                        ATTRIBUTE_DIRECTORY,
+                        child_cluster,
+                        0,
+                    )?;
+                }
+                FatNode::File { data, .. } => {
+                    let child_cluster = self.allocate(child.get_cluster_count());
+                    if child_cluster != 0 {
+                        self.write_cluster_data(child_cluster, data);
+                    }
+
+                    write_directory_entry(
+                        &mut res,
+                        &short_name,
+                        ATTRIBUTE_ARCHIVE,
+                        child_cluster,
+                        data.len() as u32,
+                    )?;
+                }
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Create a FAT12/16 filesystem image holding the given files ("/" separated path and content).
+///
+/// This is used for the EFI System Partition images, only 8.3 file names are supported.
+pub fn create_fat_image(files: Vec<(String, Vec<u8>)>) -> io::Result<Vec<u8>> {
+    let mut root_childs: Vec<FatNode> = Vec::new();
+    for (path, data) in files {
+        let path: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        add_node(&mut root_childs, &path, data);
+    }
+
+    if root_childs.len() > ROOT_ENTRY_COUNT as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "too many files in the FAT root directory!",
+        ));
+    }
+
+    let cluster_count = std::cmp::max(
+        root_childs
+            .iter()
+            .map(|child| child.get_cluster_count())
+            .sum::<u32>(),
+        1,
+    );
+
+    let is_fat16 = cluster_count > FAT12_MAX_CLUSTER_COUNT;
+    if cluster_count > FAT16_MAX_CLUSTER_COUNT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "files are too big for a FAT16 filesystem!",
+        ));
+    }
+
+    let mut builder = FatBuilder {
+        // Media descriptor and end of chain entries
+        fat: vec![0xFFFF_FFF8, 0xFFFF_FFFF],
+        data: Vec::new(),
+    };
+
+    let root_entries = builder.write_directory(&root_childs, 0, 0)?;
+
+    // Unused clusters (only needed for an empty filesystem)
+    builder
+        .data
+        .resize(cluster_count as usize * CLUSTER_SIZE, 0u8);
+
+    let mut fat: Vec<u8> = Vec::new();
+    if is_fat16 {
+        for entry in &builder.fat {
+            fat.write_u16::<LittleEndian>(*entry as u16)?;
+        }
+    } else {
+        for entries in builder.fat.chunks(2) {
+            let first = entries[0] & 0xFFF;
+            let second = entries.get(1).map_or(0, |entry| entry & 0xFFF);
+            let value = first | (second << 12);
+            fat.write_all(&value.to_le_bytes()[..3])?;
+        }
+    }
+
+    let fat_sector_count = (fat.len() as u32).div_ceil(SECTOR_SIZE);
+    fat.resize((fat_sector_count * SECTOR_SIZE) as usize, 0u8);
+
+    let root_sector_count = ROOT_ENTRY_COUNT * DIRECTORY_ENTRY_SIZE as u32 / SECTOR_SIZE;
+    let mut root_directory = root_entries;
+    root_directory.resize((root_sector_count * SECTOR_SIZE) as usize, 0u8);
+
+    let total_sector_count = RESERVED_SECTOR_COUNT
+        + FAT_COUNT * fat_sector_count
+        + root_sector_count
+        + cluster_count * SECTORS_PER_CLUSTER;
+
+    let mut res: Vec<u8> = Vec::new();
+
+    // Boot sector (jump + OEM name)
+    res.write_all(&[0xEB, 0x3C, 0x90])?;
+    res.write_all(b"MKISOFS ")?;
+
+    // BIOS Parameter Block
+    res.write_u16::<LittleEndian>(SECTOR_SIZE as u16)?;
+    res.write_u8(SECTORS_PER_CLUSTER as u8)?;
+    res.write_u16::<LittleEndian>(RESERVED_SECTOR_COUNT as u16)?;
+    res.write_u8(FAT_COUNT as u8)?;
+    res.write_u16::<LittleEndian>(ROOT_ENTRY_COUNT as u16)?;
+    if total_sector_count <= u32::from(u16::MAX) {
+        res.write_u16::<LittleEndian>(total_sector_count as u16)?;
+    } else {
+        res.write_u16::<LittleEndian>(0x0)?;
+    }
+
+    // Media descriptor (fixed disk)
+    res.write_u8(0xF8)?;
+    res.write_u16::<LittleEndian>(fat_sector_count as u16)?;
+
+    // Geometry and hidden sectors
+    res.write_u16::<LittleEndian>(32)?;
+    res.write_u16::<LittleEndian>(64)?;
+    res.write_u32::<LittleEndian>(0x0)?;
+
+    if total_sector_count > u32::from(u16::MAX) {
+        res.write_u32::<LittleEndian>(total_sector_count)?;
+    } else {
+        res.write_u32::<LittleEndian>(0x0)?;
+    }
+
+    // Extended BIOS Parameter Block
+    res.write_u8(0x80)?;
+    res.write_u8(0x0)?;
+    res.write_u8(0x29)?;
+    res.write_u32::<LittleEndian>(utils::generate_random_u32())?;
+    res.write_all(b"NO NAME    ")?;
+    if is_fat16 {
+        res.write_all(b"FAT16   ")?;
+    } else {
+        res.write_all(b"FAT12   ")?;
+    }
+
+    res.resize(SECTOR_SIZE as usize - 2, 0u8);
+    res.write_u8(0x55)?;
+    res.write_u8(0xAA)?;
+
+    for _ in 0..FAT_COUNT {
+        res.write_all(&fat)?;
+    }
+    res.write_all(&root_directory)?;
+    res.write_all(&builder.data)?;
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::ByteOrder;
+
+    fn get_fat12_entry(fat: &[u8], cluster: usize) -> u16 {
+        let value = LittleEndian::read_u16(&fat[cluster * 3 / 2..]);
+        if cluster & 1 == 0 {
+            value & 0xFFF
+        } else {
+            value >> 4
+        }
+    }
+
+    /// Find an entry of the directory, returning its attributes, first cluster and size.
+    fn find_entry(entries: &[u8], short_name: &[u8; 11]) -> Option<(u8, usize, usize)> {
+        entries
+            .chunks(DIRECTORY_ENTRY_SIZE)
+            .find(|entry| &entry[..11] == short_name)
+            .map(|entry| {
+                (
+                    entry[0xB],
+                    usize::from(LittleEndian::read_u16(&entry[0x1A..])),
+                    LittleEndian::read_u32(&entry[0x1C..]) as usize,
+                )
+            })
+    }
+
+    #[test]
+    fn short_names() {
+        assert_eq!(&get_short_name("bootx64.efi").unwrap(), b"BOOTX64 EFI");
+        assert_eq!(&get_short_name("EFI").unwrap(), b"EFI        ");
+
+        for name in [
+            "",
+            ".efi",
+            "bootloader.efi",
+            "boot.text",
+            "boot x64.efi",
+            "bööt.efi",
+        ] {
+            assert!(get_short_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn fat12_image() {
+        let content: Vec<u8> = (0..3000).map(|value| value as u8).collect();
+        let image = create_fat_image(vec![(
+            String::from("efi/boot/bootx64.efi"),
+            content.clone(),
+        )])
+        .unwrap();
+
+        let sector_size = SECTOR_SIZE as usize;
+        assert_eq!(&image[0x1FE..0x200], &[0x55, 0xAA]);
+        assert_eq!(&image[0x36..0x3E], b"FAT12   ");
+        assert_eq!(LittleEndian::read_u16(&image[0xB..]) as usize, sector_size);
+        assert_eq!(image[0xD] as u32, SECTORS_PER_CLUSTER);
+        assert_eq!(
+            LittleEndian::read_u16(&image[0x13..]) as usize * sector_size,
+            image.len()
+        );
+
+        let fat_size = LittleEndian::read_u16(&image[0x16..]) as usize * sector_size;
+        let fat = &image[sector_size..sector_size + fat_size];
+        assert_eq!(
+            fat,
+            &image[sector_size + fat_size..sector_size + 2 * fat_size]
+        );
+        assert_eq!(get_fat12_entry(fat, 0), 0xFF8);
+
+        let root_start = sector_size + 2 * fat_size;
+        let data_start = root_start + ROOT_ENTRY_COUNT as usize * DIRECTORY_ENTRY_SIZE;
+        let get_cluster = |cluster: usize| &image[data_start + (cluster - 2) * CLUSTER_SIZE..];
+
+        let (attributes, efi_cluster, _) =
+            find_entry(&image[root_start..data_start], b"EFI        ").unwrap();
+        assert_eq!(attributes, ATTRIBUTE_DIRECTORY);
+
+        let efi_entries = &get_cluster(efi_cluster)[..CLUSTER_SIZE];
+        assert_eq!(
+            find_entry(efi_entries, b".          ").unwrap().1,
+            efi_cluster
+        );
+        assert_eq!(find_entry(efi_entries, b"..         ").unwrap().1, 0);
+
+        let (_, boot_cluster, _) = find_entry(efi_entries, b"BOOT       ").unwrap();
+        let (attributes, file_cluster, size) =
+            find_entry(&get_cluster(boot_cluster)[..CLUSTER_SIZE], b"BOOTX64 EFI").unwrap();
+        assert_eq!(attributes, ATTRIBUTE_ARCHIVE);
+        assert_eq!(size, content.len());
+        assert_eq!(&get_cluster(file_cluster)[..size], &content[..]);
+
+        // The file uses two contiguous clusters
+        assert_eq!(
+            usize::from(get_fat12_entry(fat, file_cluster)),
+            file_cluster + 1
+        );
+        assert_eq!(get_fat12_entry(fat, file_cluster + 1), 0xFFF);
+    }
+
+    #[test]
+    fn too_many_root_files() {
+        let files = (0..=ROOT_ENTRY_COUNT)
+            .map(|index| (format!("file{}", index), Vec::new()))
+            .collect();
+        assert!(create_fat_image(files).is_err());
+    }
+}
@@ -13,8 +13,25 @@ use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub enum FileType {
-    Regular { path: PathBuf },
-    Buffer { name: String, data: Vec<u8> },
+    Regular {
+        path: PathBuf,
+    },
+    Buffer {
+        name: String,
+        data: Vec<u8>,
+    },
+    /// File of a previous session, its extent is kept as is
+    Previous {
+        name: String,
+        image: PathBuf,
+        lba: u32,
+    },
+    /// Part of a host file starting at the given byte offset
+    Slice {
+        name: String,
+        path: PathBuf,
+        offset: u64,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +40,7 @@ pub struct FileEntry {
     pub size: usize,
     pub lba: u32,
     pub aligned_size: usize,
+    pub hidden: bool,
 }
 
 impl FileEntry {
@@ -30,13 +48,50 @@ impl FileEntry {
         match &self.file_type {
             FileType::Regular { path } => path.file_name().unwrap().to_str().unwrap().to_string(),
             FileType::Buffer { name, .. } => name.clone(),
+            FileType::Previous { name, .. } => name.clone(),
+            FileType::Slice { name, .. } => name.clone(),
         }
     }
 
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_type = match &self.file_type {
+            FileType::Regular { path } => FileType::Slice {
+                name: String::from(file_name),
+                path: path.clone(),
+                offset: 0,
+            },
+            FileType::Buffer { data, .. } => FileType::Buffer {
+                name: String::from(file_name),
+                data: data.clone(),
+            },
+            FileType::Previous { image, lba, .. } => FileType::Previous {
+                name: String::from(file_name),
+                image: image.clone(),
+                lba: *lba,
+            },
+            FileType::Slice { path, offset, .. } => FileType::Slice {
+                name: String::from(file_name),
+                path: path.clone(),
+                offset: *offset,
+            },
+        };
+    }
+
     pub fn open_content_provider(&self) -> Box<dyn Read> {
         match &self.file_type {
             FileType::Regular { path } => Box::new(File::open(path).unwrap()),
             FileType::Buffer { data, .. } => Box::new(Cursor::new(data.clone())),
+            FileType::Previous { image, lba, .. } => {
+                let mut file = File::open(image).unwrap();
+                file.seek(SeekFrom::Start(u64::from(*lba) * LOGIC_SIZE as u64))
+                    .unwrap();
+                Box::new(file.take(self.size as u64))
+            }
+            FileType::Slice { path, offset, .. } => {
+                let mut file = File::open(path).unwrap();
+                file.seek(SeekFrom::Start(*offset)).unwrap();
+                Box::new(file.take(self.size as u64))
+            }
         }
     }
 
@@ -72,9 +127,10 @@ impl FileEntry {
             output_writter.write_u32(self.lba)?;
         }
 
-        // Extent size
+        // Extent size, files bigger than 4 GiB are only fully visible in UDF
+        let extent_size = std::cmp::min(self.size, u32::MAX as usize) as u32;
         write_bothendian! {
-            output_writter.write_u32(self.size as u32)?;
+            output_writter.write_u32(extent_size)?;
         }
 
         let record_datetime: DateTime<Utc> = Utc::now();
@@ -172,10 +228,15 @@ impl FileEntry {
     where
         T: Write + Seek,
     {
+        // The content of the previous sessions is already on the medium
+        if let FileType::Previous { .. } = self.file_type {
+            return Ok(());
+        }
+
         let old_pos = output_writter.seek(SeekFrom::Current(0))?;
 
         // Seek to the correct LBA
-        output_writter.seek(SeekFrom::Start(u64::from(self.lba * LOGIC_SIZE_U32)))?;
+        output_writter.seek(SeekFrom::Start(u64::from(self.lba) * LOGIC_SIZE as u64))?;
 
         let mut file: Box<dyn Read> = self.open_content_provider();
         io::copy(&mut file, output_writter)?;
@@ -205,6 +266,7 @@ impl FileEntry {
             lba: 0,
             size: 0,
             aligned_size: 0,
+            hidden: false,
         }
     }
 }
@@ -0,0 +1,268 @@
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+pub const BASIC_DATA_TYPE_GUID: &str = "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7";
+pub const APPLE_HFS_TYPE_GUID: &str = "48465300-0000-11AA-AA11-00306543ECAC";
+pub const EFI_SYSTEM_TYPE_GUID: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+
+const HEADER_SIZE: u32 = 0x5C;
+const ENTRY_COUNT: u32 = 0x80;
+const ENTRY_SIZE: u32 = 0x80;
+const ENTRIES_SECTOR_COUNT: u32 = (ENTRY_COUNT * ENTRY_SIZE) / SECTOR_SIZE;
+
+/// Count of LB to reserve at the end of the image for the backup GPT (partition entries + header).
+pub const BACKUP_LB_COUNT: u32 =
+    ((ENTRIES_SECTOR_COUNT + 1) * SECTOR_SIZE).div_ceil(LOGIC_SIZE_U32);
+
+#[derive(Debug, Clone)]
+pub struct GptPartition {
+    pub type_guid: [u8; 16],
+    pub guid: [u8; 16],
+    pub start_sector: u64,
+    pub end_sector: u64,
+    pub name: String,
+}
+
+impl GptPartition {
+    pub fn new(type_guid: &str, start_sector: u64, sector_count: u64, name: &str) -> GptPartition {
+        GptPartition {
+            type_guid: utils::parse_guid(type_guid).unwrap(),
+            guid: utils::generate_guid(),
+            start_sector,
+            end_sector: start_sector + sector_count - 1,
+            name: String::from(name),
+        }
+    }
+
+    fn write_entry<T>(&self, output_writter: &mut T) -> std::io::Result<()>
+    where
+        T: Write,
+    {
+        output_writter.write_all(&self.type_guid)?;
+        output_writter.write_all(&self.guid)?;
+        output_writter.write_u64::<LittleEndian>(self.start_sector)?;
+        output_writter.write_u64::<LittleEndian>(self.end_sector)?;
+
+        // Attributes
+        output_writter.write_u64::<LittleEndian>(0x0)?;
+
+        // Partition name (UTF-16LE, 36 code units)
+        let mut name: [u8; 72] = [0x0; 72];
+        for (i, code_unit) in self.name.encode_utf16().take(36).enumerate() {
+            name[i * 2..i * 2 + 2].copy_from_slice(&code_unit.to_le_bytes());
+        }
+        output_writter.write_all(&name)?;
+
+        Ok(())
+    }
+}
+
+fn write_header(
+    disk_guid: &[u8; 16],
+    current_sector: u64,
+    backup_sector: u64,
+    first_usable_sector: u64,
+    last_usable_sector: u64,
+    entries_sector: u64,
+    entries_crc32: u32,
+) -> std::io::Result<Vec<u8>> {
+    let mut header: Vec<u8> = Vec::new();
+
+    header.write_all(b"EFI PART")?;
+
+    // Revision 1.0
+    header.write_u32::<LittleEndian>(0x0001_0000)?;
+    header.write_u32::<LittleEndian>(HEADER_SIZE)?;
+
+    // Header CRC32 (patched once the header is complete)
+    header.write_u32::<LittleEndian>(0x0)?;
+
+    // Reserved
+    header.write_u32::<LittleEndian>(0x0)?;
+
+    header.write_u64::<LittleEndian>(current_sector)?;
+    header.write_u64::<LittleEndian>(backup_sector)?;
+    header.write_u64::<LittleEndian>(first_usable_sector)?;
+    header.write_u64::<LittleEndian>(last_usable_sector)?;
+    header.write_all(disk_guid)?;
+    header.write_u64::<LittleEndian>(entries_sector)?;
+    header.write_u32::<LittleEndian>(ENTRY_COUNT)?;
+    header.write_u32::<LittleEndian>(ENTRY_SIZE)?;
+    header.write_u32::<LittleEndian>(entries_crc32)?;
+
+    let header_crc32 = utils::crc32(&header);
+    header[0x10..0x14].copy_from_slice(&header_crc32.to_le_bytes());
+
+    // Pad to sector size
+    header.resize(SECTOR_SIZE as usize, 0u8);
+
+    Ok(header)
+}
+
+/// Write the primary GPT in the system area (header in sector 1, entries starting at `entries_sector`) and the backup GPT at the end of the disk.
+pub fn write_gpt<T>(
+    output_writter: &mut T,
+    partitions: &[GptPartition],
+    disk_sector_count: u64,
+    entries_sector: u64,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let current_pos = output_writter.stream_position()?;
+
+    let mut entries: Vec<u8> = Vec::new();
+    for partition in partitions {
+        partition.write_entry(&mut entries)?;
+    }
+    entries.resize((ENTRY_COUNT * ENTRY_SIZE) as usize, 0u8);
+
+    let entries_crc32 = utils::crc32(&entries);
+    let disk_guid = utils::generate_guid();
+
+    let backup_header_sector = disk_sector_count - 1;
+    let backup_entries_sector = backup_header_sector - u64::from(ENTRIES_SECTOR_COUNT);
+
+    let first_usable_sector = entries_sector + u64::from(ENTRIES_SECTOR_COUNT);
+    let last_usable_sector = backup_entries_sector - 1;
+
+    let primary_header = write_header(
+        &disk_guid,
+        1,
+        backup_header_sector,
+        first_usable_sector,
+        last_usable_sector,
+        entries_sector,
+        entries_crc32,
+    )?;
+
+    let backup_header = write_header(
+        &disk_guid,
+        backup_header_sector,
+        1,
+        first_usable_sector,
+        last_usable_sector,
+        backup_entries_sector,
+        entries_crc32,
+    )?;
+
+    output_writter.seek(SeekFrom::Start(u64::from(SECTOR_SIZE)))?;
+    output_writter.write_all(&primary_header)?;
+
+    output_writter.seek(SeekFrom::Start(entries_sector * u64::from(SECTOR_SIZE)))?;
+    output_writter.write_all(&entries)?;
+
+    output_writter.seek(SeekFrom::Start(
+        backup_entries_sector * u64::from(SECTOR_SIZE),
+    ))?;
+    output_writter.write_all(&entries)?;
+    output_writter.write_all(&backup_header)?;
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use std::io::Cursor;
+
+    const DISK_SECTOR_COUNT: u64 = 0x100;
+
+    fn get_sector(disk: &[u8], sector: u64) -> &[u8] {
+        &disk[(sector * u64::from(SECTOR_SIZE)) as usize..]
+    }
+
+    /// Check the CRC32 of the header and of its partition entries, returning the header.
+    fn check_header(disk: &[u8], sector: u64) -> &[u8] {
+        let header = &get_sector(disk, sector)[..HEADER_SIZE as usize];
+        assert_eq!(&header[0x0..0x8], b"EFI PART");
+
+        let mut header_copy = header.to_vec();
+        header_copy[0x10..0x14].fill(0x0);
+        assert_eq!(
+            LittleEndian::read_u32(&header[0x10..]),
+            utils::crc32(&header_copy)
+        );
+
+        let entries_sector = LittleEndian::read_u64(&header[0x48..]);
+        let entries = &get_sector(disk, entries_sector)[..(ENTRY_COUNT * ENTRY_SIZE) as usize];
+        assert_eq!(
+            LittleEndian::read_u32(&header[0x58..]),
+            utils::crc32(entries)
+        );
+
+        header
+    }
+
+    #[test]
+    fn crc32() {
+        assert_eq!(utils::crc32(b""), 0x0);
+        assert_eq!(utils::crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn primary_and_backup_headers() {
+        let partition = GptPartition::new(EFI_SYSTEM_TYPE_GUID, 0x40, 0x10, "EFI boot partition");
+
+        let mut disk = Cursor::new(vec![
+            0x0;
+            (DISK_SECTOR_COUNT * u64::from(SECTOR_SIZE)) as usize
+        ]);
+        write_gpt(
+            &mut disk,
+            std::slice::from_ref(&partition),
+            DISK_SECTOR_COUNT,
+            2,
+        )
+        .unwrap();
+        assert_eq!(disk.position(), 0);
+        let disk = disk.into_inner();
+
+        let primary_header = check_header(&disk, 1);
+        let backup_header = check_header(&disk, DISK_SECTOR_COUNT - 1);
+
+        let backup_entries_sector = DISK_SECTOR_COUNT - 1 - u64::from(ENTRIES_SECTOR_COUNT);
+        assert_eq!(LittleEndian::read_u64(&primary_header[0x18..]), 1);
+        assert_eq!(
+            LittleEndian::read_u64(&primary_header[0x20..]),
+            DISK_SECTOR_COUNT - 1
+        );
+        assert_eq!(LittleEndian::read_u64(&primary_header[0x48..]), 2);
+        assert_eq!(
+            LittleEndian::read_u64(&backup_header[0x18..]),
+            DISK_SECTOR_COUNT - 1
+        );
+        assert_eq!(LittleEndian::read_u64(&backup_header[0x20..]), 1);
+        assert_eq!(
+            LittleEndian::read_u64(&backup_header[0x48..]),
+            backup_entries_sector
+        );
+
+        // Both headers describe the same disk and usable area
+        assert_eq!(&primary_header[0x28..0x48], &backup_header[0x28..0x48]);
+        assert_eq!(
+            LittleEndian::read_u64(&primary_header[0x28..]),
+            2 + u64::from(ENTRIES_SECTOR_COUNT)
+        );
+        assert_eq!(
+            LittleEndian::read_u64(&primary_header[0x30..]),
+            backup_entries_sector - 1
+        );
+
+        let entry = &get_sector(&disk, 2)[..ENTRY_SIZE as usize];
+        assert_eq!(&entry[0x0..0x10], &partition.type_guid);
+        assert_eq!(LittleEndian::read_u64(&entry[0x20..]), 0x40);
+        assert_eq!(LittleEndian::read_u64(&entry[0x28..]), 0x4F);
+        assert_eq!(LittleEndian::read_u16(&entry[0x38..]), u16::from(b'E'));
+    }
+}
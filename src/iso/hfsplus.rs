@@ -0,0 +1,789 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use chrono::prelude::*;
+
+use std::cmp::Ordering;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+/// HFS+ allocation blocks match the ISO logical blocks so both filesystems can share file extents.
+const BLOCK_SIZE: u32 = LOGIC_SIZE_U32;
+const NODE_SIZE: usize = 0x1000;
+const NODE_LB_COUNT: u32 = NODE_SIZE as u32 / LOGIC_SIZE_U32;
+const NODE_DESCRIPTOR_SIZE: usize = 0xE;
+
+/// The extents overflow file only contains its header node as all our files are contiguous.
+pub const EXTENTS_LB_COUNT: u32 = NODE_LB_COUNT;
+
+const ROOT_PARENT_ID: u32 = 1;
+const ROOT_FOLDER_ID: u32 = 2;
+const FIRST_USER_CATALOG_NODE_ID: u32 = 16;
+
+const FOLDER_RECORD: u16 = 1;
+const FILE_RECORD: u16 = 2;
+const FOLDER_THREAD_RECORD: u16 = 3;
+
+/// Seconds between the HFS+ epoch (1904) and the UNIX epoch.
+const HFS_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+pub const VOLUME_NAME: &str = "ISOIMAGE";
+
+#[derive(Debug, Clone, Default)]
+pub struct HfsPlusLayout {
+    /// LBA of the start of the HFS+ partition (holding the volume header)
+    pub start_lba: u32,
+    pub catalog_lba: u32,
+    pub catalog_lb_count: u32,
+    pub extents_lba: u32,
+    pub bitmap_lba: u32,
+    pub bitmap_lb_count: u32,
+
+    /// Size of the HFS+ partition in LB (the last one holding the alternate volume header)
+    pub lb_count: u32,
+}
+
+#[derive(Debug, Default)]
+struct CatalogInfo {
+    file_count: u32,
+    folder_count: u32,
+    next_catalog_id: u32,
+    bless_folder_id: u32,
+    bless_file_id: u32,
+}
+
+struct CatalogRecord {
+    parent_id: u32,
+    name: Vec<u16>,
+    data: Vec<u8>,
+}
+
+impl CatalogRecord {
+    fn get_key(&self) -> Vec<u8> {
+        let mut key: Vec<u8> = Vec::new();
+        key.write_u16::<BigEndian>(6 + 2 * self.name.len() as u16)
+            .unwrap();
+        key.write_u32::<BigEndian>(self.parent_id).unwrap();
+        write_unicode_name(&mut key, &self.name).unwrap();
+        key
+    }
+}
+
+fn get_hfs_date() -> u32 {
+    (Utc::now().timestamp() + HFS_EPOCH_OFFSET) as u32
+}
+
+fn convert_name(name: &str) -> std::io::Result<Vec<u16>> {
+    // Only ASCII names are sorted like FastUnicodeCompare without its case folding table
+    if !name.is_ascii() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("HFS+ name \"{}\" isn't ASCII!", name),
+        ));
+    }
+
+    // ':' is the path separator of HFS+, '/' is stored in its place
+    Ok(name
+        .chars()
+        .map(|c| if c == '/' { ':' } else { c })
+        .collect::<String>()
+        .encode_utf16()
+        .take(255)
+        .collect())
+}
+
+fn fold_case(value: u16) -> u16 {
+    match u8::try_from(value) {
+        Ok(c) => u16::from(c.to_ascii_lowercase()),
+        Err(_) => value,
+    }
+}
+
+/// Case insensitive comparison of ASCII HFS+ names, like FastUnicodeCompare (ignoring null characters).
+fn compare_names(a: &[u16], b: &[u16]) -> Ordering {
+    let a_iter = a.iter().filter(|c| **c != 0).map(|c| fold_case(*c));
+    let b_iter = b.iter().filter(|c| **c != 0).map(|c| fold_case(*c));
+
+    a_iter.cmp(b_iter)
+}
+
+fn write_unicode_name<T>(output_writter: &mut T, name: &[u16]) -> std::io::Result<()>
+where
+    T: Write,
+{
+    output_writter.write_u16::<BigEndian>(name.len() as u16)?;
+    for c in name {
+        output_writter.write_u16::<BigEndian>(*c)?;
+    }
+
+    Ok(())
+}
+
+fn write_fork_data<T>(
+    output_writter: &mut T,
+    logical_size: u64,
+    start_block: u32,
+    block_count: u32,
+) -> std::io::Result<()>
+where
+    T: Write,
+{
+    output_writter.write_u64::<BigEndian>(logical_size)?;
+
+    // Clump size
+    output_writter.write_u32::<BigEndian>(0x0)?;
+    output_writter.write_u32::<BigEndian>(block_count)?;
+
+    // First extent
+    if block_count != 0 {
+        output_writter.write_u32::<BigEndian>(start_block)?;
+        output_writter.write_u32::<BigEndian>(block_count)?;
+    } else {
+        output_writter.write_u64::<BigEndian>(0x0)?;
+    }
+
+    // Other extents (unused)
+    let empty_extents: [u8; 0x38] = [0x0; 0x38];
+    output_writter.write_all(&empty_extents)?;
+
+    Ok(())
+}
+
+fn write_permissions<T>(output_writter: &mut T, mode: u16) -> std::io::Result<()>
+where
+    T: Write,
+{
+    // owner id
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // group id
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // admin flags + owner flags
+    output_writter.write_u16::<BigEndian>(0x0)?;
+
+    output_writter.write_u16::<BigEndian>(mode)?;
+
+    // special
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    Ok(())
+}
+
+fn create_folder_records(
+    records: &mut Vec<CatalogRecord>,
+    parent_id: u32,
+    folder_id: u32,
+    name: &[u16],
+    valence: u32,
+) -> std::io::Result<()> {
+    let date = get_hfs_date();
+
+    let mut data: Vec<u8> = Vec::new();
+    data.write_u16::<BigEndian>(FOLDER_RECORD)?;
+
+    // flags
+    data.write_u16::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(valence)?;
+    data.write_u32::<BigEndian>(folder_id)?;
+
+    // create, content mod, attribute mod, access and backup dates
+    for _ in 0..4 {
+        data.write_u32::<BigEndian>(date)?;
+    }
+    data.write_u32::<BigEndian>(0x0)?;
+
+    write_permissions(&mut data, 0o040_755)?;
+
+    // Finder user info and extended info
+    let finder_info: [u8; 0x20] = [0x0; 0x20];
+    data.write_all(&finder_info)?;
+
+    // Text encoding (MacRoman) + reserved
+    data.write_u32::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(0x0)?;
+
+    records.push(CatalogRecord {
+        parent_id,
+        name: name.to_vec(),
+        data,
+    });
+
+    // Folder thread record
+    let mut data: Vec<u8> = Vec::new();
+    data.write_u16::<BigEndian>(FOLDER_THREAD_RECORD)?;
+    data.write_u16::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(parent_id)?;
+    write_unicode_name(&mut data, name)?;
+
+    records.push(CatalogRecord {
+        parent_id: folder_id,
+        name: Vec::new(),
+        data,
+    });
+
+    Ok(())
+}
+
+fn create_file_record(
+    records: &mut Vec<CatalogRecord>,
+    parent_id: u32,
+    file_id: u32,
+    name: &[u16],
+    size: u64,
+    start_block: u32,
+) -> std::io::Result<()> {
+    let date = get_hfs_date();
+
+    let mut data: Vec<u8> = Vec::new();
+    data.write_u16::<BigEndian>(FILE_RECORD)?;
+
+    // flags (no thread record) + reserved
+    data.write_u16::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(file_id)?;
+
+    // create, content mod, attribute mod, access and backup dates
+    for _ in 0..4 {
+        data.write_u32::<BigEndian>(date)?;
+    }
+    data.write_u32::<BigEndian>(0x0)?;
+
+    write_permissions(&mut data, 0o100_644)?;
+
+    // Finder user info and extended info
+    let finder_info: [u8; 0x20] = [0x0; 0x20];
+    data.write_all(&finder_info)?;
+
+    // Text encoding (MacRoman) + reserved
+    data.write_u32::<BigEndian>(0x0)?;
+    data.write_u32::<BigEndian>(0x0)?;
+
+    // data fork
+    let block_count = size.div_ceil(u64::from(BLOCK_SIZE)) as u32;
+    write_fork_data(&mut data, size, start_block, block_count)?;
+
+    // resource fork
+    write_fork_data(&mut data, 0, 0, 0)?;
+
+    records.push(CatalogRecord {
+        parent_id,
+        name: name.to_vec(),
+        data,
+    });
+
+    Ok(())
+}
+
+fn create_directory_records(
+    records: &mut Vec<CatalogRecord>,
+    info: &mut CatalogInfo,
+    directory_entry: &DirectoryEntry,
+    directory_id: u32,
+    directory_path: &str,
+    partition_start_lba: u32,
+    bless: (Option<&str>, Option<&str>),
+) -> std::io::Result<()> {
+    for child_file in directory_entry
+        .files_childs
+        .iter()
+        .filter(|entry| !entry.hidden)
+    {
+        let file_id = info.next_catalog_id;
+        info.next_catalog_id += 1;
+        info.file_count += 1;
+
+        let file_name = child_file.get_file_name();
+        let file_path = format!("{}/{}", directory_path, file_name);
+        if Some(file_path.trim_start_matches('/')) == bless.1 {
+            info.bless_file_id = file_id;
+        }
+
+        let start_block = child_file.lba.saturating_sub(partition_start_lba);
+        create_file_record(
+            records,
+            directory_id,
+            file_id,
+            &convert_name(&file_name)?,
+            child_file.size as u64,
+            start_block,
+        )?;
+    }
+
+    for child_directory in &directory_entry.dir_childs {
+        let folder_id = info.next_catalog_id;
+        info.next_catalog_id += 1;
+        info.folder_count += 1;
+
+        let directory_name = child_directory.get_file_name();
+        let child_path = format!("{}/{}", directory_path, directory_name);
+        if Some(child_path.trim_start_matches('/')) == bless.0 {
+            info.bless_folder_id = folder_id;
+        }
+
+        let valence = child_directory.dir_childs.len()
+            + child_directory
+                .files_childs
+                .iter()
+                .filter(|entry| !entry.hidden)
+                .count();
+
+        create_folder_records(
+            records,
+            directory_id,
+            folder_id,
+            &convert_name(&directory_name)?,
+            valence as u32,
+        )?;
+
+        create_directory_records(
+            records,
+            info,
+            child_directory,
+            folder_id,
+            &child_path,
+            partition_start_lba,
+            bless,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_node(
+    kind: i8,
+    height: u8,
+    forward_link: u32,
+    backward_link: u32,
+    records: &[Vec<u8>],
+) -> std::io::Result<Vec<u8>> {
+    let mut node: Vec<u8> = Vec::new();
+
+    // Node descriptor
+    node.write_u32::<BigEndian>(forward_link)?;
+    node.write_u32::<BigEndian>(backward_link)?;
+    node.write_i8(kind)?;
+    node.write_u8(height)?;
+    node.write_u16::<BigEndian>(records.len() as u16)?;
+    node.write_u16::<BigEndian>(0x0)?;
+
+    let mut offsets: Vec<u16> = Vec::new();
+    for record in records {
+        offsets.push(node.len() as u16);
+        node.write_all(record)?;
+    }
+
+    // Offset of the free space
+    offsets.push(node.len() as u16);
+
+    assert!(node.len() + offsets.len() * 2 <= NODE_SIZE);
+    node.resize(NODE_SIZE - offsets.len() * 2, 0u8);
+
+    // Record offsets are stored backward at the end of the node
+    for offset in offsets.iter().rev() {
+        node.write_u16::<BigEndian>(*offset)?;
+    }
+
+    Ok(node)
+}
+
+fn fits_in_node(records_size: usize, record_count: usize) -> bool {
+    NODE_DESCRIPTOR_SIZE + records_size + (record_count + 1) * 2 <= NODE_SIZE
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_header_node(
+    tree_depth: u16,
+    root_node: u32,
+    leaf_records: u32,
+    first_leaf_node: u32,
+    last_leaf_node: u32,
+    max_key_length: u16,
+    total_nodes: u32,
+    used_nodes: u32,
+) -> std::io::Result<Vec<u8>> {
+    let mut header: Vec<u8> = Vec::new();
+    header.write_u16::<BigEndian>(tree_depth)?;
+    header.write_u32::<BigEndian>(root_node)?;
+    header.write_u32::<BigEndian>(leaf_records)?;
+    header.write_u32::<BigEndian>(first_leaf_node)?;
+    header.write_u32::<BigEndian>(last_leaf_node)?;
+    header.write_u16::<BigEndian>(NODE_SIZE as u16)?;
+    header.write_u16::<BigEndian>(max_key_length)?;
+    header.write_u32::<BigEndian>(total_nodes)?;
+    header.write_u32::<BigEndian>(total_nodes - used_nodes)?;
+
+    // reserved
+    header.write_u16::<BigEndian>(0x0)?;
+
+    // clump size
+    header.write_u32::<BigEndian>(NODE_SIZE as u32)?;
+
+    // B-tree type (HFS+ control file)
+    header.write_u8(0x0)?;
+
+    // Key compare type (case folding)
+    header.write_u8(0xCF)?;
+
+    // Attributes (kBTBigKeysMask | kBTVariableIndexKeysMask)
+    header.write_u32::<BigEndian>(0x6)?;
+
+    let reserved: [u8; 0x40] = [0x0; 0x40];
+    header.write_all(&reserved)?;
+
+    let user_data: [u8; 0x80] = [0x0; 0x80];
+
+    // Node allocation map
+    let map_size = NODE_SIZE - NODE_DESCRIPTOR_SIZE - header.len() - user_data.len() - 4 * 2;
+    let mut map = vec![0u8; map_size];
+    for node in 0..used_nodes as usize {
+        map[node / 8] |= 0x80 >> (node % 8);
+    }
+
+    write_node(1, 0, 0, 0, &[header, user_data.to_vec(), map])
+}
+
+/// Build the catalog B-tree, returns its content.
+fn build_catalog(mut records: Vec<CatalogRecord>) -> std::io::Result<Vec<u8>> {
+    records.sort_by(|a, b| {
+        a.parent_id
+            .cmp(&b.parent_id)
+            .then_with(|| compare_names(&a.name, &b.name))
+    });
+
+    let mut nodes: Vec<(i8, u8, Vec<Vec<u8>>)> = Vec::new();
+
+    // Pack leaf records, an index record (first key and node number) is kept for every node
+    let mut level: Vec<(Vec<u8>, u32)> = Vec::new();
+    let mut current_records: Vec<Vec<u8>> = Vec::new();
+    let mut current_size = 0;
+    let mut current_first_key: Vec<u8> = Vec::new();
+
+    for record in &records {
+        let key = record.get_key();
+        let mut raw_record = key.clone();
+        raw_record.extend_from_slice(&record.data);
+
+        if !fits_in_node(current_size + raw_record.len(), current_records.len() + 1) {
+            nodes.push((-1, 1, current_records));
+            level.push((std::mem::take(&mut current_first_key), nodes.len() as u32));
+            current_records = Vec::new();
+            current_size = 0;
+        }
+
+        if current_records.is_empty() {
+            current_first_key = key;
+        }
+
+        current_size += raw_record.len();
+        current_records.push(raw_record);
+    }
+
+    nodes.push((-1, 1, current_records));
+    level.push((current_first_key, nodes.len() as u32));
+
+    let first_leaf_node = 1;
+    let last_leaf_node = nodes.len() as u32;
+    let mut levels_bounds = vec![(first_leaf_node, last_leaf_node)];
+
+    // Build index levels until we get a single root node
+    let mut height = 1;
+    while level.len() > 1 {
+        height += 1;
+
+        let first_node = nodes.len() as u32 + 1;
+        let mut next_level: Vec<(Vec<u8>, u32)> = Vec::new();
+        let mut current_records: Vec<Vec<u8>> = Vec::new();
+        let mut current_size = 0;
+        let mut current_first_key: Vec<u8> = Vec::new();
+
+        for (key, node_number) in level {
+            let mut raw_record = key.clone();
+            raw_record.write_u32::<BigEndian>(node_number)?;
+
+            if !fits_in_node(current_size + raw_record.len(), current_records.len() + 1) {
+                nodes.push((0, height, current_records));
+                next_level.push((std::mem::take(&mut current_first_key), nodes.len() as u32));
+                current_records = Vec::new();
+                current_size = 0;
+            }
+
+            if current_records.is_empty() {
+                current_first_key = key;
+            }
+
+            current_size += raw_record.len();
+            current_records.push(raw_record);
+        }
+
+        nodes.push((0, height, current_records));
+        next_level.push((current_first_key, nodes.len() as u32));
+
+        levels_bounds.push((first_node, nodes.len() as u32));
+        level = next_level;
+    }
+
+    let root_node = level[0].1;
+    let total_nodes = nodes.len() as u32 + 1;
+
+    // Longest key allowed in the catalog (parent id + 255 UTF-16 characters)
+    let max_key_length = 6 + 255 * 2;
+
+    let mut res = write_header_node(
+        height as u16,
+        root_node,
+        records.len() as u32,
+        first_leaf_node,
+        last_leaf_node,
+        max_key_length,
+        total_nodes,
+        total_nodes,
+    )?;
+
+    for (index, (kind, node_height, node_records)) in nodes.iter().enumerate() {
+        let node_number = index as u32 + 1;
+        let (first_node, last_node) = levels_bounds[usize::from(*node_height) - 1];
+
+        // Nodes of the same level are linked together
+        let forward_link = if node_number == last_node {
+            0
+        } else {
+            node_number + 1
+        };
+        let backward_link = if node_number == first_node {
+            0
+        } else {
+            node_number - 1
+        };
+
+        res.extend(write_node(
+            *kind,
+            *node_height,
+            forward_link,
+            backward_link,
+            node_records,
+        )?);
+    }
+
+    Ok(res)
+}
+
+fn create_catalog(
+    tree: &DirectoryEntry,
+    partition_start_lba: u32,
+    bless: (Option<&str>, Option<&str>),
+) -> std::io::Result<(Vec<u8>, CatalogInfo)> {
+    let mut records: Vec<CatalogRecord> = Vec::new();
+    let mut info = CatalogInfo {
+        next_catalog_id: FIRST_USER_CATALOG_NODE_ID,
+        ..Default::default()
+    };
+
+    let valence = tree.dir_childs.len() + tree.files_childs.iter().filter(|e| !e.hidden).count();
+    create_folder_records(
+        &mut records,
+        ROOT_PARENT_ID,
+        ROOT_FOLDER_ID,
+        &convert_name(VOLUME_NAME)?,
+        valence as u32,
+    )?;
+
+    create_directory_records(
+        &mut records,
+        &mut info,
+        tree,
+        ROOT_FOLDER_ID,
+        "",
+        partition_start_lba,
+        bless,
+    )?;
+
+    Ok((build_catalog(records)?, info))
+}
+
+/// Count of LB needed by the catalog file of the given tree.
+pub fn get_catalog_lb_count(tree: &DirectoryEntry) -> std::io::Result<u32> {
+    // The catalog size doesn't depend on the file locations
+    let (catalog, _) = create_catalog(tree, 0, (None, None))?;
+
+    Ok(catalog.len() as u32 / LOGIC_SIZE_U32)
+}
+
+/// Count of LB needed by the allocation file of a partition of the given size.
+pub fn get_bitmap_lb_count(partition_lb_count: u32) -> u32 {
+    partition_lb_count.div_ceil(8).div_ceil(LOGIC_SIZE_U32)
+}
+
+fn write_volume_header<T>(
+    output_writter: &mut T,
+    layout: &HfsPlusLayout,
+    info: &CatalogInfo,
+    catalog_size: u64,
+) -> std::io::Result<()>
+where
+    T: Write,
+{
+    let date = get_hfs_date();
+
+    // Signature 'H+' and version
+    output_writter.write_all(b"H+")?;
+    output_writter.write_u16::<BigEndian>(0x4)?;
+
+    // Attributes (kHFSVolumeUnmountedBit | kHFSVolumeSoftwareLockBit)
+    output_writter.write_u32::<BigEndian>((1 << 8) | (1 << 15))?;
+
+    // Last mounted version
+    output_writter.write_all(b"10.0")?;
+
+    // Journal info block
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // create, modify, backup and checked dates
+    output_writter.write_u32::<BigEndian>(date)?;
+    output_writter.write_u32::<BigEndian>(date)?;
+    output_writter.write_u32::<BigEndian>(0x0)?;
+    output_writter.write_u32::<BigEndian>(date)?;
+
+    output_writter.write_u32::<BigEndian>(info.file_count)?;
+    output_writter.write_u32::<BigEndian>(info.folder_count)?;
+
+    output_writter.write_u32::<BigEndian>(BLOCK_SIZE)?;
+    output_writter.write_u32::<BigEndian>(layout.lb_count)?;
+
+    // Free blocks (read only volume, everything is allocated)
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // Next allocation
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // Resource and data clump sizes
+    output_writter.write_u32::<BigEndian>(BLOCK_SIZE)?;
+    output_writter.write_u32::<BigEndian>(BLOCK_SIZE)?;
+
+    output_writter.write_u32::<BigEndian>(info.next_catalog_id)?;
+
+    // Write count
+    output_writter.write_u32::<BigEndian>(0x0)?;
+
+    // Encodings bitmap (MacRoman)
+    output_writter.write_u64::<BigEndian>(0x1)?;
+
+    // Finder info: blessed system folder, startup file, open folder, OS 9 folder, reserved, OS X folder and volume id
+    output_writter.write_u32::<BigEndian>(info.bless_folder_id)?;
+    output_writter.write_u32::<BigEndian>(info.bless_file_id)?;
+    output_writter.write_u32::<BigEndian>(0x0)?;
+    output_writter.write_u32::<BigEndian>(0x0)?;
+    output_writter.write_u32::<BigEndian>(0x0)?;
+    output_writter.write_u32::<BigEndian>(info.bless_folder_id)?;
+    output_writter.write_u64::<BigEndian>(utils::generate_random_u64())?;
+
+    // Allocation file
+    write_fork_data(
+        output_writter,
+        u64::from(layout.bitmap_lb_count * BLOCK_SIZE),
+        layout.bitmap_lba - layout.start_lba,
+        layout.bitmap_lb_count,
+    )?;
+
+    // Extents overflow file
+    write_fork_data(
+        output_writter,
+        u64::from(EXTENTS_LB_COUNT * BLOCK_SIZE),
+        layout.extents_lba - layout.start_lba,
+        EXTENTS_LB_COUNT,
+    )?;
+
+    // Catalog file
+    write_fork_data(
+        output_writter,
+        catalog_size,
+        layout.catalog_lba - layout.start_lba,
+        layout.catalog_lb_count,
+    )?;
+
+    // Attributes and startup files (unused)
+    write_fork_data(output_writter, 0, 0, 0)?;
+    write_fork_data(output_writter, 0, 0, 0)?;
+
+    Ok(())
+}
+
+/// Write the HFS+ volume sharing the file extents of the ISO tree.
+pub fn write_hfsplus<T>(
+    output_writter: &mut T,
+    tree: &DirectoryEntry,
+    layout: &HfsPlusLayout,
+    bless_folder: Option<&str>,
+    bless_file: Option<&str>,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let old_pos = output_writter.stream_position()?;
+
+    let bless = (
+        bless_folder.map(|path| path.trim_matches('/')),
+        bless_file.map(|path| path.trim_matches('/')),
+    );
+    let (catalog, info) = create_catalog(tree, layout.start_lba, bless)?;
+    assert!(catalog.len() as u32 == layout.catalog_lb_count * LOGIC_SIZE_U32);
+
+    if bless_folder.is_some() && info.bless_folder_id == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "HFS+ blessed folder not found in the ISO tree!",
+        ));
+    }
+
+    if bless_file.is_some() && info.bless_file_id == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "HFS+ blessed file not found in the ISO tree!",
+        ));
+    }
+
+    let mut volume_header: Vec<u8> = Vec::new();
+    write_volume_header(&mut volume_header, layout, &info, catalog.len() as u64)?;
+
+    // Volume header (after the 1024 bytes of boot blocks)
+    output_writter.seek(SeekFrom::Start(
+        u64::from(layout.start_lba * LOGIC_SIZE_U32) + 0x400,
+    ))?;
+    output_writter.write_all(&volume_header)?;
+
+    // Alternate volume header (1024 bytes before the end of the partition)
+    output_writter.seek(SeekFrom::Start(
+        u64::from((layout.start_lba + layout.lb_count) * LOGIC_SIZE_U32) - 0x400,
+    ))?;
+    output_writter.write_all(&volume_header)?;
+    let padding = vec![0u8; 0x400 - volume_header.len()];
+    output_writter.write_all(&padding)?;
+
+    output_writter.seek(SeekFrom::Start(u64::from(
+        layout.catalog_lba * LOGIC_SIZE_U32,
+    )))?;
+    output_writter.write_all(&catalog)?;
+
+    // Empty extents overflow B-tree
+    let extents = write_header_node(0, 0, 0, 0, 0, 10, 1, 1)?;
+    output_writter.seek(SeekFrom::Start(u64::from(
+        layout.extents_lba * LOGIC_SIZE_U32,
+    )))?;
+    output_writter.write_all(&extents)?;
+
+    // Allocation file, every block of the volume is in use
+    let mut bitmap = vec![0u8; (layout.bitmap_lb_count as usize) * LOGIC_SIZE];
+    for block in 0..layout.lb_count as usize {
+        bitmap[block / 8] |= 0x80 >> (block % 8);
+    }
+    output_writter.seek(SeekFrom::Start(u64::from(
+        layout.bitmap_lba * LOGIC_SIZE_U32,
+    )))?;
+    output_writter.write_all(&bitmap)?;
+
+    output_writter.seek(SeekFrom::Start(old_pos))?;
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::{FileEntry, FileType};
+use crate::iso::reader;
+use crate::iso::reader::{IsoEntry, IsoReader, Namespace};
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32};
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::Path;
+
+/// How the files of an imported image get their content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// The extents stay where they are, for a new session of the same medium
+    KeepExtents,
+    /// The extents are copied to the new image
+    CopyExtents,
+}
+
+type Reader = IsoReader<BufReader<File>>;
+
+fn merge_directory(
+    reader: &mut Reader,
+    directory: &IsoEntry,
+    namespace: Namespace,
+    tree: &mut DirectoryEntry,
+    image: &Path,
+    mode: ImportMode,
+    visited: &mut HashSet<u32>,
+) -> io::Result<()> {
+    reader::mark_directory_visited(visited, directory)?;
+
+    for entry in reader.read_directory(directory, namespace)? {
+        // The entries already in the tree replace the imported ones
+        let is_replaced_by_file = tree
+            .files_childs
+            .iter()
+            .any(|file| file.get_file_name() == entry.name);
+
+        if entry.get_symlink().is_none() && entry.is_directory() {
+            if !is_replaced_by_file {
+                let child = tree.get_or_create_directory(&entry.name)?;
+                merge_directory(reader, &entry, namespace, child, image, mode, visited)?;
+            }
+            continue;
+        }
+
+        let is_replaced_by_directory = tree
+            .dir_childs
+            .iter()
+            .any(|child| child.get_file_name() == entry.name);
+        if is_replaced_by_file || is_replaced_by_directory {
+            continue;
+        }
+
+        if entry.get_symlink().is_some() || !entry.is_regular_file() {
+            eprintln!(
+                "warning: skipping \"{}\" of \"{}\", only regular files and directories are supported",
+                entry.name,
+                image.display()
+            );
+            continue;
+        }
+
+        if entry.extents.len() != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "\"{}\" of \"{}\" has multiple extents, this isn't supported!",
+                    entry.name,
+                    image.display()
+                ),
+            ));
+        }
+
+        let (lba, size) = entry.extents[0];
+        let file_type = match mode {
+            ImportMode::KeepExtents => FileType::Previous {
+                name: entry.name.clone(),
+                image: image.to_path_buf(),
+                lba,
+            },
+            ImportMode::CopyExtents => FileType::Slice {
+                name: entry.name.clone(),
+                path: image.to_path_buf(),
+                offset: u64::from(lba) * u64::from(LOGIC_SIZE_U32),
+            },
+        };
+
+        tree.add_file(FileEntry {
+            file_type,
+            size: size as usize,
+            lba,
+            aligned_size: u64::from(size).div_ceil(LOGIC_SIZE as u64) as usize * LOGIC_SIZE,
+            hidden: false,
+        });
+    }
+
+    Ok(())
+}
+
+/// Add the files of the session of `image` starting at `session_lba` to the tree, the entries already in the tree taking precedence.
+pub fn import_tree(
+    tree: &mut DirectoryEntry,
+    image: &Path,
+    session_lba: u32,
+    mode: ImportMode,
+) -> io::Result<()> {
+    let file = File::open(image)?;
+    let mut reader = IsoReader::new_at_session(BufReader::new(file), session_lba)?;
+
+    let namespace = reader.get_default_namespace();
+    let root = reader.get_root(namespace)?;
+
+    merge_directory(
+        &mut reader,
+        &root,
+        namespace,
+        tree,
+        image,
+        mode,
+        &mut HashSet::new(),
+    )
+}
@@ -0,0 +1,313 @@
+use crate::iso::option::InfoOpt;
+use crate::iso::reader::{IsoReader, VolumeDescriptorKind, VolumeInfo};
+use crate::iso::utils;
+
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+fn get_platform_name(platform_id: u8) -> &'static str {
+    match platform_id {
+        0x0 => "80x86",
+        0x1 => "PowerPC",
+        0x2 => "Mac",
+        0xEF => "EFI",
+        _ => "unknown",
+    }
+}
+
+fn get_media_type_name(media_type: u8) -> &'static str {
+    match media_type & 0xF {
+        0x0 => "no emulation",
+        0x1 => "1.2M floppy",
+        0x2 => "1.44M floppy",
+        0x3 => "2.88M floppy",
+        0x4 => "hard disk",
+        _ => "unknown",
+    }
+}
+
+fn print_volume_info(volume_info: &VolumeInfo) {
+    println!("    System id: {}", volume_info.system_id);
+    println!("    Volume id: {}", volume_info.volume_id);
+    println!(
+        "    Volume space size: {} blocks",
+        volume_info.volume_space_size
+    );
+    println!(
+        "    Volume set size: {}, volume sequence number: {}",
+        volume_info.volume_set_size, volume_info.volume_sequence_number
+    );
+    println!("    Logical block size: {}", volume_info.logical_block_size);
+    println!("    Path table size: {} bytes", volume_info.path_table_size);
+    println!(
+        "    Type L path table: LBA {} (optional LBA {})",
+        volume_info.l_path_table_lba, volume_info.optional_l_path_table_lba
+    );
+    println!(
+        "    Type M path table: LBA {} (optional LBA {})",
+        volume_info.m_path_table_lba, volume_info.optional_m_path_table_lba
+    );
+    println!(
+        "    Root directory: LBA {}, {} bytes",
+        volume_info.root_directory.lba, volume_info.root_directory.size
+    );
+    println!("    Volume set id: {}", volume_info.volume_set_id);
+    println!("    Publisher id: {}", volume_info.publisher_id);
+    println!("    Data preparer id: {}", volume_info.data_preparer_id);
+    println!("    Application id: {}", volume_info.application_id);
+    println!("    Creation date: {}", volume_info.creation_date);
+    println!("    Modification date: {}", volume_info.modification_date);
+    println!("    Expiration date: {}", volume_info.expiration_date);
+    println!("    Effective date: {}", volume_info.effective_date);
+    println!(
+        "    File structure version: {}",
+        volume_info.file_structure_version
+    );
+}
+
+fn print_volume_descriptors<R>(reader: &IsoReader<R>) {
+    println!("Volume descriptor set:");
+
+    for volume in &reader.volume_descriptors {
+        match &volume.kind {
+            VolumeDescriptorKind::Boot {
+                system_id,
+                catalog_lba,
+            } => {
+                println!("  LBA {}: Boot Record", volume.lba);
+                println!("    Boot system id: {}", system_id);
+                println!("    Boot catalog: LBA {}", catalog_lba);
+            }
+            VolumeDescriptorKind::Primary(volume_info) => {
+                println!("  LBA {}: Primary Volume Descriptor", volume.lba);
+                print_volume_info(volume_info);
+            }
+            VolumeDescriptorKind::Supplementary(volume_info) => {
+                println!("  LBA {}: Supplementary Volume Descriptor", volume.lba);
+                print_volume_info(volume_info);
+            }
+            VolumeDescriptorKind::Partition => {
+                println!("  LBA {}: Volume Partition Descriptor", volume.lba);
+            }
+            VolumeDescriptorKind::End => {
+                println!("  LBA {}: Volume Descriptor Set Terminator", volume.lba);
+            }
+            VolumeDescriptorKind::Unknown(volume_type) => {
+                println!(
+                    "  LBA {}: unknown volume descriptor type {}",
+                    volume.lba, volume_type
+                );
+            }
+        }
+    }
+}
+
+fn print_boot_catalog<R>(reader: &mut IsoReader<R>) -> io::Result<()>
+where
+    R: io::Read + io::Seek,
+{
+    let catalog = match reader.read_boot_catalog()? {
+        Some(catalog) => catalog,
+        None => return Ok(()),
+    };
+
+    println!();
+    println!("El Torito boot catalog (LBA {}):", catalog.lba);
+    println!(
+        "  Validation entry: platform 0x{:02X} ({}), id \"{}\", checksum {}",
+        catalog.platform_id,
+        get_platform_name(catalog.platform_id),
+        catalog.id_string,
+        if catalog.checksum_valid {
+            "ok"
+        } else {
+            "INVALID"
+        }
+    );
+
+    for (index, entry) in catalog.entries.iter().enumerate() {
+        let kind = if entry.is_default {
+            String::from("default entry")
+        } else {
+            format!(
+                "section entry, platform 0x{:02X} ({})",
+                entry.platform_id,
+                get_platform_name(entry.platform_id)
+            )
+        };
+
+        println!("  Entry {} ({}):", index, kind);
+        println!(
+            "    {}, {}",
+            if entry.bootable {
+                "bootable"
+            } else {
+                "not bootable"
+            },
+            get_media_type_name(entry.media_type)
+        );
+        println!(
+            "    Load segment: 0x{:04X}, system type: 0x{:02X}",
+            entry.load_segment, entry.system_type
+        );
+        println!(
+            "    Image: LBA {}, {} virtual sectors",
+            entry.lba, entry.sector_count
+        );
+    }
+
+    Ok(())
+}
+
+fn print_system_area<R>(reader: &mut IsoReader<R>) -> io::Result<()>
+where
+    R: io::Read + io::Seek,
+{
+    println!();
+    println!("System area:");
+
+    let mut is_empty = true;
+
+    if let Some(mbr) = reader.read_mbr()? {
+        is_empty = false;
+        println!("  MBR partition table (disk id 0x{:08X}):", mbr.disk_id);
+        for (index, partition) in mbr.partitions.iter().enumerate() {
+            if let Some(partition) = partition {
+                println!(
+                    "    Partition {}: type 0x{:02X}{}, start sector {}, {} sectors",
+                    index + 1,
+                    partition.partition_type,
+                    if partition.bootable { ", bootable" } else { "" },
+                    partition.start_sector,
+                    partition.sector_count
+                );
+            }
+        }
+    }
+
+    if let Some(gpt) = reader.read_gpt_at(1)? {
+        is_empty = false;
+        println!(
+            "  GPT (disk GUID {}, header CRC {}, entries CRC {}):",
+            utils::format_guid(&gpt.disk_guid),
+            if gpt.header_crc32_valid {
+                "ok"
+            } else {
+                "INVALID"
+            },
+            if gpt.entries_crc32_valid {
+                "ok"
+            } else {
+                "INVALID"
+            }
+        );
+        println!(
+            "    Header: sector {}, backup header: sector {}",
+            gpt.current_sector, gpt.backup_sector
+        );
+        println!(
+            "    Entries: sector {}, {} entries of {} bytes",
+            gpt.entries_sector, gpt.entry_count, gpt.entry_size
+        );
+        println!(
+            "    Usable sectors: {} to {}",
+            gpt.first_usable_sector, gpt.last_usable_sector
+        );
+        for (index, partition) in &gpt.partitions {
+            println!(
+                "    Partition {}: \"{}\", type {}, sectors {} to {}",
+                index,
+                partition.name,
+                utils::format_guid(&partition.type_guid),
+                partition.start_sector,
+                partition.end_sector
+            );
+        }
+    }
+
+    let apm_partitions = reader.read_apm()?;
+    if !apm_partitions.is_empty() {
+        is_empty = false;
+        println!("  Apple Partition Map:");
+        for (index, partition) in apm_partitions.iter().enumerate() {
+            println!(
+                "    Partition {}: \"{}\", type {}, start block {}, {} blocks",
+                index + 1,
+                partition.name,
+                partition.partition_type,
+                partition.start_block,
+                partition.block_count
+            );
+        }
+    }
+
+    if let Some(sun_label) = reader.read_sun_label()? {
+        is_empty = false;
+        println!(
+            "  Sun disk label \"{}\" (checksum {}):",
+            sun_label.label,
+            if sun_label.checksum_valid {
+                "ok"
+            } else {
+                "INVALID"
+            }
+        );
+        for (index, partition) in &sun_label.partitions {
+            println!(
+                "    Partition {}: start sector {}, {} sectors",
+                index, partition.start_sector, partition.sector_count
+            );
+        }
+    }
+
+    if let Some(volume_header) = reader.read_sgi_volume_header()? {
+        is_empty = false;
+        println!(
+            "  SGI volume header (boot file \"{}\", checksum {}):",
+            volume_header.boot_file_name,
+            if volume_header.checksum_valid {
+                "ok"
+            } else {
+                "INVALID"
+            }
+        );
+        for boot_file in &volume_header.boot_files {
+            println!(
+                "    Boot file \"{}\": start sector {}, {} bytes",
+                boot_file.name, boot_file.sector, boot_file.size
+            );
+        }
+    }
+
+    if let Some(boot_block) = reader.read_dec_boot_block()? {
+        is_empty = false;
+        println!("  DEC boot block:");
+        println!(
+            "    Load address: 0x{:08X}, exec address: 0x{:08X}",
+            boot_block.load_address, boot_block.exec_address
+        );
+        println!(
+            "    Boot map: start sector {}, {} sectors",
+            boot_block.start_sector, boot_block.sector_count
+        );
+    }
+
+    if is_empty {
+        println!("  no partition table");
+    }
+
+    Ok(())
+}
+
+/// Print the structures of an ISO image, like "isoinfo -d".
+pub fn print_info(info_opt: &InfoOpt) -> io::Result<()> {
+    let file = File::open(&info_opt.input)?;
+    let mut reader = IsoReader::new(BufReader::new(file))?;
+
+    print_volume_descriptors(&reader);
+    print_boot_catalog(&mut reader)?;
+    print_system_area(&mut reader)?;
+
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use crate::iso::file_entry::{FileEntry, FileType};
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+
+const INTERVAL_PREFIX: &str = "--interval:";
+
+/// Byte range of a disk file given as "--interval:local_fs:START-END:ZEROIZERS:FILE", like xorriso.
+///
+/// START and END are inclusive and may be suffixed by "d" (512 bytes), "s" (2048 bytes), "k", "m" or "g",
+/// an empty END meaning the end of the file. ZEROIZERS is a comma separated list of "zero_mbrpt",
+/// "zero_gpt" and "zero_apm", clearing the corresponding partition tables of the data.
+#[derive(Debug, Clone)]
+pub struct IntervalSource {
+    pub path: PathBuf,
+    pub offset: u64,
+    pub size: u64,
+    zero_mbr_partition_table: bool,
+    zero_gpt: bool,
+    zero_apm: bool,
+}
+
+fn invalid_interval(value: &str, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("invalid interval \"{}\": {}", value, reason),
+    )
+}
+
+fn parse_interval_bound(value: &str) -> Option<(u64, u64)> {
+    let (number, unit) = match value.char_indices().last()? {
+        (index, 'd') => (&value[..index], u64::from(SECTOR_SIZE)),
+        (index, 's') => (&value[..index], u64::from(LOGIC_SIZE_U32)),
+        (index, 'k') => (&value[..index], 1 << 10),
+        (index, 'm') => (&value[..index], 1 << 20),
+        (index, 'g') => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+
+    Some((number.parse().ok()?, unit))
+}
+
+impl IntervalSource {
+    pub fn is_interval(value: &str) -> bool {
+        value.starts_with(INTERVAL_PREFIX)
+    }
+
+    pub fn parse(value: &str) -> io::Result<IntervalSource> {
+        let parts: Vec<&str> = value
+            .strip_prefix(INTERVAL_PREFIX)
+            .ok_or_else(|| invalid_interval(value, "missing \"--interval:\" prefix"))?
+            .splitn(4, ':')
+            .collect();
+        if parts.len() != 4 {
+            return Err(invalid_interval(
+                value,
+                "expected --interval:local_fs:START-END:ZEROIZERS:FILE",
+            ));
+        }
+
+        if parts[0] != "local_fs" {
+            return Err(invalid_interval(
+                value,
+                "only local_fs sources are supported",
+            ));
+        }
+
+        let path = PathBuf::from(parts[3]);
+        let file_size = path.metadata()?.len();
+
+        let (start, end) = parts[1]
+            .split_once('-')
+            .ok_or_else(|| invalid_interval(value, "expected START-END"))?;
+        let offset = parse_interval_bound(start)
+            .and_then(|(number, unit)| number.checked_mul(unit))
+            .ok_or_else(|| invalid_interval(value, "invalid START"))?;
+        let end = if end.is_empty() {
+            file_size
+        } else {
+            // END designates the last byte or block of the interval
+            parse_interval_bound(end)
+                .and_then(|(number, unit)| number.checked_add(1)?.checked_mul(unit))
+                .ok_or_else(|| invalid_interval(value, "invalid END"))?
+        };
+
+        if end <= offset || end > file_size {
+            return Err(invalid_interval(
+                value,
+                &format!(
+                    "the range doesn't fit in the {} bytes of the file",
+                    file_size
+                ),
+            ));
+        }
+
+        let mut res = IntervalSource {
+            path,
+            offset,
+            size: end - offset,
+            zero_mbr_partition_table: false,
+            zero_gpt: false,
+            zero_apm: false,
+        };
+
+        for zeroizer in parts[2].split(',').filter(|zeroizer| !zeroizer.is_empty()) {
+            match zeroizer {
+                "zero_mbrpt" => res.zero_mbr_partition_table = true,
+                "zero_gpt" => res.zero_gpt = true,
+                "zero_apm" => res.zero_apm = true,
+                _ => {
+                    return Err(invalid_interval(
+                        value,
+                        &format!("unknown zeroizer \"{}\"", zeroizer),
+                    ))
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn has_zeroizers(&self) -> bool {
+        self.zero_mbr_partition_table || self.zero_gpt || self.zero_apm
+    }
+
+    /// Read the data of the interval, with the requested partition tables cleared.
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset))?;
+
+        let mut res = vec![0u8; self.size as usize];
+        file.read_exact(&mut res)?;
+
+        let sector_size = SECTOR_SIZE as usize;
+        let has_mbr_signature =
+            res.len() >= sector_size && res[0x1FE] == 0x55 && res[0x1FF] == 0xAA;
+
+        if self.zero_mbr_partition_table && has_mbr_signature {
+            res[0x1BE..0x1FE].fill(0x0);
+        }
+
+        if self.zero_gpt && res.len() >= 2 * sector_size && &res[0x200..0x208] == b"EFI PART" {
+            let header = &res[sector_size..2 * sector_size];
+            let entries_start = LittleEndian::read_u64(&header[0x48..])
+                .saturating_mul(u64::from(SECTOR_SIZE))
+                .min(res.len() as u64) as usize;
+            let entries_size = u64::from(LittleEndian::read_u32(&header[0x50..]))
+                * u64::from(LittleEndian::read_u32(&header[0x54..]));
+
+            let entries_end = (entries_start as u64)
+                .saturating_add(entries_size)
+                .min(res.len() as u64) as usize;
+            if entries_start < entries_end {
+                res[entries_start..entries_end].fill(0x0);
+            }
+            res[sector_size..2 * sector_size].fill(0x0);
+        }
+
+        if self.zero_apm && res.len() >= 4 && &res[0x0..0x2] == b"ER" {
+            let block_size = match BigEndian::read_u16(&res[0x2..]) as usize {
+                0 => LOGIC_SIZE,
+                block_size => block_size,
+            };
+
+            // Partition map entries follow the Driver Descriptor Map
+            let mut offset = block_size;
+            while offset + 0x2 <= res.len() && &res[offset..offset + 0x2] == b"PM" {
+                let end = std::cmp::min(offset + block_size, res.len());
+                res[offset..end].fill(0x0);
+                offset += block_size;
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Create a file of the ISO tree with the data of the interval.
+    pub fn to_file_entry(&self, name: &str) -> io::Result<FileEntry> {
+        let file_type = if self.has_zeroizers() {
+            FileType::Buffer {
+                name: String::from(name),
+                data: self.read()?,
+            }
+        } else {
+            FileType::Slice {
+                name: String::from(name),
+                path: self.path.clone(),
+                offset: self.offset,
+            }
+        };
+
+        Ok(FileEntry {
+            file_type,
+            size: self.size as usize,
+            lba: 0,
+            aligned_size: self.size.div_ceil(LOGIC_SIZE as u64) as usize * LOGIC_SIZE,
+            hidden: false,
+        })
+    }
+}
+
+/// Read the whole content of a disk file or of an interval of it.
+pub fn read_source(value: &str) -> io::Result<Vec<u8>> {
+    if IntervalSource::is_interval(value) {
+        IntervalSource::parse(value)?.read()
+    } else {
+        std::fs::read(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::iso::utils;
+
+    use std::fs;
+
+    fn get_interval(path: &std::path::Path, range: &str, zeroizers: &str) -> String {
+        format!(
+            "--interval:local_fs:{}:{}:{}",
+            range,
+            zeroizers,
+            path.display()
+        )
+    }
+
+    #[test]
+    fn parse_bounds() {
+        let test_directory = utils::create_test_directory("interval");
+        let path = test_directory.join("disk.img");
+        fs::write(&path, vec![0x0; 0x10000]).unwrap();
+
+        for (range, offset, size) in [
+            ("0-", 0, 0x10000),
+            ("0-511", 0, 0x200),
+            ("1d-1d", 0x200, 0x200),
+            ("2s-", 0x1000, 0xF000),
+            ("4k-15k", 0x1000, 0x3000),
+            ("0-0", 0, 1),
+        ] {
+            let interval = IntervalSource::parse(&get_interval(&path, range, "")).unwrap();
+            assert_eq!(
+                (interval.offset, interval.size),
+                (offset, size),
+                "{}",
+                range
+            );
+            assert!(!interval.has_zeroizers());
+        }
+
+        for range in [
+            "",
+            "0",
+            "x-",
+            "1-0",
+            "0-64k",
+            "64k-",
+            "18446744073709551615-",
+            "0-18446744073709551615",
+            "18014398509481984k-",
+            "0-18014398509481983k",
+        ] {
+            let error = IntervalSource::parse(&get_interval(&path, range, "")).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{}", range);
+        }
+
+        assert!(IntervalSource::parse(&get_interval(&path, "0-", "zero_foo")).is_err());
+        assert!(IntervalSource::parse(&format!("--interval:http:0-:{}", path.display())).is_err());
+        assert!(
+            IntervalSource::parse(&get_interval(&test_directory.join("missing"), "0-", ""))
+                .is_err()
+        );
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+
+    #[test]
+    fn read_zeroized() {
+        let test_directory = utils::create_test_directory("interval-zeroized");
+        let path = test_directory.join("disk.img");
+
+        // MBR partition table and signature, then a GPT header pointing to its entries at sector 2
+        let mut data = vec![0xAB; 0x2000];
+        data[0x1FE] = 0x55;
+        data[0x1FF] = 0xAA;
+        data[0x200..0x208].copy_from_slice(b"EFI PART");
+        data[0x248..0x250].copy_from_slice(&2u64.to_le_bytes());
+        data[0x250..0x254].copy_from_slice(&4u32.to_le_bytes());
+        data[0x254..0x258].copy_from_slice(&0x80u32.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+
+        let interval =
+            IntervalSource::parse(&get_interval(&path, "0-", "zero_mbrpt,zero_gpt")).unwrap();
+        assert!(interval.has_zeroizers());
+
+        let res = interval.read().unwrap();
+        assert_eq!(res.len(), data.len());
+        assert!(res[0x1BE..0x1FE].iter().all(|byte| *byte == 0x0));
+        assert_eq!(&res[0x1FE..0x200], &[0x55, 0xAA]);
+        assert!(res[0x200..0x600].iter().all(|byte| *byte == 0x0));
+        assert!(res[0x600..].iter().all(|byte| *byte == 0xAB));
+
+        // Entries located beyond the interval are ignored
+        data[0x248..0x250].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        let res = interval.read().unwrap();
+        assert!(res[0x200..0x400].iter().all(|byte| *byte == 0x0));
+        assert!(res[0x400..].iter().all(|byte| *byte == 0xAB));
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+// Area of the stage 1 that must not be overwritten (disk timestamp)
+const TIMESTAMP_START: usize = 218;
+const TIMESTAMP_END: usize = 224;
+
+// Start of the disk signature and partition table
+const BOOT_CODE_END: usize = 440;
+
+const STAGE2_SIZE_OFFSET: u64 = 0x1A4;
+
+/// Limine BIOS hard drive boot code, its stage 1 is installed in the MBR and its stage 2 appended after the ISO image.
+#[derive(Debug, Clone)]
+pub struct LimineBiosHdd {
+    data: Vec<u8>,
+    pub lba: u32,
+}
+
+impl LimineBiosHdd {
+    pub fn new(path: &str) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        if data.len() <= SECTOR_SIZE as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("\"{}\" isn't a Limine BIOS hard drive image!", path),
+            ));
+        }
+
+        Ok(LimineBiosHdd { data, lba: 0 })
+    }
+
+    fn get_stage2(&self) -> &[u8] {
+        &self.data[SECTOR_SIZE as usize..]
+    }
+
+    /// Get the size of the two halves of the stage 2, each one is loaded separately by the stage 1.
+    fn get_stage2_sizes(&self) -> io::Result<(u16, u16)> {
+        let sector_count = self.get_stage2().len().div_ceil(SECTOR_SIZE as usize);
+        let size_b = (sector_count / 2) * SECTOR_SIZE as usize;
+        let size_a = size_b + (sector_count % 2) * SECTOR_SIZE as usize;
+
+        // The stage 1 holds the sizes on 16 bits
+        match (u16::try_from(size_a), u16::try_from(size_b)) {
+            (Ok(size_a), Ok(size_b)) => Ok((size_a, size_b)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Limine stage 2 of {} bytes is too big for the stage 1!",
+                    self.get_stage2().len()
+                ),
+            )),
+        }
+    }
+
+    pub fn get_lb_count(&self) -> io::Result<u32> {
+        let (size_a, size_b) = self.get_stage2_sizes()?;
+        Ok((u32::from(size_a) + u32::from(size_b)).div_ceil(LOGIC_SIZE_U32))
+    }
+
+    /// Install the stage 1 in the MBR starting at `mbr_pos`, keeping the disk timestamp, signature and partition table.
+    pub fn write_stage1<T>(&self, output_writter: &mut T, mbr_pos: u64) -> io::Result<()>
+    where
+        T: Write + Seek,
+    {
+        let current_pos = output_writter.stream_position()?;
+
+        output_writter.seek(SeekFrom::Start(mbr_pos))?;
+        output_writter.write_all(&self.data[..TIMESTAMP_START])?;
+
+        output_writter.seek(SeekFrom::Start(mbr_pos + TIMESTAMP_END as u64))?;
+        output_writter.write_all(&self.data[TIMESTAMP_END..BOOT_CODE_END])?;
+
+        // Size and location (in bytes) of the two halves of the stage 2
+        let (size_a, size_b) = self.get_stage2_sizes()?;
+        let location_a = u64::from(self.lba) * LOGIC_SIZE as u64;
+        let location_b = location_a + u64::from(size_a);
+
+        output_writter.seek(SeekFrom::Start(mbr_pos + STAGE2_SIZE_OFFSET))?;
+        output_writter.write_u16::<LittleEndian>(size_a)?;
+        output_writter.write_u16::<LittleEndian>(size_b)?;
+        output_writter.write_u64::<LittleEndian>(location_a)?;
+        output_writter.write_u64::<LittleEndian>(location_b)?;
+
+        output_writter.seek(SeekFrom::Start(current_pos))?;
+
+        Ok(())
+    }
+
+    pub fn write_stage2<T>(&self, output_writter: &mut T) -> io::Result<()>
+    where
+        T: Write + Seek,
+    {
+        let current_pos = output_writter.stream_position()?;
+
+        let mut stage2 = self.get_stage2().to_vec();
+
+        // Pad to LBA size
+        stage2.resize(self.get_lb_count()? as usize * LOGIC_SIZE, 0u8);
+
+        output_writter.seek(SeekFrom::Start(u64::from(self.lba) * LOGIC_SIZE as u64))?;
+        output_writter.write_all(&stage2)?;
+
+        output_writter.seek(SeekFrom::Start(current_pos))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use crate::iso::option::LsOpt;
+use crate::iso::reader;
+use crate::iso::reader::{IsoEntry, IsoReader, Namespace};
+
+use chrono::{TimeZone, Utc};
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+fn format_mode(entry: &IsoEntry) -> String {
+    let mode = match entry
+        .rock_ridge
+        .as_ref()
+        .and_then(|rock_ridge| rock_ridge.mode)
+    {
+        Some(mode) => mode,
+        // Plain ISO 9660 entries are read-only
+        None if entry.is_directory() => 0o040_555,
+        None => 0o100_444,
+    };
+
+    let file_type = match mode & 0o170_000 {
+        0o040_000 => 'd',
+        0o120_000 => 'l',
+        0o020_000 => 'c',
+        0o060_000 => 'b',
+        0o010_000 => 'p',
+        0o140_000 => 's',
+        _ => '-',
+    };
+
+    let mut res = String::from(file_type);
+    for (bit, c) in ['r', 'w', 'x', 'r', 'w', 'x', 'r', 'w', 'x']
+        .iter()
+        .enumerate()
+    {
+        let mask = 0o400 >> bit;
+        res.push(if mode & mask != 0 { *c } else { '-' });
+    }
+
+    res
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(date) => date.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => String::from("-"),
+    }
+}
+
+fn format_optional(value: Option<u32>) -> String {
+    value.map_or_else(|| String::from("-"), |value| value.to_string())
+}
+
+fn print_entry(entry: &IsoEntry, namespace: Namespace) {
+    let rock_ridge = entry.rock_ridge.clone().unwrap_or_default();
+
+    let mut line = format!(
+        "{} {:>3} {:>5} {:>5} {:>10}  LBA {:>7}  recorded {}  modified {}  {}",
+        format_mode(entry),
+        format_optional(rock_ridge.links),
+        format_optional(rock_ridge.uid),
+        format_optional(rock_ridge.gid),
+        entry.get_size(),
+        entry.extents.first().map_or(0, |(lba, _)| *lba),
+        format_timestamp(entry.get_recording_time()),
+        format_timestamp(entry.get_modification_time()),
+        entry.identifier
+    );
+
+    if entry.extents.len() > 1 {
+        line.push_str(&format!(" ({} extents)", entry.extents.len()));
+    }
+
+    if namespace == Namespace::RockRidge {
+        line.push_str(&format!("  {}", rock_ridge.name.as_deref().unwrap_or("-")));
+    }
+
+    if let Some(target) = entry.get_symlink() {
+        line.push_str(&format!(" -> {}", target));
+    }
+
+    println!("{}", line);
+}
+
+fn list_directory<R>(
+    reader: &mut IsoReader<R>,
+    directory: &IsoEntry,
+    path: &str,
+    namespace: Namespace,
+    visited: &mut HashSet<u32>,
+) -> io::Result<()>
+where
+    R: io::Read + io::Seek,
+{
+    reader::mark_directory_visited(visited, directory)?;
+    println!("{}:", path);
+
+    let entries = reader.read_directory(directory, namespace)?;
+    for entry in &entries {
+        print_entry(entry, namespace);
+    }
+
+    for entry in entries.iter().filter(|entry| entry.is_directory()) {
+        println!();
+        list_directory(
+            reader,
+            entry,
+            &format!("{}/{}", path.trim_end_matches('/'), entry.name),
+            namespace,
+            visited,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// List the directory tree of an ISO image, like "ls -lR" with the extent location of each entry.
+pub fn list_iso(ls_opt: &LsOpt) -> io::Result<()> {
+    let file = File::open(&ls_opt.input)?;
+    let mut reader = IsoReader::new(BufReader::new(file))?;
+
+    let namespace = ls_opt
+        .namespace
+        .unwrap_or_else(|| reader.get_default_namespace());
+    if !reader.has_namespace(namespace) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no {:?} namespace in the ISO image", namespace),
+        ));
+    }
+
+    let entry = reader.find_entry(&ls_opt.path, namespace)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("\"{}\" not found in the ISO image", ls_opt.path),
+        )
+    })?;
+
+    if entry.is_directory() {
+        let path = format!("/{}", ls_opt.path.trim_matches('/'));
+        list_directory(&mut reader, &entry, &path, namespace, &mut HashSet::new())
+    } else {
+        print_entry(&entry, namespace);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::iso;
+    use crate::iso::option::Opt;
+    use crate::iso::utils;
+
+    use structopt::StructOpt;
+
+    use std::fs;
+
+    #[test]
+    fn list_created_image() {
+        let test_directory = utils::create_test_directory("ls");
+        let source_directory = test_directory.join("source");
+        let image = test_directory.join("test.iso");
+        fs::create_dir_all(source_directory.join("beta")).unwrap();
+        fs::create_dir_all(source_directory.join("Zeta")).unwrap();
+        fs::write(source_directory.join("alpha.txt"), b"alpha").unwrap();
+        fs::write(source_directory.join("beta/inner.bin"), vec![0x0; 3000]).unwrap();
+
+        let mut opt = Opt::from_iter(vec![
+            "mkisofs-rs",
+            "-o",
+            image.to_str().unwrap(),
+            source_directory.to_str().unwrap(),
+        ]);
+        iso::create_iso(&mut opt).unwrap();
+
+        let mut reader = IsoReader::new(BufReader::new(File::open(&image).unwrap())).unwrap();
+        assert_eq!(reader.get_default_namespace(), Namespace::RockRidge);
+
+        let root = reader.get_root(Namespace::RockRidge).unwrap();
+        let entries = reader.read_directory(&root, Namespace::RockRidge).unwrap();
+        let mut names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Zeta", "alpha.txt", "beta"]);
+
+        for entry in &entries {
+            assert_eq!(entry.is_directory(), entry.name != "alpha.txt");
+            assert_eq!(format_mode(entry).starts_with('d'), entry.is_directory());
+        }
+
+        let entry = reader
+            .find_entry("/beta/inner.bin", Namespace::RockRidge)
+            .unwrap()
+            .unwrap();
+        assert!(entry.is_regular_file());
+        assert_eq!(entry.get_size(), 3000);
+
+        let mut ls_opt = LsOpt {
+            input: image,
+            namespace: None,
+            path: String::from("/"),
+        };
+        list_iso(&ls_opt).unwrap();
+
+        ls_opt.path = String::from("/missing");
+        assert_eq!(
+            list_iso(&ls_opt).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+}
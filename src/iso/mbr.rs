@@ -0,0 +1,87 @@
+use crate::iso::utils;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+pub const MBR_PARTITION_COUNT: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct MbrPartition {
+    pub bootable: bool,
+    pub partition_type: u8,
+    pub start_sector: u32,
+    pub sector_count: u32,
+}
+
+impl MbrPartition {
+    fn write_entry<T>(
+        &self,
+        output_writter: &mut T,
+        head_count: u32,
+        sector_count: u32,
+    ) -> std::io::Result<()>
+    where
+        T: Write,
+    {
+        let boot_indicator = if self.bootable { 0x80 } else { 0x0 };
+        output_writter.write_u8(boot_indicator)?;
+
+        // CHS address start
+        utils::write_lba_to_cls(output_writter, self.start_sector, head_count, sector_count)?;
+
+        output_writter.write_u8(self.partition_type)?;
+
+        // CHS address end
+        utils::write_lba_to_cls(
+            output_writter,
+            self.start_sector + self.sector_count - 1,
+            head_count,
+            sector_count,
+        )?;
+
+        // partition offset
+        output_writter.write_u32::<LittleEndian>(self.start_sector)?;
+
+        // partition size
+        output_writter.write_u32::<LittleEndian>(self.sector_count)?;
+
+        Ok(())
+    }
+}
+
+/// Write the MBR partition table and boot signature of the system area starting at `mbr_pos`.
+pub fn write_partition_table<T>(
+    output_writter: &mut T,
+    mbr_pos: u64,
+    partitions: &[Option<MbrPartition>; MBR_PARTITION_COUNT],
+    head_count: u32,
+    sector_count: u32,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let current_pos = output_writter.stream_position()?;
+
+    output_writter.seek(SeekFrom::Start(mbr_pos + 0x1BE))?;
+
+    for partition in partitions {
+        match partition {
+            Some(partition) => partition.write_entry(output_writter, head_count, sector_count)?,
+            None => {
+                // Clean unused boot entries
+                let empty_data: [u8; 0x10] = [0x0; 0x10];
+                output_writter.write_all(&empty_data)?;
+            }
+        }
+    }
+
+    // write "valid bootsector"
+    output_writter.write_u8(0x55)?;
+    output_writter.write_u8(0xAA)?;
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
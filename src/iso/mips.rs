@@ -0,0 +1,217 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::FileEntry;
+use crate::iso::utils::SECTOR_SIZE;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+/// Maximum count of boot files in the volume directory of the SGI volume header.
+pub const VOLUME_DIRECTORY_ENTRY_COUNT: usize = 15;
+
+const SGI_VOLUME_HEADER_MAGIC: u32 = 0x0BE5_A941;
+const SGI_PARTITION_COUNT: usize = 16;
+
+// SGI partition types
+const SGI_VOLUME_HEADER_PARTITION: u32 = 0x0;
+const SGI_VOLUME_PARTITION: u32 = 0x6;
+
+const DEC_BOOT_BLOCK_OFFSET: u64 = 0x1E0;
+const DEC_BOOT_MAGIC: u32 = 0x0002_757A;
+
+// DEC boot modes
+const DEC_BOOT_MODE_CONTIGUOUS: u32 = 0x0;
+
+fn get_boot_file<'a>(tree: &'a mut DirectoryEntry, path: &str) -> io::Result<&'a mut FileEntry> {
+    tree.get_file(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("MIPS boot file \"{}\" not found in the ISO tree!", path),
+        )
+    })
+}
+
+/// Write the SGI volume header in the first 512 bytes of the system area starting at `label_pos`.
+///
+/// The volume directory references the given boot files, which must already have their final LBA.
+pub fn write_sgi_volume_header<T>(
+    output_writter: &mut T,
+    label_pos: u64,
+    tree: &mut DirectoryEntry,
+    boot_files: &[String],
+    disk_sector_count: u32,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    if boot_files.len() > VOLUME_DIRECTORY_ENTRY_COUNT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "too many MIPS boot files (at most {})",
+                VOLUME_DIRECTORY_ENTRY_COUNT
+            ),
+        ));
+    }
+
+    let mut volume_directory: Vec<u8> = Vec::new();
+    let mut default_boot_file = None;
+    for path in boot_files {
+        let file = get_boot_file(tree, path)?;
+
+        // Names are limited to 8 characters
+        let mut name: [u8; 0x8] = [0x0; 0x8];
+        let file_name = file.get_file_name();
+        let len = std::cmp::min(file_name.len(), name.len());
+        name[..len].copy_from_slice(&file_name.as_bytes()[..len]);
+
+        if default_boot_file.is_none() {
+            default_boot_file = Some(name);
+        }
+
+        volume_directory.write_all(&name)?;
+        volume_directory.write_u32::<BigEndian>(file.lba * 4)?;
+        volume_directory.write_u32::<BigEndian>(file.size as u32)?;
+    }
+    volume_directory.resize(VOLUME_DIRECTORY_ENTRY_COUNT * 0x10, 0u8);
+
+    let mut buff: Vec<u8> = Vec::new();
+
+    buff.write_u32::<BigEndian>(SGI_VOLUME_HEADER_MAGIC)?;
+
+    // Root and swap partitions
+    buff.write_u16::<BigEndian>(0x0)?;
+    buff.write_u16::<BigEndian>(0x0)?;
+
+    // Name of the default boot file
+    let mut boot_file_name: [u8; 0x10] = [0x0; 0x10];
+    if let Some(name) = default_boot_file {
+        boot_file_name[..name.len()].copy_from_slice(&name);
+    }
+    buff.write_all(&boot_file_name)?;
+
+    // Device parameters (only the sector size is relevant for a CD-ROM)
+    let device_parameters: [u8; 0x10] = [0x0; 0x10];
+    buff.write_all(&device_parameters)?;
+    buff.write_u16::<BigEndian>(SECTOR_SIZE as u16)?;
+    let device_parameters: [u8; 0x1E] = [0x0; 0x1E];
+    buff.write_all(&device_parameters)?;
+
+    buff.write_all(&volume_directory)?;
+
+    // Partition table, the boot files need to be inside of the volume header partition
+    for index in 0..SGI_PARTITION_COUNT {
+        let partition_type = match index {
+            8 => Some(SGI_VOLUME_HEADER_PARTITION),
+            10 => Some(SGI_VOLUME_PARTITION),
+            _ => None,
+        };
+
+        match partition_type {
+            Some(partition_type) => {
+                buff.write_u32::<BigEndian>(disk_sector_count)?;
+                buff.write_u32::<BigEndian>(0x0)?;
+                buff.write_u32::<BigEndian>(partition_type)?;
+            }
+            None => {
+                let empty_data: [u8; 0xC] = [0x0; 0xC];
+                buff.write_all(&empty_data)?;
+            }
+        }
+    }
+
+    // The sum of all the 32-bit words of the volume header must be 0
+    let checksum = buff
+        .chunks(4)
+        .fold(0u32, |sum, word| {
+            sum.wrapping_add(BigEndian::read_u32(word))
+        })
+        .wrapping_neg();
+    buff.write_u32::<BigEndian>(checksum)?;
+    buff.resize(SECTOR_SIZE as usize, 0u8);
+
+    let current_pos = output_writter.stream_position()?;
+
+    output_writter.seek(SeekFrom::Start(label_pos))?;
+    output_writter.write_all(&buff)?;
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
+
+struct ElfLoadSegment {
+    entry_address: u32,
+    load_address: u32,
+    file_offset: u32,
+    file_size: u32,
+}
+
+/// Get the first loadable segment of a 32-bit little endian ELF executable.
+fn get_elf_load_segment(data: &[u8]) -> Option<ElfLoadSegment> {
+    if data.len() < 0x34 || &data[0x0..0x4] != b"\x7FELF" || data[0x4] != 0x1 || data[0x5] != 0x1 {
+        return None;
+    }
+
+    let entry_address = LittleEndian::read_u32(&data[0x18..]);
+    let program_header_offset = LittleEndian::read_u32(&data[0x1C..]) as usize;
+    let program_header_size = usize::from(LittleEndian::read_u16(&data[0x2A..]));
+    let program_header_count = usize::from(LittleEndian::read_u16(&data[0x2C..]));
+
+    (0..program_header_count)
+        .map(|index| program_header_offset + index * program_header_size)
+        .filter(|offset| offset + 0x20 <= data.len())
+        .map(|offset| &data[offset..offset + 0x20])
+        .find(|program_header| LittleEndian::read_u32(program_header) == 0x1)
+        .map(|program_header| ElfLoadSegment {
+            entry_address,
+            load_address: LittleEndian::read_u32(&program_header[0x8..]),
+            file_offset: LittleEndian::read_u32(&program_header[0x4..]),
+            file_size: LittleEndian::read_u32(&program_header[0x10..]),
+        })
+}
+
+/// Write the DEC boot block in the system area starting at `label_pos`, loading the first segment of the given ELF boot file.
+pub fn write_dec_boot_block<T>(
+    output_writter: &mut T,
+    label_pos: u64,
+    tree: &mut DirectoryEntry,
+    boot_file: &str,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let file = get_boot_file(tree, boot_file)?;
+
+    let mut content: Box<dyn Read> = file.open_content_provider();
+    let mut data: Vec<u8> = Vec::new();
+    content.read_to_end(&mut data)?;
+
+    let segment = get_elf_load_segment(&data).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "MIPS boot file \"{}\" is not a 32-bit little endian ELF executable!",
+                boot_file
+            ),
+        )
+    })?;
+
+    let current_pos = output_writter.stream_position()?;
+
+    output_writter.seek(SeekFrom::Start(label_pos + DEC_BOOT_BLOCK_OFFSET))?;
+    output_writter.write_u32::<LittleEndian>(DEC_BOOT_MAGIC)?;
+    output_writter.write_u32::<LittleEndian>(DEC_BOOT_MODE_CONTIGUOUS)?;
+    output_writter.write_u32::<LittleEndian>(segment.load_address)?;
+    output_writter.write_u32::<LittleEndian>(segment.entry_address)?;
+
+    // Boot map of the segment (in 512-byte sectors)
+    output_writter.write_u32::<LittleEndian>(segment.file_size.div_ceil(SECTOR_SIZE))?;
+    output_writter.write_u32::<LittleEndian>(file.lba * 4 + segment.file_offset / SECTOR_SIZE)?;
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
@@ -1,14 +1,39 @@
 #[macro_use]
 mod utils;
+mod apm;
+mod appended_partition;
+mod boot_replay;
+pub mod diff;
 mod directory_entry;
+pub mod edit;
+pub mod extract;
+mod fat;
 mod file_entry;
+mod gpt;
+mod hfsplus;
+mod import;
+pub mod info;
+mod interval;
+mod limine;
+pub mod ls;
+mod mbr;
+mod mips;
+mod multisession;
 pub mod option;
+mod preset;
+mod reader;
+pub mod rescue;
+mod sun_label;
+mod udf;
+pub mod verify;
 mod volume_descriptor;
 
 use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 
+use crate::iso::appended_partition::AppendedPartition;
 use crate::iso::directory_entry::DirectoryEntry;
 use crate::iso::file_entry::{FileEntry, FileType};
+use crate::iso::interval::IntervalSource;
 use crate::iso::utils::SECTOR_SIZE;
 use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32};
 use crate::iso::volume_descriptor::VolumeDescriptor;
@@ -16,44 +41,87 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::Cursor;
 use std::io::SeekFrom;
-use std::path::PathBuf;
-use std::str::FromStr;
 
-fn assign_directory_identifiers(
-    tree: &mut DirectoryEntry,
-    last_index: &mut u32,
-    last_lba: &mut u32,
-) {
+struct ImageLayout {
+    /// Size of the ISO 9660 filesystem in LB
+    iso_lb_count: u32,
+
+    /// Size of the ISO 9660 filesystem padded to the partition alignment in LB
+    iso_partition_lb_count: u32,
+
+    /// Size of the whole image (ISO 9660 filesystem, appended partitions and backup GPT) in LB
+    image_lb_count: u32,
+
+    appended_partitions: Vec<AppendedPartition>,
+
+    hfsplus: Option<hfsplus::HfsPlusLayout>,
+
+    udf: Option<udf::UdfLayout>,
+
+    /// SPARC boot images appended after the ISO 9660 filesystem, described by the Sun disk label
+    sparc_boot_images: Vec<AppendedPartition>,
+
+    /// Limine stage 2 appended after the ISO 9660 filesystem and the appended partitions
+    limine_bios_hdd: Option<limine::LimineBiosHdd>,
+}
+
+fn assign_directory_identifiers(tree: &mut DirectoryEntry, is_root: bool, last_lba: &mut u32) {
     // Reserve CE space for SUSP
     if tree.continuation_area.is_some() {
         *last_lba += 1;
     }
 
-    if *last_index == 0 {
-        tree.parent_index = *last_index;
-        tree.path_table_index = *last_index + 1;
-
-        *last_index = tree.path_table_index;
-    } else {
+    if !is_root {
         tree.lba = *last_lba;
     }
     *last_lba += tree.get_extent_size_in_lb();
 
     for entry in &mut tree.dir_childs {
-        entry.parent_index = tree.path_table_index;
-        entry.path_table_index = *last_index + 1;
+        assign_directory_identifiers(entry, false, last_lba);
+    }
+}
 
-        *last_index = entry.path_table_index;
+/// Number the directories found `depth` levels below `tree`, returning false when there is none.
+fn assign_path_table_level(tree: &mut DirectoryEntry, depth: u32, last_index: &mut u32) -> bool {
+    if depth == 0 {
+        for entry in &mut tree.dir_childs {
+            entry.parent_index = tree.path_table_index;
+            entry.path_table_index = *last_index + 1;
+
+            *last_index = entry.path_table_index;
+        }
+
+        return !tree.dir_childs.is_empty();
     }
 
+    let mut res = false;
     for entry in &mut tree.dir_childs {
-        assign_directory_identifiers(entry, last_index, last_lba);
+        res |= assign_path_table_level(entry, depth - 1, last_index);
+    }
+
+    res
+}
+
+/// Number the directories level by level as the path table is sorted by level then by parent number (ECMA-119 9.4.3).
+fn assign_path_table_indices(tree: &mut DirectoryEntry) {
+    tree.parent_index = 1;
+    tree.path_table_index = 1;
+
+    let mut last_index = tree.path_table_index;
+    let mut depth = 0;
+    while assign_path_table_level(tree, depth, &mut last_index) {
+        depth += 1;
     }
 }
 
 fn reserve_file_space(directory_entry: &mut DirectoryEntry, current_lba: &mut u32) {
     for child_file in &mut directory_entry.files_childs {
-        let lba_count = ((child_file.size as u32) + LOGIC_SIZE_U32) / LOGIC_SIZE_U32;
+        // Files of the previous sessions keep their extent
+        if let FileType::Previous { .. } = child_file.file_type {
+            continue;
+        }
+
+        let lba_count = (child_file.size as u64).div_ceil(LOGIC_SIZE as u64) as u32;
         child_file.lba = *current_lba;
         *current_lba += lba_count;
     }
@@ -63,31 +131,191 @@ fn reserve_file_space(directory_entry: &mut DirectoryEntry, current_lba: &mut u3
     }
 }
 
+/// Find a file too big to be described by a single ISO 9660 directory record.
+fn find_big_file(directory_entry: &DirectoryEntry, parent_path: &str) -> Option<String> {
+    for child_file in &directory_entry.files_childs {
+        if child_file.size > u32::MAX as usize {
+            return Some(format!("{}/{}", parent_path, child_file.get_file_name()));
+        }
+    }
+
+    directory_entry
+        .dir_childs
+        .iter()
+        .find_map(|child_directory| {
+            find_big_file(
+                child_directory,
+                &format!("{}/{}", parent_path, child_directory.get_file_name()),
+            )
+        })
+}
+
 fn generate_volume_descriptors(opt: &option::Opt) -> Vec<VolumeDescriptor> {
     let mut res: Vec<VolumeDescriptor> = Vec::new();
 
     res.push(VolumeDescriptor::Primary);
-    if opt.eltorito_opt.eltorito_boot.is_some() {
-        res.push(VolumeDescriptor::Boot);
+    if opt.eltorito_opt.has_boot_catalog() {
+        res.push(VolumeDescriptor::Boot {
+            catalog_path: opt.eltorito_opt.eltorito_catalog.clone(),
+        });
     }
     res.push(VolumeDescriptor::End);
 
     res
 }
 
-fn create_boot_catalog(tree: &mut DirectoryEntry) {
-    let catalog_file = FileEntry::new_buffered(String::from("boot.catalog"));
-    tree.add_file(catalog_file);
+/// Add the El Torito boot images given as intervals as hidden files at the root of the tree.
+fn add_interval_boot_images(
+    tree: &mut DirectoryEntry,
+    opt: &mut option::Opt,
+) -> std::io::Result<()> {
+    let eltorito_opt = &mut opt.eltorito_opt;
+    let boot_images = [
+        (&mut eltorito_opt.eltorito_boot, "eltorito_boot.img"),
+        (&mut eltorito_opt.efi_boot, "efi_boot.img"),
+    ];
+
+    for (boot_image, name) in boot_images {
+        let interval = match boot_image.as_deref() {
+            Some(value) if IntervalSource::is_interval(value) => IntervalSource::parse(value)?,
+            _ => continue,
+        };
+
+        if tree.get_file(name).is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("\"{}\" already exists in the ISO tree!", name),
+            ));
+        }
+
+        let mut file = interval.to_file_entry(name)?;
+        file.hidden = true;
+        tree.add_file(file);
+
+        *boot_image = Some(String::from(name));
+    }
+
+    Ok(())
+}
+
+fn create_boot_catalog(tree: &mut DirectoryEntry, opt: &option::Opt) -> std::io::Result<()> {
+    let mut catalog_file = FileEntry::new_buffered(String::from("boot.catalog"));
+    catalog_file.hidden = opt.eltorito_opt.hide_boot_catalog;
+
+    // Reserve its LB, the catalog is only filled once the boot images are located
+    catalog_file.size = LOGIC_SIZE;
+    tree.add_file_at(&opt.eltorito_opt.eltorito_catalog, catalog_file)?;
+
+    Ok(())
+}
+
+/// Entry of the El Torito boot catalog, the first one of the catalog being the default entry.
+struct BootCatalogEntry {
+    platform_id: u8,
+    bootable: bool,
+    media_type: u8,
+    load_segment: u16,
+    system_type: u8,
+    sector_count: u16,
+    lba: u32,
 }
 
-fn fill_boot_catalog(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::io::Result<()> {
-    let value = opt.eltorito_opt.eltorito_boot.clone().unwrap();
-    let eltorito_boot_file: &mut FileEntry = tree.get_file(&value).unwrap();
-    let sector_count = (opt.boot_load_size * SECTOR_SIZE) / LOGIC_SIZE_U32;
+fn write_boot_catalog_entry(buff: &mut Vec<u8>, entry: &BootCatalogEntry) -> std::io::Result<()> {
+    let boot_indicator = if entry.bootable { 0x88 } else { 0x0 };
 
-    let eltorito_lba = eltorito_boot_file.lba;
+    buff.write_u8(boot_indicator)?;
 
-    let file: &mut FileEntry = tree.get_file("boot.catalog").unwrap();
+    // Boot medium type (0 = no emulation, only replayed catalogs use the emulation modes)
+    buff.write_u8(entry.media_type)?;
+
+    // Load segment (0 means default, 0x7C0)
+    buff.write_u16::<LittleEndian>(entry.load_segment)?;
+
+    // System Type. "This must be a copy of byte 5 (System Type) from the Partition Table found in the boot image."
+    // As we don't emulate harddrive, this is 0 here unless replayed
+    buff.write_u8(entry.system_type)?;
+
+    // Unused (0xC - 0x1F)
+    buff.write_u8(0x0)?;
+
+    // Sector count
+    buff.write_u16::<LittleEndian>(entry.sector_count)?;
+
+    // LBA of the file
+    buff.write_u32::<LittleEndian>(entry.lba)?;
+
+    // Unused (or selection criteria for section entries)
+    let unused: [u8; 0x14] = [0x0; 0x14];
+    buff.write_all(&unused)?;
+
+    Ok(())
+}
+
+fn get_boot_catalog_entries(
+    tree: &mut DirectoryEntry,
+    opt: &option::Opt,
+) -> std::io::Result<Vec<BootCatalogEntry>> {
+    let mut res = Vec::new();
+
+    if let Some(value) = &opt.eltorito_opt.eltorito_boot {
+        let eltorito_boot_file: &mut FileEntry = tree.get_file(value).unwrap();
+
+        res.push(BootCatalogEntry {
+            platform_id: 0x0,
+            bootable: !opt.eltorito_opt.no_boot,
+            media_type: 0x0,
+            load_segment: opt.eltorito_opt.boot_load_seg,
+            system_type: 0x0,
+            sector_count: opt.boot_load_size.get_sector_count(eltorito_boot_file.size),
+            lba: eltorito_boot_file.lba,
+        });
+    }
+
+    if let Some(value) = &opt.eltorito_opt.efi_boot {
+        let efi_boot_file: &mut FileEntry = tree.get_file(value).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("EFI boot image \"{}\" not found in the ISO tree!", value),
+            )
+        })?;
+
+        res.push(BootCatalogEntry {
+            platform_id: 0xEF,
+            bootable: true,
+            media_type: 0x0,
+            load_segment: 0x0,
+            system_type: 0x0,
+            // The whole FAT image needs to be loaded by the firmware
+            sector_count: option::BootLoadSize::Auto.get_sector_count(efi_boot_file.size),
+            lba: efi_boot_file.lba,
+        });
+    }
+
+    Ok(res)
+}
+
+fn get_validation_header_checksum(header: &[u8]) -> u16 {
+    // The sum of all the words of the validation header must be 0
+    header
+        .chunks(2)
+        .fold(0u16, |sum, word| {
+            sum.wrapping_add(u16::from_le_bytes([word[0], word[1]]))
+        })
+        .wrapping_neg()
+}
+
+fn fill_boot_catalog(
+    tree: &mut DirectoryEntry,
+    opt: &mut option::Opt,
+    boot_replay: Option<&boot_replay::BootReplay>,
+) -> std::io::Result<()> {
+    let entries = match boot_replay {
+        Some(boot_replay) => boot_replay.get_boot_catalog_entries(tree)?,
+        None => get_boot_catalog_entries(tree, opt)?,
+    };
+    let (default_entry, section_entries) = entries.split_first().unwrap();
+
+    let file: &mut FileEntry = tree.get_file(&opt.eltorito_opt.eltorito_catalog).unwrap();
 
     let mut buff: Vec<u8> = Vec::new();
 
@@ -97,47 +325,61 @@ fn fill_boot_catalog(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::i
     buff.write_u8(0x1)?;
 
     // Plateform ID (0x0 = 80x86, 0x1 = PowerPC, 0x2 = Mac, 0xef = EFI)
-    // TODO: UEFI?
-    buff.write_u8(0x0)?;
+    buff.write_u8(default_entry.platform_id)?;
 
     // Reserved
     buff.write_u16::<LittleEndian>(0x0)?;
 
-    let id_str: [u8; 0x16] = [0x0; 0x16];
+    let id_str: [u8; 0x18] = [0x0; 0x18];
     buff.write_all(&id_str)?;
 
-    // FIXME: actually calculate the checksum correctly!
-    buff.write_u32::<LittleEndian>(0x55aa_0000)?;
+    // Checksum (patched once the header is complete)
+    buff.write_u16::<LittleEndian>(0x0)?;
 
     buff.write_u8(0x55)?;
     buff.write_u8(0xAA)?;
 
-    let boot_indicator = if opt.eltorito_opt.no_boot { 0x0 } else { 0x88 };
+    let checksum = get_validation_header_checksum(&buff);
+    buff[0x1C..0x1E].copy_from_slice(&checksum.to_le_bytes());
 
-    buff.write_u8(boot_indicator)?;
+    // Initial/Default Entry
+    write_boot_catalog_entry(&mut buff, default_entry)?;
 
-    // Boot medium type (force no emu mode)
-    buff.write_u8(0x0)?;
+    // The other entries are grouped in sections by platform
+    let sections: Vec<&[BootCatalogEntry]> = section_entries
+        .chunk_by(|entry, next_entry| entry.platform_id == next_entry.platform_id)
+        .collect();
 
-    // Load segment (0 means default, 0x7C0. As we don't manage any emulation mode, we don't care of it)
-    buff.write_u16::<LittleEndian>(0x0)?;
+    for (index, section) in sections.iter().enumerate() {
+        // Section Header (0x91 for the final one)
+        let header_id = if index + 1 == sections.len() {
+            0x91
+        } else {
+            0x90
+        };
+        buff.write_u8(header_id)?;
 
-    // System Type. "This must be a copy of byte 5 (System Type) from the Partition Table found in the boot image."
-    // As we don't emulate harddrive, this is 0 here
-    buff.write_u8(0x0)?;
+        // Plateform ID
+        buff.write_u8(section[0].platform_id)?;
 
-    // Unused (0xC - 0x1F)
-    buff.write_u8(0x0)?;
+        // Number of section entries
+        buff.write_u16::<LittleEndian>(section.len() as u16)?;
 
-    // Sector count
-    buff.write_u16::<LittleEndian>(sector_count as u16)?;
+        let id_str: [u8; 0x1C] = [0x0; 0x1C];
+        buff.write_all(&id_str)?;
 
-    // LBA of the file
-    buff.write_u32::<LittleEndian>(eltorito_lba)?;
+        // Section Entries
+        for entry in section.iter() {
+            write_boot_catalog_entry(&mut buff, entry)?;
+        }
+    }
 
-    let unused: [u8; 0x14] = [0x0; 0x14];
-    // Unused
-    buff.write_all(&unused)?;
+    if buff.len() > LOGIC_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "too many boot entries, the boot catalog doesn't fit in one block!",
+        ));
+    }
 
     file.file_type = match &file.file_type {
         FileType::Buffer { name, .. } => FileType::Buffer {
@@ -151,7 +393,20 @@ fn fill_boot_catalog(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::i
     Ok(())
 }
 
-fn patch_boot_image(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::io::Result<()> {
+fn get_boot_info_table_checksum(data: &[u8]) -> u32 {
+    // 32-bit little endian sum of the whole image starting after the boot info table.
+    data[64..].chunks(4).fold(0u32, |checksum, chunk| {
+        let mut word: [u8; 4] = [0x0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        checksum.wrapping_add(u32::from_le_bytes(word))
+    })
+}
+
+fn patch_boot_image(
+    tree: &mut DirectoryEntry,
+    opt: &mut option::Opt,
+    primary_volume_lba: u32,
+) -> std::io::Result<()> {
     let value = opt.eltorito_opt.eltorito_boot.clone().unwrap();
     let file: &mut FileEntry = tree.get_file(&value).unwrap();
 
@@ -161,11 +416,20 @@ fn patch_boot_image(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::io
     std::io::copy(&mut content, &mut buff)?;
 
     if opt.eltorito_opt.boot_info_table {
+        if buff.get_ref().len() < 64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "boot image is too small to hold a boot info table!",
+            ));
+        }
+
+        let checksum = get_boot_info_table_checksum(buff.get_ref());
+
         // Patch the content now
         buff.seek(SeekFrom::Start(0x8))?;
 
-        // LBA of primary volume descriptor (always 0x10 in our case)
-        buff.write_u32::<LittleEndian>(0x10)?;
+        // LBA of primary volume descriptor
+        buff.write_u32::<LittleEndian>(primary_volume_lba)?;
 
         // LBA of boot file.
         buff.write_u32::<LittleEndian>(file.lba)?;
@@ -173,9 +437,8 @@ fn patch_boot_image(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::io
         // Length of boot file.
         buff.write_u32::<LittleEndian>(file.size as u32)?;
 
-        // Checksum (actually ignored by GRUB2)
-        // FIXME: should we implement it?
-        buff.write_u32::<LittleEndian>(0x0)?;
+        // Checksum (validated by ISOLINUX, ignored by GRUB2)
+        buff.write_u32::<LittleEndian>(checksum)?;
     }
 
     if opt.eltorito_opt.grub2_boot_info {
@@ -193,11 +456,21 @@ fn patch_boot_image(tree: &mut DirectoryEntry, opt: &mut option::Opt) -> std::io
     Ok(())
 }
 
+/// Check if the System Area starts with an isohybrid MBR, given or replayed.
+fn has_isohybrid_mbr(opt: &option::Opt, boot_replay: Option<&boot_replay::BootReplay>) -> bool {
+    opt.isohybrid_mbr.is_some()
+        || boot_replay.is_some_and(|boot_replay| {
+            boot_replay.system_area.is_some()
+                && boot_replay.mbr_patch == boot_replay::MbrPatch::Isohybrid
+        })
+}
+
 fn write_system_area<T>(
     tree: &mut DirectoryEntry,
     output_writter: &mut T,
     opt: &option::Opt,
-    lb_count: u32,
+    layout: &ImageLayout,
+    boot_replay: Option<&boot_replay::BootReplay>,
 ) -> std::io::Result<()>
 where
     T: Write + Seek,
@@ -205,31 +478,65 @@ where
     let old_pos = output_writter.seek(SeekFrom::Current(0))?;
 
     let mut embedded_boot = None;
+    let mut replayed_system_area = None;
     let need_grub2_mbr_patches;
+    let need_isohybrid_mbr_patches;
 
     if opt.embedded_boot.is_some() {
         embedded_boot = opt.embedded_boot.clone();
         need_grub2_mbr_patches = false;
+        need_isohybrid_mbr_patches = false;
     } else if opt.grub2_mbr.is_some() {
         embedded_boot = opt.grub2_mbr.clone();
         need_grub2_mbr_patches = true;
+        need_isohybrid_mbr_patches = false;
+    } else if opt.isohybrid_mbr.is_some() {
+        embedded_boot = opt.isohybrid_mbr.clone();
+        need_grub2_mbr_patches = false;
+        need_isohybrid_mbr_patches = true;
+    } else if let Some(boot_replay) =
+        boot_replay.filter(|boot_replay| boot_replay.system_area.is_some())
+    {
+        replayed_system_area = boot_replay.system_area.as_ref();
+        need_grub2_mbr_patches = boot_replay.mbr_patch == boot_replay::MbrPatch::Grub2;
+        need_isohybrid_mbr_patches = boot_replay.mbr_patch == boot_replay::MbrPatch::Isohybrid;
     } else {
         need_grub2_mbr_patches = false;
+        need_isohybrid_mbr_patches = false;
     }
 
     if let Some(embedded_boot) = embedded_boot {
-        let path: PathBuf = PathBuf::from_str(&embedded_boot).unwrap();
-        if path.metadata().unwrap().len() > (LOGIC_SIZE * 0x10) as u64 {
+        // Disk file or interval of one
+        let content = interval::read_source(&embedded_boot)?;
+        if content.len() > LOGIC_SIZE * 0x10 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "generic boot file is bigger than 32768 bytes!",
             ));
         }
-        let mut embedded_boot_file = File::open(path)?;
-        std::io::copy(&mut embedded_boot_file, output_writter)?;
+
+        if need_isohybrid_mbr_patches && content.len() > SECTOR_SIZE as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "isohybrid MBR file is bigger than 512 bytes!",
+            ));
+        }
+
+        output_writter.write_all(&content)?;
+    }
+
+    if let Some(system_area) = replayed_system_area {
+        output_writter.write_all(system_area)?;
     }
 
-    let current_pos = output_writter.seek(SeekFrom::Current(0))?;
+    let mut current_pos = output_writter.seek(SeekFrom::Current(0))?;
+
+    // isohybrid MBR files may only contain the boot code, make sure the patched area isn't overwritten by the padding
+    if need_isohybrid_mbr_patches && current_pos < old_pos + u64::from(SECTOR_SIZE) {
+        let padding = vec![0u8; (old_pos + u64::from(SECTOR_SIZE) - current_pos) as usize];
+        output_writter.write_all(&padding)?;
+        current_pos = output_writter.stream_position()?;
+    }
 
     if need_grub2_mbr_patches {
         if let Some(boot) = &opt.eltorito_opt.eltorito_boot {
@@ -241,6 +548,29 @@ where
         }
     }
 
+    if need_isohybrid_mbr_patches {
+        let boot = opt.eltorito_opt.eltorito_boot.as_ref().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "isohybrid MBR requires an El Torito boot image!",
+            )
+        })?;
+
+        output_writter.seek(SeekFrom::Start(old_pos + 0x1B0))?;
+        let file: &mut FileEntry = tree.get_file(boot).unwrap();
+
+        // LBA of the El Torito boot image in 512-byte sectors
+        output_writter.write_u32::<LittleEndian>(file.lba * 4)?;
+        output_writter.write_u32::<LittleEndian>(0x0)?;
+
+        // MBR ID
+        output_writter.write_u32::<LittleEndian>(utils::generate_random_u32())?;
+        output_writter.write_u16::<LittleEndian>(0x0)?;
+
+        // Go back to where we are supposed to be...
+        output_writter.seek(SeekFrom::Start(current_pos))?;
+    }
+
     // Pad to 0x8000 if needed
     let diff_size = current_pos as usize - old_pos as usize;
 
@@ -250,75 +580,517 @@ where
         output_writter.write_all(&padding)?;
     }
 
-    if opt.protective_msdos_label {
-        let current_pos = output_writter.seek(SeekFrom::Current(0))?;
+    let head_count = opt.partition_hd_cyl;
+    let sector_count = opt.partition_sec_hd;
+
+    if opt.sparc_boot.is_some() {
+        let mut partitions: [Option<sun_label::SunPartition>; sun_label::SUN_PARTITION_COUNT] =
+            Default::default();
+
+        // The first partition claims the ISO image
+        partitions[0] = Some(sun_label::SunPartition {
+            start_sector: 0,
+            sector_count: utils::get_sector_field(layout.iso_partition_lb_count, "Sun disk label")?,
+        });
+
+        for image in &layout.sparc_boot_images {
+            partitions[usize::from(image.number)] = Some(sun_label::SunPartition {
+                start_sector: utils::check_sector_field(
+                    image.get_start_sector(),
+                    "Sun disk label",
+                )?,
+                sector_count: utils::check_sector_field(
+                    image.get_sector_count(),
+                    "Sun disk label",
+                )?,
+            });
+        }
 
-        // First MBR partition
-        output_writter.seek(SeekFrom::Start(0x1BE))?;
+        let label = opt
+            .sparc_label
+            .as_deref()
+            .unwrap_or(sun_label::DEFAULT_LABEL);
+        sun_label::write_sun_label(
+            output_writter,
+            old_pos,
+            label,
+            &partitions,
+            utils::get_sector_field(layout.image_lb_count, "Sun disk label")?,
+        )?;
+    }
+
+    if let Some(limine_bios_hdd) = &layout.limine_bios_hdd {
+        limine_bios_hdd.write_stage1(output_writter, old_pos)?;
+    }
+
+    if !opt.mips_boot.is_empty() {
+        mips::write_sgi_volume_header(
+            output_writter,
+            old_pos,
+            tree,
+            &opt.mips_boot,
+            utils::get_sector_field(layout.image_lb_count, "SGI volume header")?,
+        )?;
+    }
+
+    if let Some(mipsel_boot) = &opt.mipsel_boot {
+        mips::write_dec_boot_block(output_writter, old_pos, tree, mipsel_boot)?;
+    }
+
+    let apm_partitions = layout.hfsplus.as_ref().map(|hfsplus_layout| {
+        vec![apm::ApmPartition {
+            name: String::from("HFSPLUS_Hybrid"),
+            partition_type: String::from("Apple_HFS"),
+            start_block: hfsplus_layout.start_lba,
+            block_count: hfsplus_layout.lb_count,
+        }]
+    });
+
+    if let Some(apm_partitions) = &apm_partitions {
+        apm::write_apm(output_writter, apm_partitions, layout.image_lb_count)?;
+    }
+
+    if opt.isohybrid_gpt_basdat {
+        // The ISO partition starts after the system area as the GPT lives in it
+        let iso_partition_start =
+            utils::get_sector_count(std::cmp::max(opt.partition_offset, 0x10));
+        let image_size_in_sector = utils::get_sector_count(layout.image_lb_count);
+        let mut gpt_partitions = vec![gpt::GptPartition::new(
+            gpt::BASIC_DATA_TYPE_GUID,
+            iso_partition_start,
+            utils::get_sector_count(layout.iso_lb_count) - iso_partition_start,
+            "ISO9660",
+        )];
+
+        if let Some(efi_boot) = &opt.eltorito_opt.efi_boot {
+            let file: &mut FileEntry = tree.get_file(efi_boot).unwrap();
+            gpt_partitions.push(gpt::GptPartition::new(
+                gpt::EFI_SYSTEM_TYPE_GUID,
+                utils::get_sector_count(file.lba),
+                (file.size as u64).div_ceil(u64::from(SECTOR_SIZE)),
+                "EFI boot partition",
+            ));
+        }
+
+        if let Some(hfsplus_layout) = &layout.hfsplus {
+            gpt_partitions.push(gpt::GptPartition::new(
+                gpt::APPLE_HFS_TYPE_GUID,
+                utils::get_sector_count(hfsplus_layout.start_lba),
+                utils::get_sector_count(hfsplus_layout.lb_count),
+                "HFSPLUS_Hybrid",
+            ));
+        }
 
-        // bootable
-        output_writter.write_u8(0x80)?;
+        for partition in &layout.appended_partitions {
+            gpt_partitions.push(gpt::GptPartition::new(
+                partition.get_gpt_type_guid(),
+                partition.get_start_sector(),
+                partition.get_sector_count(),
+                &format!("Appended{}", partition.number),
+            ));
+        }
 
-        let partition_offset = 1;
-        let partition_number = 1;
-        let head_count = 64;
-        let sector_count = 32;
+        // The GPT entries are moved after the Apple Partition Map if needed
+        let entries_sector = match &apm_partitions {
+            Some(apm_partitions) => {
+                apm::get_block_count(apm_partitions) * (apm::APM_BLOCK_SIZE / SECTOR_SIZE)
+            }
+            None => 2,
+        };
+
+        gpt::write_gpt(
+            output_writter,
+            &gpt_partitions,
+            image_size_in_sector,
+            u64::from(entries_sector),
+        )?;
+
+        // Protective MBR claiming the whole disk for the GPT, or as much of it as it can describe
+        let partitions = [
+            Some(mbr::MbrPartition {
+                bootable: false,
+                partition_type: 0xEE,
+                start_sector: 1,
+                sector_count: std::cmp::min(image_size_in_sector - 1, u64::from(u32::MAX)) as u32,
+            }),
+            None,
+            None,
+            None,
+        ];
+
+        mbr::write_partition_table(
+            output_writter,
+            old_pos,
+            &partitions,
+            head_count,
+            sector_count,
+        )?;
+    } else if opt.protective_msdos_label
+        || has_isohybrid_mbr(opt, boot_replay)
+        || opt.chrp_boot
+        || opt.efi_boot_part
+        || !layout.appended_partitions.is_empty()
+    {
+        let mut partitions: [Option<mbr::MbrPartition>; mbr::MBR_PARTITION_COUNT] =
+            [None, None, None, None];
+
+        for partition in &layout.appended_partitions {
+            partitions[usize::from(partition.number - 1)] = Some(mbr::MbrPartition {
+                bootable: false,
+                partition_type: partition.partition_type,
+                start_sector: utils::check_sector_field(partition.get_start_sector(), "MBR")?,
+                sector_count: utils::check_sector_field(partition.get_sector_count(), "MBR")?,
+            });
+        }
 
-        let size_in_sector = (lb_count * LOGIC_SIZE_U32) / SECTOR_SIZE;
+        if has_isohybrid_mbr(opt, boot_replay) && partitions[0].is_none() {
+            // The ISO partition claims the image up to the next cylinder boundary
+            let partition_offset = opt.partition_offset * (LOGIC_SIZE_U32 / SECTOR_SIZE);
+            let partition_end = utils::get_sector_field(layout.iso_partition_lb_count, "MBR")?;
+
+            partitions[0] = Some(mbr::MbrPartition {
+                bootable: true,
+                partition_type: 0x17,
+                start_sector: partition_offset,
+                sector_count: partition_end - partition_offset,
+            });
+        }
 
-        // CHS address start
-        utils::write_lba_to_cls(output_writter, partition_number, head_count, sector_count)?;
+        // Open Firmware looks for a CHRP partition holding an ISO 9660 filesystem with ppc/bootinfo.txt
+        if opt.chrp_boot && partitions[0].is_none() {
+            let partition_offset = opt.partition_offset * (LOGIC_SIZE_U32 / SECTOR_SIZE);
+            partitions[0] = Some(mbr::MbrPartition {
+                bootable: true,
+                partition_type: 0x96,
+                start_sector: partition_offset,
+                sector_count: utils::get_sector_field(layout.iso_lb_count, "MBR")?
+                    - partition_offset,
+            });
+        }
 
         // Simple partition table as we want to tell that we are a cd
-        output_writter.write_u8(0x17)?;
+        if opt.protective_msdos_label && partitions[0].is_none() {
+            let partition_offset = if opt.partition_offset != 0 {
+                opt.partition_offset * (LOGIC_SIZE_U32 / SECTOR_SIZE)
+            } else {
+                1
+            };
+            partitions[0] = Some(mbr::MbrPartition {
+                bootable: true,
+                partition_type: 0x17,
+                start_sector: partition_offset,
+                sector_count: utils::get_sector_field(layout.iso_lb_count, "MBR")?
+                    - partition_offset,
+            });
+        }
+
+        // EFI System Partition pointing to the EFI boot image inside the ISO
+        let efi_boot = opt
+            .eltorito_opt
+            .efi_boot
+            .as_ref()
+            .filter(|_| opt.efi_boot_part && opt.efi_boot_image.is_none());
+        if let Some(efi_boot) = efi_boot {
+            let slot = partitions
+                .iter()
+                .position(|partition| partition.is_none())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "no MBR partition slot left for the EFI boot partition!",
+                    )
+                })?;
+
+            let file: &mut FileEntry = tree.get_file(efi_boot).unwrap();
+            partitions[slot] = Some(mbr::MbrPartition {
+                bootable: false,
+                partition_type: 0xEF,
+                start_sector: utils::get_sector_field(file.lba, "MBR")?,
+                sector_count: utils::check_sector_field(
+                    (file.size as u64).div_ceil(u64::from(SECTOR_SIZE)),
+                    "MBR",
+                )?,
+            });
+        }
 
-        // CHS address end
-        utils::write_lba_to_cls(output_writter, size_in_sector - 1, head_count, sector_count)?;
+        mbr::write_partition_table(
+            output_writter,
+            old_pos,
+            &partitions,
+            head_count,
+            sector_count,
+        )?;
+    }
 
-        // partition offset
-        output_writter.write_u32::<LittleEndian>(partition_offset)?;
+    Ok(())
+}
 
-        // Image size
-        output_writter.write_u32::<LittleEndian>(size_in_sector - partition_offset)?;
+/// Make sure that the requested disk labels and boot code don't overlap, as they all live in the first 512 bytes of the system area.
+fn check_system_area_options(
+    opt: &option::Opt,
+    boot_replay: Option<&boot_replay::BootReplay>,
+) -> std::io::Result<()> {
+    let need_mbr = opt.grub2_mbr.is_some()
+        || opt.isohybrid_mbr.is_some()
+        || opt.protective_msdos_label
+        || opt.isohybrid_gpt_basdat
+        || opt.hfsplus
+        || opt.chrp_boot
+        || opt.prep_boot.is_some()
+        || opt.efi_boot_part
+        || opt.efi_boot_image.is_some()
+        || !opt.append_partition.is_empty();
+
+    let label_count = [
+        need_mbr,
+        opt.sparc_boot.is_some(),
+        !opt.mips_boot.is_empty(),
+        opt.mipsel_boot.is_some(),
+    ]
+    .iter()
+    .filter(|needed| **needed)
+    .count();
+
+    if label_count > 1 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "MBR, Sun disk label, SGI volume header and DEC boot block can't be combined!",
+        ));
+    }
 
-        // Clean other boot entries
-        let empty_data: [u8; 0x10] = [0x0; 0x10];
+    // The signature of the Apple Partition Map DDM overwrites the first bytes of the MBR boot code
+    let replayed_boot_code = boot_replay
+        .and_then(|boot_replay| boot_replay.system_area.as_ref())
+        .is_some_and(|system_area| {
+            !system_area.starts_with(b"ER") && system_area[..8].iter().any(|byte| *byte != 0x0)
+        });
+    let has_mbr_boot_code = opt.embedded_boot.is_some()
+        || opt.grub2_mbr.is_some()
+        || opt.isohybrid_mbr.is_some()
+        || opt.limine_bios_hdd.is_some()
+        || replayed_boot_code;
+
+    if opt.efi_boot_part && opt.efi_boot_image.is_none() && opt.eltorito_opt.efi_boot.is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "EFI boot partition requires an EFI boot image!",
+        ));
+    }
 
-        output_writter.write_all(&empty_data)?;
-        output_writter.write_all(&empty_data)?;
-        output_writter.write_all(&empty_data)?;
+    if opt.hfsplus && has_mbr_boot_code {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "HFS+ Apple Partition Map can't be combined with MBR boot code!",
+        ));
+    }
 
-        // write "valid bootsector"
-        output_writter.seek(SeekFrom::Start(0x1FE))?;
-        output_writter.write_u8(0x55)?;
-        output_writter.write_u8(0xAA)?;
+    Ok(())
+}
 
-        output_writter.seek(SeekFrom::Start(current_pos))?;
+/// Check that no option needs a System Area or data outside of the new session of a multisession medium.
+fn check_multisession_options(opt: &option::Opt) -> std::io::Result<()> {
+    let need_system_area = opt.boot_replay.is_some()
+        || opt.embedded_boot.is_some()
+        || opt.grub2_mbr.is_some()
+        || opt.isohybrid_mbr.is_some()
+        || opt.partition_offset != 0
+        || opt.protective_msdos_label
+        || opt.isohybrid_gpt_basdat
+        || opt.hfsplus
+        || opt.chrp_boot
+        || opt.prep_boot.is_some()
+        || opt.efi_boot_part
+        || opt.efi_boot_image.is_some()
+        || !opt.append_partition.is_empty()
+        || opt.sparc_boot.is_some()
+        || !opt.mips_boot.is_empty()
+        || opt.mipsel_boot.is_some()
+        || opt.limine_bios_hdd.is_some();
+
+    if need_system_area {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "System Area, partitions and appended images can't be used in a multisession image!",
+        ));
     }
 
     Ok(())
 }
 
+/// Get the first partition number not used by the appended partitions, the first one being kept for the ISO partition.
+fn get_free_partition_number(
+    appended_partitions: &[AppendedPartition],
+    partition_name: &str,
+) -> std::io::Result<u8> {
+    (2..=mbr::MBR_PARTITION_COUNT as u8)
+        .find(|number| {
+            !appended_partitions
+                .iter()
+                .any(|partition| partition.number == *number)
+        })
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "no MBR partition slot left for the {} partition!",
+                    partition_name
+                ),
+            )
+        })
+}
+
 pub fn create_iso(opt: &mut option::Opt) -> std::io::Result<()> {
+    preset::apply_presets(opt)?;
+
+    let mut tree = DirectoryEntry::new()?;
+    tree.set_path(&opt.input_files)?;
+
+    write_image(opt, tree)
+}
+
+/// Lay out and write the image of the given tree with the options of the command line.
+fn write_image(opt: &mut option::Opt, mut tree: DirectoryEntry) -> std::io::Result<()> {
+    let session_info = match &opt.cdrecord_params {
+        Some(cdrecord_params) => Some(multisession::SessionInfo::parse(cdrecord_params)?),
+        None => None,
+    };
+    if session_info.is_some() {
+        check_multisession_options(opt)?;
+    } else if opt.prev_session.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "merging a previous session requires the multisession parameters (-C)!",
+        ));
+    }
+
+    // The new session starts at the next writable address of the medium
+    let session_start = session_info.map_or(0, |session_info| session_info.next_start);
+
+    let mut boot_replay = match &opt.boot_replay {
+        Some(image) => Some(boot_replay::BootReplay::read(image)?),
+        None => None,
+    };
+    if let Some(boot_replay) = &boot_replay {
+        boot_replay.apply_options(opt)?;
+    }
+
     let volume_descriptor_list = generate_volume_descriptors(opt);
+    let primary_volume_lba = session_start
+        + 0x10
+        + volume_descriptor_list
+            .iter()
+            .position(|volume| matches!(volume, VolumeDescriptor::Primary))
+            .unwrap() as u32;
+
+    let output = opt.output.clone().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "no output file given!")
+    })?;
+
+    let mut current_lba: u32 = session_start + 0x10 + 1 + (volume_descriptor_list.len() as u32);
+
+    // The UDF Volume Recognition Sequence follows the Volume Descriptor Set Terminator
+    if opt.udf {
+        current_lba += udf::RECOGNITION_SEQUENCE_LB_COUNT;
+    }
+
+    // The partition starting at partition_offset gets its own volume descriptor set to be mountable
+    let partition_offset = opt.partition_offset;
+    let partition_volume_descriptor_list = if partition_offset != 0 {
+        if partition_offset < 0x10 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "partition offset must be at least 16 blocks!",
+            ));
+        }
+
+        let res = vec![VolumeDescriptor::Primary, VolumeDescriptor::End];
+        current_lba = partition_offset + 0x10 + 1 + (res.len() as u32);
+        res
+    } else {
+        Vec::new()
+    };
+
+    // Reserve the UDF volume descriptor sequences, the ISO 9660 structures start after the first anchor
+    let mut udf_layout = None;
+    if opt.udf {
+        if session_start != 0 || partition_offset != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "UDF can't be used with multisession or a partition offset!",
+            ));
+        }
+
+        let mut res = udf::UdfLayout {
+            main_sequence_lba: current_lba,
+            ..Default::default()
+        };
+        current_lba += udf::VOLUME_DESCRIPTOR_SEQUENCE_LB_COUNT;
+
+        res.reserve_sequence_lba = current_lba;
+        current_lba += udf::VOLUME_DESCRIPTOR_SEQUENCE_LB_COUNT;
 
-    let mut out_file = File::create(&opt.output)?;
+        res.integrity_sequence_lba = current_lba;
+        current_lba += udf::INTEGRITY_SEQUENCE_LB_COUNT;
 
-    let mut current_lba: u32 = 0x10 + 1 + (volume_descriptor_list.len() as u32);
+        assert!(current_lba <= udf::ANCHOR_LBA);
+        current_lba = udf::ANCHOR_LBA + 1;
+
+        udf_layout = Some(res);
+    }
 
+    // The path tables are reserved once the directories of the tree are known
     let path_table_start_lba = current_lba;
 
-    // Reserve 4 LBA for path tables (add some spacing after table)
-    current_lba += 4;
+    preset::check_presets(opt, &mut tree)?;
 
-    let mut tree = DirectoryEntry::new()?;
+    add_interval_boot_images(&mut tree, opt)?;
 
-    if opt.eltorito_opt.eltorito_boot.is_some() {
-        create_boot_catalog(&mut tree);
+    if let Some(boot_replay) = &mut boot_replay {
+        boot_replay.locate_boot_images(opt, &mut tree)?;
     }
 
-    tree.set_path(&opt.input_files)?;
-    let mut path_table_index = 0;
+    if opt.eltorito_opt.has_boot_catalog() {
+        create_boot_catalog(&mut tree, opt)?;
+    }
+
+    if let (Some(session_info), Some(prev_session)) = (&session_info, &opt.prev_session) {
+        multisession::merge_previous_session(&mut tree, prev_session, session_info)?;
+    }
+
+    check_system_area_options(opt, boot_replay.as_ref())?;
+
+    if opt.chrp_boot {
+        if opt.isohybrid_gpt_basdat {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "CHRP boot requires an MBR partition table, it can't be used with a GPT!",
+            ));
+        }
+
+        if tree.get_file("ppc/bootinfo.txt").is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "CHRP boot script \"ppc/bootinfo.txt\" not found in the ISO tree!",
+            ));
+        }
+    }
+
+    // The EFI boot image can't be described by an empty partition
+    if let Some(efi_boot) = &opt.eltorito_opt.efi_boot {
+        let is_partition =
+            (opt.efi_boot_part && opt.efi_boot_image.is_none()) || opt.isohybrid_gpt_basdat;
+        if is_partition && tree.get_file(efi_boot).is_some_and(|file| file.size == 0) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("EFI boot image \"{}\" is empty!", efi_boot),
+            ));
+        }
+    }
+
+    assign_path_table_indices(&mut tree);
+
+    // Reserve the L and M path tables (add some spacing after them)
+    current_lba += 2 * tree.get_path_table_lb_count() + 2;
 
     let mut tmp_lba = current_lba;
 
@@ -337,38 +1109,319 @@ pub fn create_iso(opt: &mut option::Opt) -> std::io::Result<()> {
     continuation_area.write_all(b"PLEASE CONTACT THE IEEE STANDARDS DEPARTMENT, PISCATAWAY, NJ, USA FOR THE 1282 SPECIFICATION.")?;
     tree.continuation_area = Some(continuation_area);
 
-    assign_directory_identifiers(&mut tree, &mut path_table_index, &mut tmp_lba);
-    tree.parent_index = 1;
+    assign_directory_identifiers(&mut tree, true, &mut tmp_lba);
     tree.lba = current_lba;
 
     current_lba = tmp_lba;
+
+    // Reserve the path tables and directories of the partition tree (same layout as the image one)
+    let partition_path_table_start_lba = current_lba;
+    if partition_offset != 0 {
+        current_lba += current_lba - path_table_start_lba;
+    }
+
+    // Reserve the UDF file set and entries before the files, they need to be in the UDF partition
+    if let Some(udf_layout) = &mut udf_layout {
+        udf_layout.partition_lba = current_lba;
+        udf_layout.metadata_lb_count = udf::get_metadata_lb_count(&tree);
+        current_lba += udf_layout.metadata_lb_count;
+    } else if let Some(path) = find_big_file(&tree, "") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("\"{}\" is bigger than 4 GiB, it requires --udf!", path),
+        ));
+    }
+
+    // Reserve the HFS+ volume header and B-trees before the files, they need to be in the HFS+ partition
+    let mut hfsplus_layout = None;
+    if opt.hfsplus {
+        let mut res = hfsplus::HfsPlusLayout {
+            start_lba: current_lba,
+            ..Default::default()
+        };
+        current_lba += 1;
+
+        res.catalog_lba = current_lba;
+        res.catalog_lb_count = hfsplus::get_catalog_lb_count(&tree)?;
+        current_lba += res.catalog_lb_count;
+
+        res.extents_lba = current_lba;
+        current_lba += hfsplus::EXTENTS_LB_COUNT;
+
+        hfsplus_layout = Some(res);
+    }
+
     current_lba += 1;
 
     reserve_file_space(&mut tree, &mut current_lba);
 
-    if opt.eltorito_opt.eltorito_boot.is_some() {
-        fill_boot_catalog(&mut tree, opt)?;
+    // The allocation file and the alternate volume header end the HFS+ partition
+    if let Some(hfsplus_layout) = &mut hfsplus_layout {
+        hfsplus_layout.bitmap_lba = current_lba;
+        hfsplus_layout.bitmap_lb_count = 0;
+
+        loop {
+            let lb_count =
+                current_lba + hfsplus_layout.bitmap_lb_count + 1 - hfsplus_layout.start_lba;
+            let bitmap_lb_count = hfsplus::get_bitmap_lb_count(lb_count);
+
+            if bitmap_lb_count == hfsplus_layout.bitmap_lb_count {
+                hfsplus_layout.lb_count = lb_count;
+                break;
+            }
+            hfsplus_layout.bitmap_lb_count = bitmap_lb_count;
+        }
+
+        current_lba = hfsplus_layout.start_lba + hfsplus_layout.lb_count;
     }
 
-    if opt.eltorito_opt.boot_info_table || opt.eltorito_opt.grub2_boot_info {
-        patch_boot_image(&mut tree, opt)?;
+    // The last anchor ends the UDF partition
+    if let Some(udf_layout) = &mut udf_layout {
+        udf_layout.last_anchor_lba = current_lba;
+        current_lba += 1;
     }
 
-    write_system_area(&mut tree, &mut out_file, opt, current_lba)?;
+    if opt.eltorito_opt.has_boot_catalog() {
+        fill_boot_catalog(&mut tree, opt, boot_replay.as_ref())?;
+    }
+
+    if opt.eltorito_opt.eltorito_boot.is_some()
+        && (opt.eltorito_opt.boot_info_table || opt.eltorito_opt.grub2_boot_info)
+    {
+        patch_boot_image(&mut tree, opt, primary_volume_lba)?;
+    }
+
+    let mut partition_tree = None;
+    if partition_offset != 0 {
+        // Copy of the tree addressed relatively to the partition start
+        let mut tree_copy = tree.clone();
+        let directory_shift = i64::from(partition_path_table_start_lba)
+            - i64::from(path_table_start_lba)
+            - i64::from(partition_offset);
+        tree_copy.shift_lba(directory_shift, -i64::from(partition_offset));
+        partition_tree = Some(tree_copy);
+    }
+
+    let mut iso_partition_lb_count = current_lba;
+    if has_isohybrid_mbr(opt, boot_replay.as_ref()) {
+        // Pad to a cylinder boundary that is also a LB boundary
+        let cylinder_size = opt.partition_hd_cyl * opt.partition_sec_hd;
+        let lb_sector_count = LOGIC_SIZE_U32 / SECTOR_SIZE;
+        let alignment =
+            (cylinder_size / utils::gcd(cylinder_size, lb_sector_count)) * lb_sector_count;
+
+        let iso_size_in_sector = current_lba * lb_sector_count;
+        iso_partition_lb_count =
+            iso_size_in_sector.div_ceil(alignment) * alignment / lb_sector_count;
+    }
+
+    let mut layout = ImageLayout {
+        iso_lb_count: current_lba,
+        iso_partition_lb_count,
+        image_lb_count: iso_partition_lb_count,
+        appended_partitions: appended_partition::parse_appended_partitions(&opt.append_partition)?,
+        hfsplus: hfsplus_layout,
+        udf: udf_layout,
+        sparc_boot_images: Vec::new(),
+        limine_bios_hdd: None,
+    };
+
+    // PReP boot partition from a disk file, appended in the first free partition slot after the ISO one
+    if let Some(prep_boot) = &opt.prep_boot {
+        let number = get_free_partition_number(&layout.appended_partitions, "PReP boot")?;
+        layout
+            .appended_partitions
+            .push(AppendedPartition::from_source(number, 0x41, prep_boot)?);
+    }
+
+    // EFI System Partition from a disk file, appended in the first free partition slot after the ISO one
+    if let Some(efi_boot_image) = &opt.efi_boot_image {
+        let number = get_free_partition_number(&layout.appended_partitions, "EFI boot")?;
+        layout
+            .appended_partitions
+            .push(AppendedPartition::from_source(
+                number,
+                0xEF,
+                efi_boot_image,
+            )?);
+    }
+
+    // Partitions of the replayed image after its ISO image
+    if let Some(boot_replay) = &boot_replay {
+        let mut partitions = boot_replay.get_appended_partitions(&layout.appended_partitions)?;
+        layout.appended_partitions.append(&mut partitions);
+    }
+
+    for partition in &mut layout.appended_partitions {
+        partition.lba = layout.image_lb_count;
+        layout.image_lb_count += partition.get_lb_count();
+    }
+
+    // SPARC boot images start on cylinder boundaries of the Sun disk label geometry
+    if let Some(sparc_boot) = &opt.sparc_boot {
+        layout.iso_partition_lb_count = layout
+            .iso_partition_lb_count
+            .div_ceil(sun_label::CYLINDER_LB_COUNT)
+            * sun_label::CYLINDER_LB_COUNT;
+        layout.image_lb_count = layout.iso_partition_lb_count;
+
+        layout.sparc_boot_images = sun_label::parse_sparc_boot_images(sparc_boot)?;
+        for image in &mut layout.sparc_boot_images {
+            image.lba = layout.image_lb_count;
+            layout.image_lb_count += image.get_lb_count().div_ceil(sun_label::CYLINDER_LB_COUNT)
+                * sun_label::CYLINDER_LB_COUNT;
+        }
+    }
+
+    if let Some(limine_bios_hdd) = &opt.limine_bios_hdd {
+        let mut res = limine::LimineBiosHdd::new(limine_bios_hdd)?;
+        res.lba = layout.image_lb_count;
+        layout.image_lb_count += res.get_lb_count()?;
+        layout.limine_bios_hdd = Some(res);
+    }
+
+    if opt.isohybrid_gpt_basdat {
+        layout.image_lb_count += gpt::BACKUP_LB_COUNT;
+    }
+
+    // The output file is only created once the options are validated, to keep an existing image on errors
+    let mut output_file = File::create(output)?;
+
+    // The output file only holds the new session, LBAs are still relative to the start of the medium
+    let mut out_file =
+        utils::OffsetWriter::new(&mut output_file, -i64::from(session_start * LOGIC_SIZE_U32));
+
+    write_system_area(&mut tree, &mut out_file, opt, &layout, boot_replay.as_ref())?;
 
     for mut volume in volume_descriptor_list {
         volume.write_volume(&mut out_file, &mut tree, path_table_start_lba, current_lba)?;
     }
 
+    if opt.udf {
+        udf::write_volume_recognition_sequence(&mut out_file)?;
+    }
+
     // FIXME: what is this and why do I need it???? checksum infos??
     let empty_mki_section: [u8; 2044] = [0; 2044];
     out_file.write_all(b"MKI ")?;
     out_file.write_all(&empty_mki_section)?;
 
-    tree.write_path_table::<File, LittleEndian>(&mut out_file, path_table_start_lba)?;
-    tree.write_path_table::<File, BigEndian>(&mut out_file, path_table_start_lba + 1)?;
+    tree.write_path_table::<_, LittleEndian>(&mut out_file, path_table_start_lba)?;
+    tree.write_path_table::<_, BigEndian>(
+        &mut out_file,
+        path_table_start_lba + tree.get_path_table_lb_count(),
+    )?;
     tree.write_extent(&mut out_file, None)?;
+
+    if let Some(mut partition_tree) = partition_tree {
+        let mut partition_writter =
+            utils::OffsetWriter::new(&mut out_file, i64::from(partition_offset * LOGIC_SIZE_U32));
+
+        // The partition System Area is left untouched as it may overlap with the image volume descriptors
+        partition_writter.seek(SeekFrom::Start(u64::from(0x10 * LOGIC_SIZE_U32)))?;
+
+        let partition_path_table_lba = partition_path_table_start_lba - partition_offset;
+        for mut volume in partition_volume_descriptor_list {
+            volume.write_volume(
+                &mut partition_writter,
+                &mut partition_tree,
+                partition_path_table_lba,
+                current_lba - partition_offset,
+            )?;
+        }
+
+        partition_writter.write_all(b"MKI ")?;
+        partition_writter.write_all(&empty_mki_section)?;
+
+        partition_tree.write_path_table::<_, LittleEndian>(
+            &mut partition_writter,
+            partition_path_table_lba,
+        )?;
+        partition_tree.write_path_table::<_, BigEndian>(
+            &mut partition_writter,
+            partition_path_table_lba + partition_tree.get_path_table_lb_count(),
+        )?;
+        partition_tree.write_extent(&mut partition_writter, None)?;
+    }
+
     tree.write_files(&mut out_file)?;
 
+    if let Some(hfsplus_layout) = &layout.hfsplus {
+        hfsplus::write_hfsplus(
+            &mut out_file,
+            &tree,
+            hfsplus_layout,
+            opt.hfs_bless_folder.as_deref(),
+            opt.hfs_bless_file.as_deref(),
+        )?;
+    }
+
+    if let Some(udf_layout) = &layout.udf {
+        udf::write_udf(&mut out_file, &tree, udf_layout)?;
+    }
+
+    for partition in &layout.appended_partitions {
+        partition.write_content(&mut out_file)?;
+    }
+
+    if let Some(limine_bios_hdd) = &layout.limine_bios_hdd {
+        limine_bios_hdd.write_stage2(&mut out_file)?;
+    }
+
+    for image in &layout.sparc_boot_images {
+        image.write_content(&mut out_file)?;
+    }
+
+    // Make sure the padding of the ISO partition and of the last SPARC boot image is part of the image
+    output_file.set_len(u64::from(layout.image_lb_count - session_start) * LOGIC_SIZE as u64)?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_validation_header(platform_id: u8) -> Vec<u8> {
+        let mut res = vec![0x0; 0x20];
+        res[0x0] = 0x1;
+        res[0x1] = platform_id;
+        res[0x1E] = 0x55;
+        res[0x1F] = 0xAA;
+        res
+    }
+
+    #[test]
+    fn validation_header_checksum() {
+        assert_eq!(
+            get_validation_header_checksum(&create_validation_header(0x0)),
+            0x55AA
+        );
+
+        for platform_id in [0x0, 0x1, 0x2, 0xEF] {
+            let mut header = create_validation_header(platform_id);
+            let checksum = get_validation_header_checksum(&header);
+            header[0x1C..0x1E].copy_from_slice(&checksum.to_le_bytes());
+
+            let sum = header.chunks(2).fold(0u16, |sum, word| {
+                sum.wrapping_add(u16::from_le_bytes([word[0], word[1]]))
+            });
+            assert_eq!(sum, 0x0);
+        }
+    }
+
+    #[test]
+    fn boot_info_table_checksum() {
+        // The boot info table itself and the boot sector before it aren't summed
+        let mut data = vec![0xFF; 64];
+        data.extend_from_slice(&[0x1, 0x0, 0x0, 0x0, 0x2, 0x0, 0x0, 0x0]);
+        assert_eq!(get_boot_info_table_checksum(&data), 0x3);
+
+        // A trailing partial word is padded with zeros
+        data.extend_from_slice(&[0x0, 0x1]);
+        assert_eq!(get_boot_info_table_checksum(&data), 0x103);
+
+        let data = vec![0xFF; 64 + 8];
+        assert_eq!(get_boot_info_table_checksum(&data), 0xFFFF_FFFE);
+    }
+}
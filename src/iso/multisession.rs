@@ -0,0 +1,54 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::import;
+
+use std::io;
+use std::path::Path;
+
+/// Location of the sessions on a multisession medium, as given by "cdrecord -msinfo".
+#[derive(Debug, Clone, Copy)]
+pub struct SessionInfo {
+    /// First block of the last session
+    pub last_start: u32,
+    /// First writable block, where the new session starts
+    pub next_start: u32,
+}
+
+impl SessionInfo {
+    pub fn parse(value: &str) -> io::Result<SessionInfo> {
+        let invalid_value = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "invalid multisession parameters \"{}\", expected last_start,next_start",
+                    value
+                ),
+            )
+        };
+
+        let (last_start, next_start) = value.split_once(',').ok_or_else(invalid_value)?;
+        let res = SessionInfo {
+            last_start: last_start.trim().parse().map_err(|_| invalid_value())?,
+            next_start: next_start.trim().parse().map_err(|_| invalid_value())?,
+        };
+
+        if res.next_start < res.last_start {
+            return Err(invalid_value());
+        }
+
+        Ok(res)
+    }
+}
+
+/// Add the files of the last session of `image` to the tree, the new inputs taking precedence.
+pub fn merge_previous_session(
+    tree: &mut DirectoryEntry,
+    image: &Path,
+    session_info: &SessionInfo,
+) -> io::Result<()> {
+    import::import_tree(
+        tree,
+        image,
+        session_info.last_start,
+        import::ImportMode::KeepExtents,
+    )
+}
@@ -1,15 +1,48 @@
+use std::num::ParseIntError;
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 
+use crate::iso::reader::Namespace;
+use crate::iso::utils::SECTOR_SIZE;
+
+fn parse_u16(src: &str) -> Result<u16, ParseIntError> {
+    if let Some(hex) = src.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16)
+    } else {
+        src.parse()
+    }
+}
+
+fn parse_in_range(src: &str, min: u32, max: u32) -> Result<u32, String> {
+    match src.parse() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("expected a number from {} to {}", min, max)),
+    }
+}
+
+fn parse_head_count(src: &str) -> Result<u32, String> {
+    parse_in_range(src, 1, 255)
+}
+
+fn parse_sector_count(src: &str) -> Result<u32, String> {
+    parse_in_range(src, 1, 63)
+}
+
 /// A basic example
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "mkisofs-rs",
-    about = "create an hybrid ISO-9660 filesystem-image with Rock Ridge attributes."
+    about = "create an hybrid ISO-9660 filesystem-image with Rock Ridge attributes.",
+    // Once an option is given, an input directory named like a subcommand isn't one
+    setting = structopt::clap::AppSettings::ArgsNegateSubcommands
 )]
 pub struct Opt {
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+
     #[structopt(long, short = "o", help = "Set output file name")]
-    pub output: String,
+    pub output: Option<String>,
 
     #[structopt(flatten)]
     pub eltorito_opt: ElToritoOpt,
@@ -17,23 +50,55 @@ pub struct Opt {
     #[structopt(
         long = "generic-boot",
         short = "G",
-        help = "Copy at most 32768 bytes from the given disk file to the very start of the ISO image",
-        aliases = &["embedded-boot"]
+        help = "Copy at most 32768 bytes from the given disk file or interval (--interval:local_fs:START-END:ZEROIZERS:FILE) to the very start of the ISO image",
+        aliases = &["embedded-boot"],
+        allow_hyphen_values = true
     )]
     pub embedded_boot: Option<String>,
 
     #[structopt(
         long = "grub2-mbr",
-        help = "Patch and embedded_boot to simplify hybrid images"
+        help = "Patch and embedded_boot to simplify hybrid images",
+        allow_hyphen_values = true
     )]
     pub grub2_mbr: Option<String>,
 
+    #[structopt(
+        long = "isohybrid-mbr",
+        help = "Copy at most 512 bytes from the given disk file (e.g. SYSLINUX isohdpfx.bin) to the start of the ISO image, patch it with the El Torito boot image location and write an isohybrid MBR partition table padded to a cylinder boundary",
+        allow_hyphen_values = true
+    )]
+    pub isohybrid_mbr: Option<String>,
+
+    #[structopt(
+        long = "partition-offset",
+        help = "Set the start of the ISO partition in 2048-byte blocks (at least 16) and write a second volume descriptor set and directory tree addressed relatively to it, making the partition mountable",
+        default_value = "0"
+    )]
+    pub partition_offset: u32,
+
+    #[structopt(
+        long = "partition-hd-cyl",
+        help = "Set the number of heads per cylinder of the MBR partition table geometry (1 to 255)",
+        default_value = "64",
+        parse(try_from_str = parse_head_count)
+    )]
+    pub partition_hd_cyl: u32,
+
+    #[structopt(
+        long = "partition-sec-hd",
+        help = "Set the number of sectors per head of the MBR partition table geometry (1 to 63)",
+        default_value = "32",
+        parse(try_from_str = parse_sector_count)
+    )]
+    pub partition_sec_hd: u32,
+
     #[structopt(
         long = "boot-load-size",
-        help = "Set the number of 512-byte blocks to be loaded at boot time from the boot image in the current catalog entry.",
+        help = "Set the number of 512-byte blocks to be loaded at boot time from the boot image in the current catalog entry, or 'auto' to load the whole image.",
         default_value = "4"
     )]
-    pub boot_load_size: u32,
+    pub boot_load_size: BootLoadSize,
 
     #[structopt(
         long = "protective-msdos-label",
@@ -41,6 +106,322 @@ pub struct Opt {
     )]
     pub protective_msdos_label: bool,
 
+    #[structopt(
+        long = "isohybrid-gpt-basdat",
+        help = "Write a GPT in the System Area with the ISO image as Basic Data partition and the EFI boot image as EFI System Partition, and a backup GPT at the end of the image. The MBR becomes a protective MBR."
+    )]
+    pub isohybrid_gpt_basdat: bool,
+
+    #[structopt(
+        long = "append-partition",
+        help = "Append the given disk file or interval after the ISO image and describe it as partition N (1 to 4) of the given type (MBR type byte, e.g. 0xef or 0x83) in the MBR and GPT",
+        number_of_values = 3,
+        allow_hyphen_values = true,
+        value_names = &["N", "TYPE", "FILE"]
+    )]
+    pub append_partition: Vec<String>,
+
+    #[structopt(
+        long = "efi-boot-part",
+        help = "Add an EFI System Partition (type 0xef) to the MBR partition table, pointing to the EFI boot image inside the ISO image"
+    )]
+    pub efi_boot_part: bool,
+
+    #[structopt(
+        long = "efi-boot-image",
+        help = "Append the given disk file or interval after the ISO image and describe it as EFI System Partition (type 0xef) in the MBR partition table, instead of the EFI boot image inside the ISO image",
+        allow_hyphen_values = true,
+        value_name = "FILE"
+    )]
+    pub efi_boot_image: Option<String>,
+
+    #[structopt(
+        long = "hfsplus",
+        help = "Add an HFS+ filesystem sharing the file content of the ISO image, described by an Apple Partition Map in the System Area, the names must be ASCII"
+    )]
+    pub hfsplus: bool,
+
+    #[structopt(
+        long = "udf",
+        help = "Add a UDF 1.02 filesystem sharing the file content of the ISO image, files bigger than 4 GiB being only fully visible in UDF"
+    )]
+    pub udf: bool,
+
+    #[structopt(
+        long = "hfs-bless-folder",
+        help = "Set the HFS+ blessed system folder (e.g. System/Library/CoreServices)"
+    )]
+    pub hfs_bless_folder: Option<String>,
+
+    #[structopt(
+        long = "hfs-bless-file",
+        help = "Set the HFS+ blessed startup file (e.g. System/Library/CoreServices/boot.efi)"
+    )]
+    pub hfs_bless_file: Option<String>,
+
+    #[structopt(
+        long = "chrp-boot",
+        help = "Mark the ISO image as CHRP partition (type 0x96) in the MBR partition table for PowerPC Open Firmware, which boots the CHRP boot script ppc/bootinfo.txt of the ISO tree",
+        aliases = &["chrp-boot-part"]
+    )]
+    pub chrp_boot: bool,
+
+    #[structopt(
+        long = "prep-boot",
+        help = "Append the given disk file or interval (e.g. a PowerPC ELF boot loader) after the ISO image and describe it as PReP boot partition (type 0x41) in the MBR partition table",
+        aliases = &["prep-boot-part"],
+        allow_hyphen_values = true,
+        value_name = "FILE"
+    )]
+    pub prep_boot: Option<String>,
+
+    #[structopt(
+        long = "sparc-boot",
+        help = "Write a Sun disk label in the System Area and append the given comma separated disk files (up to 7 SPARC boot images, an empty name leaves the partition unused) after the ISO image as partitions 1 to 7",
+        value_name = "FILES"
+    )]
+    pub sparc_boot: Option<String>,
+
+    #[structopt(
+        long = "sparc-label",
+        help = "Set the ASCII label of the Sun disk label written by --sparc-boot"
+    )]
+    pub sparc_label: Option<String>,
+
+    #[structopt(
+        long = "mips-boot",
+        help = "Write an SGI volume header in the System Area and reference the given boot file of the ISO tree in its volume directory (may be repeated up to 15 times)",
+        number_of_values = 1
+    )]
+    pub mips_boot: Vec<String>,
+
+    #[structopt(
+        long = "mipsel-boot",
+        help = "Write a DEC boot block in the System Area loading the first segment of the given ELF boot file of the ISO tree"
+    )]
+    pub mipsel_boot: Option<String>,
+
+    #[structopt(
+        long = "isolinux",
+        help = "Make the image bootable with the ISOLINUX files of the given directory of the ISO tree (isolinux.bin, ldlinux.c32 and isolinux.cfg or syslinux.cfg), using isohdpfx.bin of that directory as isohybrid MBR if --isohybrid-mbr isn't given",
+        value_name = "DIR"
+    )]
+    pub isolinux: Option<String>,
+
+    #[structopt(
+        long = "limine",
+        help = "Make the image bootable on BIOS and UEFI with the Limine files of the given directory of the ISO tree (limine-bios-cd.bin, limine-uefi-cd.bin and limine-bios.sys)",
+        value_name = "DIR"
+    )]
+    pub limine: Option<String>,
+
+    #[structopt(
+        long = "limine-bios-hdd",
+        help = "Install the stage 1 of the given Limine BIOS hard drive image (limine-bios-hdd.bin) in the MBR and append its stage 2 after the ISO image, like \"limine bios-install\"",
+        value_name = "FILE"
+    )]
+    pub limine_bios_hdd: Option<String>,
+
+    #[structopt(
+        long = "boot-replay",
+        help = "Replay the boot setup of the given ISO image: its El Torito boot entries (pointing to the same paths in the new tree), boot info table patches, System Area and MBR/GPT partition layout, the partitions after its ISO image being appended again",
+        value_name = "FILE",
+        parse(from_os_str)
+    )]
+    pub boot_replay: Option<PathBuf>,
+
+    #[structopt(
+        long = "cdrecord-params",
+        short = "C",
+        help = "Create a new session of a multisession medium, given the start of its last session and its next writable address (output of \"cdrecord -msinfo\"), the output file only holds the new session",
+        value_name = "LAST_START,NEXT_START"
+    )]
+    pub cdrecord_params: Option<String>,
+
+    #[structopt(
+        long = "prev-session",
+        short = "M",
+        help = "Merge the directory tree of the last session of the given medium image with the input files, its files are kept at their location (requires -C)",
+        value_name = "FILE"
+    )]
+    pub prev_session: Option<PathBuf>,
+
+    #[structopt(parse(from_os_str))]
+    pub input_files: Vec<PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    #[structopt(
+        name = "rescue",
+        about = "create a GRUB rescue image bootable on BIOS and UEFI (grub-mkrescue replacement)."
+    )]
+    Rescue(RescueOpt),
+
+    #[structopt(
+        name = "info",
+        about = "print the volume descriptors, the El Torito boot catalog and the partition tables of an ISO image."
+    )]
+    Info(InfoOpt),
+
+    #[structopt(
+        name = "extract",
+        about = "extract files from an ISO image, restoring their Rock Ridge attributes."
+    )]
+    Extract(ExtractOpt),
+
+    #[structopt(
+        name = "ls",
+        about = "recursively list the directory tree of an ISO image with the location and attributes of each entry."
+    )]
+    Ls(LsOpt),
+
+    #[structopt(
+        name = "verify",
+        about = "check the ECMA-119, Rock Ridge, El Torito and partition table invariants of an ISO image."
+    )]
+    Verify(VerifyOpt),
+
+    #[structopt(
+        name = "diff",
+        about = "compare the filesystems, boot catalogs and volume descriptors of two ISO images."
+    )]
+    Diff(DiffOpt),
+
+    #[structopt(
+        name = "edit",
+        about = "write a modified copy of an ISO image, reusing the data of its unchanged files and its El Torito boot setup."
+    )]
+    Edit(EditOpt),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct EditOpt {
+    #[structopt(parse(from_os_str), help = "ISO image to modify")]
+    pub input: PathBuf,
+
+    #[structopt(
+        long = "output",
+        short = "o",
+        help = "Set the output file name",
+        parse(from_os_str)
+    )]
+    pub output: PathBuf,
+
+    #[structopt(
+        long = "remove",
+        help = "Remove a file or a directory (with its content) from the tree, applied first",
+        value_name = "ISO_PATH",
+        number_of_values = 1
+    )]
+    pub remove: Vec<String>,
+
+    #[structopt(
+        long = "rename",
+        help = "Move a file or a directory of the tree to another path, applied after the removals",
+        value_name = "OLD_PATH=NEW_PATH",
+        number_of_values = 1
+    )]
+    pub rename: Vec<String>,
+
+    #[structopt(
+        long = "add",
+        help = "Graft a file, a directory or an interval of a file of the disk at the given path of the tree, replacing any entry already there, applied last",
+        value_name = "ISO_PATH=DISK_PATH",
+        number_of_values = 1
+    )]
+    pub add: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct DiffOpt {
+    #[structopt(parse(from_os_str), help = "Original ISO image")]
+    pub old: PathBuf,
+
+    #[structopt(parse(from_os_str), help = "Modified ISO image")]
+    pub new: PathBuf,
+
+    #[structopt(
+        long = "layout",
+        help = "Also report the location changes (extents, path tables, boot images)"
+    )]
+    pub layout: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct VerifyOpt {
+    #[structopt(parse(from_os_str), help = "ISO image to verify")]
+    pub input: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct LsOpt {
+    #[structopt(parse(from_os_str), help = "ISO image to list")]
+    pub input: PathBuf,
+
+    #[structopt(
+        long = "namespace",
+        short = "n",
+        help = "Select the directory tree to list (iso, joliet or rockridge), defaults to the richest one available",
+        possible_values = &["iso", "joliet", "rockridge"]
+    )]
+    pub namespace: Option<Namespace>,
+
+    #[structopt(help = "Directory or file to list", default_value = "/")]
+    pub path: String,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ExtractOpt {
+    #[structopt(parse(from_os_str), help = "ISO image to extract")]
+    pub input: PathBuf,
+
+    #[structopt(
+        long = "output",
+        short = "o",
+        help = "Set the output directory",
+        default_value = ".",
+        parse(from_os_str)
+    )]
+    pub output: PathBuf,
+
+    #[structopt(help = "Paths in the ISO image to extract, everything if none is given")]
+    pub paths: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct InfoOpt {
+    #[structopt(parse(from_os_str), help = "ISO image to inspect")]
+    pub input: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct RescueOpt {
+    #[structopt(long, short = "o", help = "Set output file name")]
+    pub output: String,
+
+    #[structopt(
+        long = "directory",
+        short = "d",
+        help = "Use the GRUB platform directory (e.g. /usr/lib/grub/i386-pc or /usr/lib/grub/x86_64-efi), may be repeated. Defaults to the supported platforms found in /usr/lib/grub",
+        number_of_values = 1,
+        parse(from_os_str)
+    )]
+    pub directory: Vec<PathBuf>,
+
+    #[structopt(
+        long = "grub-mkimage",
+        help = "Set the grub-mkimage program used to create the core images",
+        default_value = "grub-mkimage"
+    )]
+    pub grub_mkimage: String,
+
+    #[structopt(
+        long = "modules",
+        help = "Preload the given space separated GRUB modules in the core images"
+    )]
+    pub modules: Option<String>,
+
     #[structopt(parse(from_os_str))]
     pub input_files: Vec<PathBuf>,
 }
@@ -50,10 +431,41 @@ pub struct ElToritoOpt {
     #[structopt(
         long = "eltorito-boot",
         short = "b",
-        help = "Set El Torito boot image name"
+        help = "Set El Torito boot image name, or an interval of a disk file added as hidden file",
+        allow_hyphen_values = true
     )]
     pub eltorito_boot: Option<String>,
 
+    #[structopt(
+        long = "eltorito-catalog",
+        short = "c",
+        help = "Set El Torito boot catalog path inside the ISO image",
+        default_value = "boot.catalog"
+    )]
+    pub eltorito_catalog: String,
+
+    #[structopt(
+        long = "hide-boot-catalog",
+        help = "Hide the El Torito boot catalog from the directory listings"
+    )]
+    pub hide_boot_catalog: bool,
+
+    #[structopt(
+        long = "boot-load-seg",
+        help = "Set the load segment address of the boot image for no-emulation El Torito boot (0 means default, 0x7C0)",
+        default_value = "0",
+        parse(try_from_str = parse_u16)
+    )]
+    pub boot_load_seg: u16,
+
+    #[structopt(
+        long = "efi-boot",
+        short = "e",
+        help = "Set EFI boot image name (a FAT filesystem image inside the ISO image), or an interval of a disk file added as hidden file",
+        allow_hyphen_values = true
+    )]
+    pub efi_boot: Option<String>,
+
     #[structopt(long = "no-emul-boot", help = "Boot image is 'no emulation' image")]
     pub no_emu_boot: bool,
 
@@ -66,3 +478,40 @@ pub struct ElToritoOpt {
     #[structopt(long = "grub2-boot-info", help = "Patch for GRUB 2 El Torino image")]
     pub grub2_boot_info: bool,
 }
+
+impl ElToritoOpt {
+    pub fn has_boot_catalog(&self) -> bool {
+        self.eltorito_boot.is_some() || self.efi_boot.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BootLoadSize {
+    Auto,
+    Sectors(u16),
+}
+
+impl BootLoadSize {
+    /// Get the count of 512-byte virtual sectors to write in the boot catalog for an image of the given size.
+    pub fn get_sector_count(self, image_size: usize) -> u16 {
+        match self {
+            BootLoadSize::Auto => {
+                let sector_count = image_size.div_ceil(SECTOR_SIZE as usize);
+                std::cmp::min(sector_count, usize::from(u16::MAX)) as u16
+            }
+            BootLoadSize::Sectors(sector_count) => sector_count,
+        }
+    }
+}
+
+impl FromStr for BootLoadSize {
+    type Err = ParseIntError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        if src == "auto" {
+            Ok(BootLoadSize::Auto)
+        } else {
+            Ok(BootLoadSize::Sectors(parse_u16(src)?))
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::FileType;
+use crate::iso::option::{BootLoadSize, Opt};
+
+use std::io;
+
+const ISOLINUX_CONFIG_NAMES: [&str; 2] = ["isolinux.cfg", "syslinux.cfg"];
+const LIMINE_FILE_NAMES: [&str; 3] = [
+    "limine-bios-cd.bin",
+    "limine-uefi-cd.bin",
+    "limine-bios.sys",
+];
+
+fn get_preset_path(directory: &str, name: &str) -> String {
+    format!("{}/{}", directory.trim_end_matches('/'), name)
+}
+
+fn check_boot_options_unset(opt: &Opt, preset_option: &str) -> io::Result<()> {
+    if opt.eltorito_opt.eltorito_boot.is_some() || opt.eltorito_opt.efi_boot.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} can't be combined with an El Torito boot image!",
+                preset_option
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Set the El Torito options of the presets, this needs to be done before generating the volume descriptors.
+pub fn apply_presets(opt: &mut Opt) -> io::Result<()> {
+    if let Some(directory) = opt.isolinux.clone() {
+        check_boot_options_unset(opt, "--isolinux")?;
+
+        opt.eltorito_opt.eltorito_boot = Some(get_preset_path(&directory, "isolinux.bin"));
+        opt.eltorito_opt.eltorito_catalog = get_preset_path(&directory, "boot.cat");
+        opt.eltorito_opt.no_emu_boot = true;
+        opt.eltorito_opt.boot_info_table = true;
+        opt.boot_load_size = BootLoadSize::Sectors(4);
+    }
+
+    if let Some(directory) = opt.limine.clone() {
+        check_boot_options_unset(opt, "--limine")?;
+
+        opt.eltorito_opt.eltorito_boot = Some(get_preset_path(&directory, "limine-bios-cd.bin"));
+        opt.eltorito_opt.efi_boot = Some(get_preset_path(&directory, "limine-uefi-cd.bin"));
+        opt.eltorito_opt.no_emu_boot = true;
+        opt.eltorito_opt.boot_info_table = true;
+        opt.boot_load_size = BootLoadSize::Sectors(4);
+        opt.protective_msdos_label = true;
+        opt.efi_boot_part = true;
+    }
+
+    // The Limine stage 1 replaces the MBR boot code
+    if opt.limine_bios_hdd.is_some()
+        && (opt.embedded_boot.is_some() || opt.grub2_mbr.is_some() || opt.isohybrid_mbr.is_some())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--limine-bios-hdd can't be combined with another MBR boot code!",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that the files required by the presets are part of the ISO tree and finish their setup.
+pub fn check_presets(opt: &mut Opt, tree: &mut DirectoryEntry) -> io::Result<()> {
+    if let Some(directory) = opt.isolinux.clone() {
+        for name in ["isolinux.bin", "ldlinux.c32"] {
+            let path = get_preset_path(&directory, name);
+            if tree.get_file(&path).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("ISOLINUX file \"{}\" not found in the ISO tree!", path),
+                ));
+            }
+        }
+
+        let has_config = ISOLINUX_CONFIG_NAMES
+            .iter()
+            .any(|name| tree.get_file(&get_preset_path(&directory, name)).is_some());
+        if !has_config {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "ISOLINUX configuration ({}) not found in \"{}\" of the ISO tree!",
+                    ISOLINUX_CONFIG_NAMES.join(" or "),
+                    directory
+                ),
+            ));
+        }
+
+        // Make the image hybrid with the isohybrid MBR shipped next to isolinux.bin if none was given
+        if opt.isohybrid_mbr.is_none() {
+            let isohybrid_mbr = tree.get_file(&get_preset_path(&directory, "isohdpfx.bin"));
+            if let Some(FileType::Regular { path }) = isohybrid_mbr.map(|file| &file.file_type) {
+                opt.isohybrid_mbr = Some(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    if let Some(directory) = &opt.limine {
+        for name in LIMINE_FILE_NAMES {
+            let path = get_preset_path(directory, name);
+            if tree.get_file(&path).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Limine file \"{}\" not found in the ISO tree!", path),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1201 @@
+use crate::iso::apm::ApmPartition;
+use crate::iso::gpt::GptPartition;
+use crate::iso::mbr::{MbrPartition, MBR_PARTITION_COUNT};
+use crate::iso::sun_label;
+use crate::iso::sun_label::SunPartition;
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use chrono::NaiveDate;
+
+use std::collections::HashSet;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::str::FromStr;
+
+// Directory record flags (ECMA-119 9.1.6)
+const FLAG_DIRECTORY: u8 = 0x2;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+// POSIX file types as found in the Rock Ridge PX entry
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFREG: u32 = 0o100_000;
+const S_IFLNK: u32 = 0o120_000;
+
+/// Maximum number of SUSP continuation areas followed for a single directory record.
+const MAX_CONTINUATION_AREAS: usize = 64;
+
+/// Maximum size of the GPT partition entries array read, 128 times the minimum required by UEFI.
+const MAX_GPT_ENTRIES_SIZE: u64 = 128 * 0x4000;
+
+/// Directory record (ECMA-119 9.1) as found on disk.
+#[derive(Debug, Clone)]
+pub struct DirectoryRecord {
+    pub lba: u32,
+    pub size: u32,
+    pub recording_date: [u8; 7],
+    pub flags: u8,
+    pub identifier: Vec<u8>,
+    pub system_use: Vec<u8>,
+}
+
+impl DirectoryRecord {
+    /// Parse the directory record at the start of `data`, returning it with its length.
+    pub fn parse(data: &[u8]) -> Option<(DirectoryRecord, usize)> {
+        let record_len = usize::from(*data.first()?);
+        if record_len < 0x22 || record_len > data.len() {
+            return None;
+        }
+
+        let identifier_len = usize::from(data[0x20]);
+        if 0x21 + identifier_len > record_len {
+            return None;
+        }
+
+        // The identifier is padded to an even length
+        let system_use_start = 0x21 + identifier_len + (1 - identifier_len % 2);
+
+        let mut recording_date: [u8; 7] = [0x0; 7];
+        recording_date.copy_from_slice(&data[0x12..0x19]);
+
+        let res = DirectoryRecord {
+            lba: LittleEndian::read_u32(&data[0x2..]),
+            size: LittleEndian::read_u32(&data[0xA..]),
+            recording_date,
+            flags: data[0x19],
+            identifier: data[0x21..0x21 + identifier_len].to_vec(),
+            system_use: data[std::cmp::min(system_use_start, record_len)..record_len].to_vec(),
+        };
+
+        Some((res, record_len))
+    }
+
+    fn is_current_or_parent(&self) -> bool {
+        self.identifier == [0x0] || self.identifier == [0x1]
+    }
+}
+
+/// Naming scheme used when walking the directory tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Iso,
+    Joliet,
+    RockRidge,
+}
+
+impl FromStr for Namespace {
+    type Err = String;
+
+    fn from_str(namespace: &str) -> Result<Self, Self::Err> {
+        match namespace {
+            "iso" => Ok(Namespace::Iso),
+            "joliet" => Ok(Namespace::Joliet),
+            "rockridge" => Ok(Namespace::RockRidge),
+            _ => Err(format!(
+                "unknown namespace \"{}\" (expected iso, joliet or rockridge)",
+                namespace
+            )),
+        }
+    }
+}
+
+/// POSIX attributes of a directory record (RRIP 1.12).
+#[derive(Debug, Clone, Default)]
+pub struct RockRidgeInfo {
+    pub name: Option<String>,
+    pub mode: Option<u32>,
+    pub links: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub symlink: Option<String>,
+    pub modification_time: Option<i64>,
+    pub access_time: Option<i64>,
+    pub relocated: bool,
+    pub child_link: Option<u32>,
+}
+
+/// A file or directory of the tree, multi-extent files are merged into one entry.
+#[derive(Debug, Clone)]
+pub struct IsoEntry {
+    pub identifier: String,
+    pub name: String,
+    pub record: DirectoryRecord,
+    pub extents: Vec<(u32, u32)>,
+    pub rock_ridge: Option<RockRidgeInfo>,
+}
+
+impl IsoEntry {
+    pub fn is_directory(&self) -> bool {
+        match self
+            .rock_ridge
+            .as_ref()
+            .and_then(|rock_ridge| rock_ridge.mode)
+        {
+            Some(mode) => mode & S_IFMT == S_IFDIR,
+            None => self.record.flags & FLAG_DIRECTORY != 0,
+        }
+    }
+
+    pub fn is_regular_file(&self) -> bool {
+        match self
+            .rock_ridge
+            .as_ref()
+            .and_then(|rock_ridge| rock_ridge.mode)
+        {
+            Some(mode) => mode & S_IFMT == S_IFREG,
+            None => self.record.flags & FLAG_DIRECTORY == 0,
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        self.extents.iter().map(|(_, size)| u64::from(*size)).sum()
+    }
+
+    pub fn get_symlink(&self) -> Option<&str> {
+        let rock_ridge = self.rock_ridge.as_ref()?;
+        match rock_ridge.mode {
+            Some(mode) if mode & S_IFMT != S_IFLNK => None,
+            _ => rock_ridge.symlink.as_deref(),
+        }
+    }
+
+    pub fn get_recording_time(&self) -> i64 {
+        read_record_date(&self.record.recording_date).unwrap_or(0)
+    }
+
+    /// Get the modification time as a UNIX timestamp, falling back to the recording date.
+    pub fn get_modification_time(&self) -> i64 {
+        self.rock_ridge
+            .as_ref()
+            .and_then(|rock_ridge| rock_ridge.modification_time)
+            .unwrap_or_else(|| self.get_recording_time())
+    }
+
+    pub fn get_access_time(&self) -> i64 {
+        self.rock_ridge
+            .as_ref()
+            .and_then(|rock_ridge| rock_ridge.access_time)
+            .unwrap_or_else(|| self.get_modification_time())
+    }
+}
+
+/// Content of a Primary or Supplementary Volume Descriptor (ECMA-119 8.4).
+#[derive(Debug, Clone)]
+pub struct VolumeInfo {
+    pub system_id: String,
+    pub volume_id: String,
+    pub volume_space_size: u32,
+    pub volume_set_size: u16,
+    pub volume_sequence_number: u16,
+    pub logical_block_size: u16,
+    pub path_table_size: u32,
+    pub l_path_table_lba: u32,
+    pub optional_l_path_table_lba: u32,
+    pub m_path_table_lba: u32,
+    pub optional_m_path_table_lba: u32,
+    pub root_directory: DirectoryRecord,
+    pub volume_set_id: String,
+    pub publisher_id: String,
+    pub data_preparer_id: String,
+    pub application_id: String,
+    pub creation_date: String,
+    pub modification_date: String,
+    pub expiration_date: String,
+    pub effective_date: String,
+    pub file_structure_version: u8,
+    pub is_joliet: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum VolumeDescriptorKind {
+    Boot { system_id: String, catalog_lba: u32 },
+    Primary(VolumeInfo),
+    Supplementary(VolumeInfo),
+    Partition,
+    End,
+    Unknown(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct VolumeDescriptorInfo {
+    pub lba: u32,
+    pub kind: VolumeDescriptorKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct BootEntryInfo {
+    pub platform_id: u8,
+    pub is_default: bool,
+    pub bootable: bool,
+    pub media_type: u8,
+    pub load_segment: u16,
+    pub system_type: u8,
+    pub sector_count: u16,
+    pub lba: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct BootCatalogInfo {
+    pub lba: u32,
+    pub platform_id: u8,
+    pub id_string: String,
+    pub checksum_valid: bool,
+    pub entries: Vec<BootEntryInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MbrInfo {
+    pub disk_id: u32,
+    pub partitions: [Option<MbrPartition>; MBR_PARTITION_COUNT],
+}
+
+#[derive(Debug, Clone)]
+pub struct GptInfo {
+    pub disk_guid: [u8; 16],
+    pub current_sector: u64,
+    pub backup_sector: u64,
+    pub first_usable_sector: u64,
+    pub last_usable_sector: u64,
+    pub entries_sector: u64,
+    pub entry_count: u32,
+    pub entry_size: u32,
+    pub header_crc32_valid: bool,
+    pub entries_crc32_valid: bool,
+    pub partitions: Vec<(u32, GptPartition)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SunLabelInfo {
+    pub label: String,
+    pub checksum_valid: bool,
+    pub partitions: Vec<(usize, SunPartition)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SgiBootFileInfo {
+    pub name: String,
+    pub sector: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SgiVolumeHeaderInfo {
+    pub checksum_valid: bool,
+    pub boot_file_name: String,
+    pub boot_files: Vec<SgiBootFileInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecBootBlockInfo {
+    pub load_address: u32,
+    pub exec_address: u32,
+    pub sector_count: u32,
+    pub start_sector: u32,
+}
+
+fn read_string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches([' ', '\0'])
+        .to_string()
+}
+
+/// Format a 17-byte volume descriptor date (ECMA-119 8.4.26.1).
+fn read_volume_date(data: &[u8]) -> String {
+    if data[..16].iter().all(|c| *c == b'0' || *c == 0x0) {
+        return String::from("not specified");
+    }
+
+    let digits = String::from_utf8_lossy(&data[..16]);
+    let offset = i32::from(data[16] as i8) * 15;
+
+    format!(
+        "{}-{}-{} {}:{}:{}.{} (GMT{:+03}:{:02})",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14],
+        &digits[14..16],
+        offset / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Convert a 7-byte directory record date (ECMA-119 9.1.5) to a UNIX timestamp.
+fn read_record_date(data: &[u8]) -> Option<i64> {
+    let date = NaiveDate::from_ymd_opt(
+        1900 + i32::from(data[0]),
+        u32::from(data[1]),
+        u32::from(data[2]),
+    )?
+    .and_hms_opt(u32::from(data[3]), u32::from(data[4]), u32::from(data[5]))?;
+
+    Some(date.timestamp() - i64::from(data[6] as i8) * 15 * 60)
+}
+
+/// Convert a 17-byte volume descriptor date (ECMA-119 8.4.26.1) to a UNIX timestamp.
+fn read_volume_timestamp(data: &[u8]) -> Option<i64> {
+    let digits = std::str::from_utf8(&data[..14]).ok()?;
+    let field = |start: usize, end: usize| digits.get(start..end)?.parse::<u32>().ok();
+
+    let date = NaiveDate::from_ymd_opt(field(0, 4)? as i32, field(4, 6)?, field(6, 8)?)?
+        .and_hms_opt(field(8, 10)?, field(10, 12)?, field(12, 14)?)?;
+
+    Some(date.timestamp() - i64::from(data[16] as i8) * 15 * 60)
+}
+
+/// Decode the identifier of a directory record, Joliet identifiers are in UCS-2 big endian.
+fn read_record_identifier(identifier: &[u8], is_joliet: bool) -> String {
+    if is_joliet {
+        let code_units: Vec<u16> = identifier
+            .chunks_exact(2)
+            .map(BigEndian::read_u16)
+            .collect();
+        String::from_utf16_lossy(&code_units)
+    } else {
+        String::from_utf8_lossy(identifier).into_owned()
+    }
+}
+
+/// Get the name of a directory record in the ISO 9660 or Joliet namespace, without its version.
+fn read_record_name(identifier: &str) -> String {
+    let name = match identifier.rfind(';') {
+        Some(position) => &identifier[..position],
+        None => identifier,
+    };
+
+    // Files without extension are recorded as "NAME."
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+/// Parse the Rock Ridge entries of a System Use Sharing Protocol entry list.
+fn parse_rock_ridge(entries: &[([u8; 2], Vec<u8>)]) -> RockRidgeInfo {
+    let mut res = RockRidgeInfo::default();
+    let mut name: Option<String> = None;
+    let mut symlink: Option<String> = None;
+    let mut symlink_separator = false;
+
+    for (signature, data) in entries {
+        match signature {
+            b"PX" if data.len() >= 32 => {
+                res.mode = Some(LittleEndian::read_u32(&data[0x0..]));
+                res.links = Some(LittleEndian::read_u32(&data[0x8..]));
+                res.uid = Some(LittleEndian::read_u32(&data[0x10..]));
+                res.gid = Some(LittleEndian::read_u32(&data[0x18..]));
+            }
+            // Current and parent directory flags have no name
+            b"NM" if !data.is_empty() && data[0] & 0x6 == 0 => {
+                name.get_or_insert_with(String::new)
+                    .push_str(&String::from_utf8_lossy(&data[1..]));
+            }
+            b"SL" if !data.is_empty() => {
+                let target = symlink.get_or_insert_with(String::new);
+
+                let mut offset = 1;
+                while offset + 2 <= data.len() {
+                    let component_flags = data[offset];
+                    let component_len = usize::from(data[offset + 1]);
+                    let end = std::cmp::min(offset + 2 + component_len, data.len());
+
+                    if symlink_separator {
+                        target.push('/');
+                    }
+
+                    if component_flags & 0x8 != 0 {
+                        target.push('/');
+                        symlink_separator = false;
+                    } else {
+                        if component_flags & 0x2 != 0 {
+                            target.push('.');
+                        } else if component_flags & 0x4 != 0 {
+                            target.push_str("..");
+                        } else {
+                            target.push_str(&String::from_utf8_lossy(&data[offset + 2..end]));
+                        }
+
+                        // The component continues in the next one
+                        symlink_separator = component_flags & 0x1 == 0;
+                    }
+
+                    offset = end;
+                }
+            }
+            b"TF" if !data.is_empty() => {
+                let flags = data[0];
+                let timestamp_size = if flags & 0x80 != 0 { 17 } else { 7 };
+
+                let mut offset = 1;
+                for bit in 0..7 {
+                    if flags & (1 << bit) == 0 {
+                        continue;
+                    }
+                    if offset + timestamp_size > data.len() {
+                        break;
+                    }
+
+                    let timestamp_data = &data[offset..offset + timestamp_size];
+                    let timestamp = if timestamp_size == 17 {
+                        read_volume_timestamp(timestamp_data)
+                    } else {
+                        read_record_date(timestamp_data)
+                    };
+
+                    match bit {
+                        1 => res.modification_time = timestamp,
+                        2 => res.access_time = timestamp,
+                        _ => {}
+                    }
+                    offset += timestamp_size;
+                }
+            }
+            b"CL" if data.len() >= 4 => res.child_link = Some(LittleEndian::read_u32(data)),
+            b"RE" => res.relocated = true,
+            _ => {}
+        }
+    }
+
+    res.name = name;
+    res.symlink = symlink;
+    res
+}
+
+fn parse_volume_info(data: &[u8]) -> Option<VolumeInfo> {
+    let (root_directory, _) = DirectoryRecord::parse(&data[0x9C..0xBE])?;
+
+    Some(VolumeInfo {
+        system_id: read_string(&data[0x8..0x28]),
+        volume_id: read_string(&data[0x28..0x48]),
+        volume_space_size: LittleEndian::read_u32(&data[0x50..]),
+        volume_set_size: LittleEndian::read_u16(&data[0x78..]),
+        volume_sequence_number: LittleEndian::read_u16(&data[0x7C..]),
+        logical_block_size: LittleEndian::read_u16(&data[0x80..]),
+        path_table_size: LittleEndian::read_u32(&data[0x84..]),
+        l_path_table_lba: LittleEndian::read_u32(&data[0x8C..]),
+        optional_l_path_table_lba: LittleEndian::read_u32(&data[0x90..]),
+        m_path_table_lba: BigEndian::read_u32(&data[0x94..]),
+        optional_m_path_table_lba: BigEndian::read_u32(&data[0x98..]),
+        root_directory,
+        volume_set_id: read_string(&data[0xBE..0x13E]),
+        publisher_id: read_string(&data[0x13E..0x1BE]),
+        data_preparer_id: read_string(&data[0x1BE..0x23E]),
+        application_id: read_string(&data[0x23E..0x2BE]),
+        creation_date: read_volume_date(&data[0x32D..0x33E]),
+        modification_date: read_volume_date(&data[0x33E..0x34F]),
+        expiration_date: read_volume_date(&data[0x34F..0x360]),
+        effective_date: read_volume_date(&data[0x360..0x371]),
+        file_structure_version: data[0x371],
+        is_joliet: data[0x0] == 0x2
+            && data[0x58] == b'%'
+            && data[0x59] == b'/'
+            && [b'@', b'C', b'E'].contains(&data[0x5A]),
+    })
+}
+
+/// Record a directory about to be walked, as the records of a crafted image may loop back to its ancestors.
+pub fn mark_directory_visited(visited: &mut HashSet<u32>, directory: &IsoEntry) -> io::Result<()> {
+    let lba = directory.extents[0].0;
+
+    if !visited.insert(lba) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("directory at LBA {} is referenced more than once", lba),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Read back the structures written by this crate from an existing ISO image.
+pub struct IsoReader<R> {
+    inner: R,
+    pub volume_descriptors: Vec<VolumeDescriptorInfo>,
+    susp_skip: Option<usize>,
+}
+
+impl<R> IsoReader<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(inner: R) -> io::Result<Self> {
+        IsoReader::new_at_session(inner, 0)
+    }
+
+    /// Read the session of a multisession medium starting at `session_lba`, the LBAs being relative to the start of the medium.
+    pub fn new_at_session(inner: R, session_lba: u32) -> io::Result<Self> {
+        let mut res = IsoReader {
+            inner,
+            volume_descriptors: Vec::new(),
+            susp_skip: None,
+        };
+
+        let mut lba = session_lba + 0x10;
+        loop {
+            let data = res.read_blocks(lba, 1)?;
+            if &data[0x1..0x6] != b"CD001" {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid volume descriptor at LBA {}", lba),
+                ));
+            }
+
+            let kind = match data[0] {
+                0x0 => VolumeDescriptorKind::Boot {
+                    system_id: read_string(&data[0x7..0x27]),
+                    catalog_lba: LittleEndian::read_u32(&data[0x47..]),
+                },
+                0x1 | 0x2 => {
+                    let volume_info = parse_volume_info(&data).ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid root directory record at LBA {}", lba),
+                        )
+                    })?;
+
+                    if data[0] == 0x1 {
+                        VolumeDescriptorKind::Primary(volume_info)
+                    } else {
+                        VolumeDescriptorKind::Supplementary(volume_info)
+                    }
+                }
+                0x3 => VolumeDescriptorKind::Partition,
+                0xFF => VolumeDescriptorKind::End,
+                volume_type => VolumeDescriptorKind::Unknown(volume_type),
+            };
+
+            let is_end = matches!(kind, VolumeDescriptorKind::End);
+            res.volume_descriptors
+                .push(VolumeDescriptorInfo { lba, kind });
+
+            if is_end {
+                break;
+            }
+            lba += 1;
+        }
+
+        // The SP entry of the root directory announces the System Use Sharing Protocol
+        if let Some(root) = res
+            .get_volume(Namespace::Iso)
+            .map(|volume| volume.root_directory.clone())
+        {
+            let data = res.read_blocks(root.lba, 1)?;
+            if let Some((current, _)) = DirectoryRecord::parse(&data) {
+                let system_use = &current.system_use;
+                if system_use.len() >= 7
+                    && &system_use[0x0..0x2] == b"SP"
+                    && system_use[0x4..0x6] == [0xBE, 0xEF]
+                {
+                    res.susp_skip = Some(usize::from(system_use[0x6]));
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    pub fn get_volume(&self, namespace: Namespace) -> Option<&VolumeInfo> {
+        self.volume_descriptors
+            .iter()
+            .find_map(|volume| match (&volume.kind, namespace) {
+                (VolumeDescriptorKind::Primary(volume_info), Namespace::Iso)
+                | (VolumeDescriptorKind::Primary(volume_info), Namespace::RockRidge) => {
+                    Some(volume_info)
+                }
+                (VolumeDescriptorKind::Supplementary(volume_info), Namespace::Joliet)
+                    if volume_info.is_joliet =>
+                {
+                    Some(volume_info)
+                }
+                _ => None,
+            })
+    }
+
+    /// Get the number of bytes to skip in the System Use fields, if the image uses SUSP.
+    pub fn get_susp_skip(&self) -> Option<usize> {
+        self.susp_skip
+    }
+
+    pub fn get_image_size(&mut self) -> io::Result<u64> {
+        self.inner.seek(SeekFrom::End(0))
+    }
+
+    pub fn has_namespace(&self, namespace: Namespace) -> bool {
+        match namespace {
+            Namespace::RockRidge => self.susp_skip.is_some(),
+            _ => self.get_volume(namespace).is_some(),
+        }
+    }
+
+    /// Get the richest namespace available: Rock Ridge, then Joliet, then plain ISO 9660.
+    pub fn get_default_namespace(&self) -> Namespace {
+        [Namespace::RockRidge, Namespace::Joliet]
+            .iter()
+            .copied()
+            .find(|namespace| self.has_namespace(*namespace))
+            .unwrap_or(Namespace::Iso)
+    }
+
+    /// Read the SUSP entries of a directory record, following the continuation areas.
+    fn read_system_use_entries(
+        &mut self,
+        system_use: &[u8],
+        skip: usize,
+    ) -> io::Result<Vec<([u8; 2], Vec<u8>)>> {
+        let mut res = Vec::new();
+        let mut area = system_use.get(skip..).unwrap_or(&[]).to_vec();
+
+        for _ in 0..MAX_CONTINUATION_AREAS {
+            let mut continuation = None;
+
+            let mut offset = 0;
+            while offset + 4 <= area.len() {
+                let entry_len = usize::from(area[offset + 2]);
+                if entry_len < 4 || offset + entry_len > area.len() {
+                    break;
+                }
+
+                let signature = [area[offset], area[offset + 1]];
+                let data = area[offset + 4..offset + entry_len].to_vec();
+                offset += entry_len;
+
+                match &signature {
+                    b"CE" if data.len() >= 24 => {
+                        continuation = Some((
+                            LittleEndian::read_u32(&data[0x0..]),
+                            LittleEndian::read_u32(&data[0x8..]),
+                            LittleEndian::read_u32(&data[0x10..]),
+                        ));
+                    }
+                    b"ST" => break,
+                    _ => res.push((signature, data)),
+                }
+            }
+
+            match continuation {
+                Some((lba, offset, size)) => {
+                    area = self.read_data(
+                        u64::from(lba) * LOGIC_SIZE as u64 + u64::from(offset),
+                        size as usize,
+                    )?;
+                }
+                None => break,
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn get_rock_ridge(
+        &mut self,
+        record: &DirectoryRecord,
+        skip: usize,
+    ) -> io::Result<RockRidgeInfo> {
+        let entries = self.read_system_use_entries(&record.system_use, skip)?;
+        Ok(parse_rock_ridge(&entries))
+    }
+
+    /// Get the root directory of the given namespace.
+    pub fn get_root(&mut self, namespace: Namespace) -> io::Result<IsoEntry> {
+        let root = match self.get_volume(namespace) {
+            Some(volume) => volume.root_directory.clone(),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {:?} namespace in the ISO image", namespace),
+                ))
+            }
+        };
+
+        // The "." record of the root holds its attributes
+        let data = self.read_blocks(root.lba, 1)?;
+        let record = match DirectoryRecord::parse(&data) {
+            Some((record, _)) => record,
+            None => root,
+        };
+
+        let rock_ridge = match (namespace, self.susp_skip) {
+            (Namespace::RockRidge, Some(_)) => Some(self.get_rock_ridge(&record, 0)?),
+            _ => None,
+        };
+
+        Ok(IsoEntry {
+            identifier: String::new(),
+            name: String::new(),
+            extents: vec![(record.lba, record.size)],
+            record,
+            rock_ridge,
+        })
+    }
+
+    /// Read the entries of a directory, without "." and "..".
+    pub fn read_directory(
+        &mut self,
+        directory: &IsoEntry,
+        namespace: Namespace,
+    ) -> io::Result<Vec<IsoEntry>> {
+        let mut res = Vec::new();
+        let (lba, size) = directory.extents[0];
+        let data = self.read_blocks(lba, size.div_ceil(LOGIC_SIZE_U32))?;
+        let data = &data[..size as usize];
+
+        let mut extents = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            // Records don't cross logical block boundaries
+            if data[offset] == 0x0 {
+                offset = (offset / LOGIC_SIZE + 1) * LOGIC_SIZE;
+                continue;
+            }
+
+            let (record, record_len) =
+                DirectoryRecord::parse(&data[offset..]).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid directory record at LBA {} offset {}", lba, offset),
+                    )
+                })?;
+            offset += record_len;
+
+            if record.is_current_or_parent() {
+                continue;
+            }
+
+            extents.push((record.lba, record.size));
+            if record.flags & FLAG_MULTI_EXTENT != 0 {
+                continue;
+            }
+
+            let rock_ridge = match (namespace, self.susp_skip) {
+                (Namespace::RockRidge, Some(skip)) => Some(self.get_rock_ridge(&record, skip)?),
+                _ => None,
+            };
+
+            let identifier =
+                read_record_identifier(&record.identifier, namespace == Namespace::Joliet);
+            let mut entry = IsoEntry {
+                name: read_record_name(&identifier),
+                identifier,
+                extents: std::mem::take(&mut extents),
+                record,
+                rock_ridge,
+            };
+
+            if let Some(rock_ridge) = &entry.rock_ridge {
+                // Relocated directories are listed at their original location
+                if rock_ridge.relocated {
+                    continue;
+                }
+
+                if let Some(name) = &rock_ridge.name {
+                    entry.name = name.clone();
+                }
+
+                if let Some(child_lba) = rock_ridge.child_link {
+                    let child_data = self.read_blocks(child_lba, 1)?;
+                    if let Some((child, _)) = DirectoryRecord::parse(&child_data) {
+                        entry.extents = vec![(child.lba, child.size)];
+                        entry.record.flags |= FLAG_DIRECTORY;
+                    }
+                }
+            }
+
+            res.push(entry);
+        }
+
+        Ok(res)
+    }
+
+    /// Find an entry by its path in the given namespace.
+    pub fn find_entry(&mut self, path: &str, namespace: Namespace) -> io::Result<Option<IsoEntry>> {
+        let mut res = self.get_root(namespace)?;
+
+        for component in path.split('/').filter(|component| !component.is_empty()) {
+            if !res.is_directory() {
+                return Ok(None);
+            }
+
+            let child = self
+                .read_directory(&res, namespace)?
+                .into_iter()
+                .find(|entry| entry.name == component);
+
+            res = match child {
+                Some(child) => child,
+                None => return Ok(None),
+            };
+        }
+
+        Ok(Some(res))
+    }
+
+    /// Copy the content of a file to `output_writter`.
+    pub fn read_content<T>(&mut self, entry: &IsoEntry, output_writter: &mut T) -> io::Result<()>
+    where
+        T: Write,
+    {
+        for (lba, size) in &entry.extents {
+            self.inner
+                .seek(SeekFrom::Start(u64::from(*lba) * LOGIC_SIZE as u64))?;
+            let copied = io::copy(
+                &mut (&mut self.inner).take(u64::from(*size)),
+                output_writter,
+            )?;
+
+            if copied != u64::from(*size) {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("extent at LBA {} is truncated", lba),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_data(&mut self, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+        // Sizes come from the image, don't allocate more than what it can hold
+        let image_size = self.get_image_size()?;
+        if offset
+            .checked_add(size as u64)
+            .is_none_or(|end| end > image_size)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{} bytes at offset {} exceed the {} bytes of the image",
+                    size, offset, image_size
+                ),
+            ));
+        }
+
+        let mut res = vec![0u8; size];
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.read_exact(&mut res)?;
+        Ok(res)
+    }
+
+    pub fn read_blocks(&mut self, lba: u32, count: u32) -> io::Result<Vec<u8>> {
+        self.read_data(
+            u64::from(lba) * LOGIC_SIZE as u64,
+            count as usize * LOGIC_SIZE,
+        )
+    }
+
+    pub fn read_boot_catalog(&mut self) -> io::Result<Option<BootCatalogInfo>> {
+        let catalog_lba = self
+            .volume_descriptors
+            .iter()
+            .find_map(|volume| match &volume.kind {
+                VolumeDescriptorKind::Boot {
+                    system_id,
+                    catalog_lba,
+                } if system_id == "EL TORITO SPECIFICATION" => Some(*catalog_lba),
+                _ => None,
+            });
+
+        let catalog_lba = match catalog_lba {
+            Some(catalog_lba) => catalog_lba,
+            None => return Ok(None),
+        };
+
+        let data = self.read_blocks(catalog_lba, 1)?;
+        if data[0x0] != 0x1 || data[0x1E] != 0x55 || data[0x1F] != 0xAA {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid boot catalog validation entry at LBA {}",
+                    catalog_lba
+                ),
+            ));
+        }
+
+        let checksum = data[..0x20].chunks(2).fold(0u16, |sum, word| {
+            sum.wrapping_add(LittleEndian::read_u16(word))
+        });
+
+        let mut res = BootCatalogInfo {
+            lba: catalog_lba,
+            platform_id: data[0x1],
+            id_string: read_string(&data[0x4..0x1C]),
+            checksum_valid: checksum == 0,
+            entries: Vec::new(),
+        };
+
+        let parse_entry = |entry: &[u8], platform_id: u8, is_default: bool| BootEntryInfo {
+            platform_id,
+            is_default,
+            bootable: entry[0x0] == 0x88,
+            media_type: entry[0x1],
+            load_segment: LittleEndian::read_u16(&entry[0x2..]),
+            system_type: entry[0x4],
+            sector_count: LittleEndian::read_u16(&entry[0x6..]),
+            lba: LittleEndian::read_u32(&entry[0x8..]),
+        };
+
+        res.entries
+            .push(parse_entry(&data[0x20..0x40], res.platform_id, true));
+
+        // Section headers (0x90, or 0x91 for the last one) followed by their section entries
+        let mut offset = 0x40;
+        while offset + 0x20 <= data.len() && (data[offset] == 0x90 || data[offset] == 0x91) {
+            let is_last = data[offset] == 0x91;
+            let platform_id = data[offset + 0x1];
+            let entry_count = usize::from(LittleEndian::read_u16(&data[offset + 0x2..]));
+            offset += 0x20;
+
+            for _ in 0..entry_count {
+                if offset + 0x20 > data.len() {
+                    break;
+                }
+                res.entries.push(parse_entry(
+                    &data[offset..offset + 0x20],
+                    platform_id,
+                    false,
+                ));
+                offset += 0x20;
+            }
+
+            if is_last {
+                break;
+            }
+        }
+
+        Ok(Some(res))
+    }
+
+    pub fn read_mbr(&mut self) -> io::Result<Option<MbrInfo>> {
+        let data = self.read_data(0, SECTOR_SIZE as usize)?;
+        if data[0x1FE] != 0x55 || data[0x1FF] != 0xAA {
+            return Ok(None);
+        }
+
+        let mut partitions: [Option<MbrPartition>; MBR_PARTITION_COUNT] = Default::default();
+        for (index, partition) in partitions.iter_mut().enumerate() {
+            let entry = &data[0x1BE + index * 0x10..0x1BE + (index + 1) * 0x10];
+            if entry[0x4] != 0x0 {
+                *partition = Some(MbrPartition {
+                    bootable: entry[0x0] == 0x80,
+                    partition_type: entry[0x4],
+                    start_sector: LittleEndian::read_u32(&entry[0x8..]),
+                    sector_count: LittleEndian::read_u32(&entry[0xC..]),
+                });
+            }
+        }
+
+        Ok(Some(MbrInfo {
+            disk_id: LittleEndian::read_u32(&data[0x1B8..]),
+            partitions,
+        }))
+    }
+
+    /// Read the GPT header at the given sector (1 for the primary one).
+    pub fn read_gpt_at(&mut self, sector: u64) -> io::Result<Option<GptInfo>> {
+        let header = self.read_data(sector * u64::from(SECTOR_SIZE), SECTOR_SIZE as usize)?;
+        if &header[0x0..0x8] != b"EFI PART" {
+            return Ok(None);
+        }
+
+        let header_size = LittleEndian::read_u32(&header[0xC..]) as usize;
+        if !(92..=header.len()).contains(&header_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid GPT header size {} at sector {}",
+                    header_size, sector
+                ),
+            ));
+        }
+
+        let mut header_copy = header[..header_size].to_vec();
+        let header_crc32 = LittleEndian::read_u32(&header[0x10..]);
+        header_copy[0x10..0x14].copy_from_slice(&[0x0; 4]);
+
+        let mut disk_guid: [u8; 16] = [0x0; 16];
+        disk_guid.copy_from_slice(&header[0x38..0x48]);
+
+        let entries_sector = LittleEndian::read_u64(&header[0x48..]);
+        let entry_count = LittleEndian::read_u32(&header[0x50..]);
+        let entry_size = LittleEndian::read_u32(&header[0x54..]);
+        let entries_crc32 = LittleEndian::read_u32(&header[0x58..]);
+
+        let entries_size = u64::from(entry_count) * u64::from(entry_size);
+        if entry_size < 0x80 || entries_size > MAX_GPT_ENTRIES_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid GPT partition entries ({} of {} bytes) at sector {}",
+                    entry_count, entry_size, sector
+                ),
+            ));
+        }
+
+        let entries = self.read_data(
+            entries_sector
+                .checked_mul(u64::from(SECTOR_SIZE))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid GPT partition entries sector {}", entries_sector),
+                    )
+                })?,
+            entries_size as usize,
+        )?;
+
+        let mut partitions = Vec::new();
+        for (index, entry) in entries.chunks(entry_size as usize).enumerate() {
+            if entry.len() < 0x80 || entry[0x0..0x10].iter().all(|byte| *byte == 0x0) {
+                continue;
+            }
+
+            let mut type_guid: [u8; 16] = [0x0; 16];
+            type_guid.copy_from_slice(&entry[0x0..0x10]);
+            let mut guid: [u8; 16] = [0x0; 16];
+            guid.copy_from_slice(&entry[0x10..0x20]);
+
+            let name: Vec<u16> = entry[0x38..0x80]
+                .chunks(2)
+                .map(LittleEndian::read_u16)
+                .take_while(|code_unit| *code_unit != 0x0)
+                .collect();
+
+            partitions.push((
+                index as u32 + 1,
+                GptPartition {
+                    type_guid,
+                    guid,
+                    start_sector: LittleEndian::read_u64(&entry[0x20..]),
+                    end_sector: LittleEndian::read_u64(&entry[0x28..]),
+                    name: String::from_utf16_lossy(&name),
+                },
+            ));
+        }
+
+        Ok(Some(GptInfo {
+            disk_guid,
+            current_sector: LittleEndian::read_u64(&header[0x18..]),
+            backup_sector: LittleEndian::read_u64(&header[0x20..]),
+            first_usable_sector: LittleEndian::read_u64(&header[0x28..]),
+            last_usable_sector: LittleEndian::read_u64(&header[0x30..]),
+            entries_sector,
+            entry_count,
+            entry_size,
+            header_crc32_valid: utils::crc32(&header_copy) == header_crc32,
+            entries_crc32_valid: utils::crc32(&entries) == entries_crc32,
+            partitions,
+        }))
+    }
+
+    pub fn read_apm(&mut self) -> io::Result<Vec<ApmPartition>> {
+        let mut res = Vec::new();
+
+        let ddm = self.read_data(0, 0x4)?;
+        if &ddm[0x0..0x2] != b"ER" {
+            return Ok(res);
+        }
+
+        let block_size = u64::from(BigEndian::read_u16(&ddm[0x2..]));
+        let block_size = if block_size == 0 {
+            u64::from(LOGIC_SIZE_U32)
+        } else {
+            block_size
+        };
+
+        let mut index = 1;
+        loop {
+            let entry = self.read_data(index * block_size, 0x58)?;
+            if &entry[0x0..0x2] != b"PM" {
+                break;
+            }
+
+            res.push(ApmPartition {
+                name: read_string(&entry[0x10..0x30]),
+                partition_type: read_string(&entry[0x30..0x50]),
+                start_block: BigEndian::read_u32(&entry[0x8..]),
+                block_count: BigEndian::read_u32(&entry[0xC..]),
+            });
+
+            let map_block_count = u64::from(BigEndian::read_u32(&entry[0x4..]));
+            if index >= map_block_count {
+                break;
+            }
+            index += 1;
+        }
+
+        Ok(res)
+    }
+
+    pub fn read_sun_label(&mut self) -> io::Result<Option<SunLabelInfo>> {
+        let data = self.read_data(0, SECTOR_SIZE as usize)?;
+        if BigEndian::read_u16(&data[0x1FC..]) != 0xDABE {
+            return Ok(None);
+        }
+
+        let checksum = data
+            .chunks(2)
+            .fold(0u16, |checksum, word| checksum ^ BigEndian::read_u16(word));
+
+        let partitions = (0..sun_label::SUN_PARTITION_COUNT)
+            .map(|index| (index, &data[0x1BC + index * 0x8..0x1BC + (index + 1) * 0x8]))
+            .filter(|(_, entry)| BigEndian::read_u32(&entry[0x4..]) != 0)
+            .map(|(index, entry)| {
+                (
+                    index,
+                    SunPartition {
+                        start_sector: BigEndian::read_u32(entry) * sun_label::SECTORS_PER_CYLINDER,
+                        sector_count: BigEndian::read_u32(&entry[0x4..]),
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Some(SunLabelInfo {
+            label: read_string(&data[0x0..0x80]),
+            checksum_valid: checksum == 0,
+            partitions,
+        }))
+    }
+
+    pub fn read_sgi_volume_header(&mut self) -> io::Result<Option<SgiVolumeHeaderInfo>> {
+        let data = self.read_data(0, SECTOR_SIZE as usize)?;
+        if BigEndian::read_u32(&data) != 0x0BE5_A941 {
+            return Ok(None);
+        }
+
+        let checksum = data[..0x1FC].chunks(4).fold(0u32, |sum, word| {
+            sum.wrapping_add(BigEndian::read_u32(word))
+        });
+
+        let boot_files = data[0x48..0x138]
+            .chunks(0x10)
+            .filter(|entry| entry[0x0] != 0x0)
+            .map(|entry| SgiBootFileInfo {
+                name: read_string(&entry[0x0..0x8]),
+                sector: BigEndian::read_u32(&entry[0x8..]),
+                size: BigEndian::read_u32(&entry[0xC..]),
+            })
+            .collect();
+
+        Ok(Some(SgiVolumeHeaderInfo {
+            checksum_valid: checksum == 0,
+            boot_file_name: read_string(&data[0x8..0x18]),
+            boot_files,
+        }))
+    }
+
+    pub fn read_dec_boot_block(&mut self) -> io::Result<Option<DecBootBlockInfo>> {
+        let data = self.read_data(0x1E0, 0x18)?;
+        if LittleEndian::read_u32(&data) != 0x0002_757A {
+            return Ok(None);
+        }
+
+        Ok(Some(DecBootBlockInfo {
+            load_address: LittleEndian::read_u32(&data[0x8..]),
+            exec_address: LittleEndian::read_u32(&data[0xC..]),
+            sector_count: LittleEndian::read_u32(&data[0x10..]),
+            start_sector: LittleEndian::read_u32(&data[0x14..]),
+        }))
+    }
+}
@@ -0,0 +1,382 @@
+use crate::iso;
+use crate::iso::fat;
+use crate::iso::option::{Opt, RescueOpt};
+use crate::iso::utils;
+
+use structopt::StructOpt;
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DEFAULT_GRUB_DIRECTORY: &str = "/usr/lib/grub";
+
+const BIOS_PLATFORM: &str = "i386-pc";
+
+/// EFI platforms and the name of their default boot application in the EFI System Partition.
+const EFI_PLATFORMS: [(&str, &str); 5] = [
+    ("i386-efi", "bootia32.efi"),
+    ("x86_64-efi", "bootx64.efi"),
+    ("ia64-efi", "bootia64.efi"),
+    ("arm-efi", "bootarm.efi"),
+    ("arm64-efi", "bootaa64.efi"),
+];
+
+/// Configurations of the payload, the first one is loaded as is and the others are offered in the generated menu.
+const PAYLOAD_CONFIGS: [&str; 3] = ["boot/grub/grub.cfg", "boot/grub2/grub.cfg", "grub/grub.cfg"];
+
+/// Directories of the payload searched for Linux kernels and their initial ramdisks.
+const KERNEL_DIRECTORIES: [&str; 2] = ["", "boot"];
+
+const BIOS_MODULES: &str = "biosdisk iso9660 search";
+const EFI_MODULES: &str = "part_gpt part_msdos iso9660 fat search";
+
+fn get_efi_image_name(platform: &str) -> Option<&'static str> {
+    EFI_PLATFORMS
+        .iter()
+        .find(|(name, _)| *name == platform)
+        .map(|(_, image_name)| *image_name)
+}
+
+fn get_platform_name(directory: &Path) -> io::Result<String> {
+    if !directory.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "GRUB platform directory \"{}\" not found",
+                directory.display()
+            ),
+        ));
+    }
+
+    let name = directory
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("");
+
+    if name != BIOS_PLATFORM && get_efi_image_name(name).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unsupported GRUB platform directory \"{}\"",
+                directory.display()
+            ),
+        ));
+    }
+
+    Ok(String::from(name))
+}
+
+/// Find the supported platforms installed in the default GRUB directory.
+fn get_default_platform_directories() -> io::Result<Vec<PathBuf>> {
+    let grub_directory = PathBuf::from(DEFAULT_GRUB_DIRECTORY);
+
+    let res: Vec<PathBuf> = std::iter::once(BIOS_PLATFORM)
+        .chain(EFI_PLATFORMS.iter().map(|(name, _)| *name))
+        .map(|name| grub_directory.join(name))
+        .filter(|directory| directory.join("moddep.lst").is_file())
+        .collect();
+
+    if res.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no GRUB platform found in {}", DEFAULT_GRUB_DIRECTORY),
+        ));
+    }
+
+    Ok(res)
+}
+
+/// Copy the modules and their lists of a platform directory to the ISO tree.
+fn copy_modules(platform_directory: &Path, destination: &Path) -> io::Result<()> {
+    fs::create_dir_all(destination)?;
+
+    for entry in fs::read_dir(platform_directory)? {
+        let path = entry?.path();
+        let is_module = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("mod") | Some("lst")
+        );
+
+        if is_module && path.is_file() {
+            fs::copy(&path, destination.join(path.file_name().unwrap()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_grub_mkimage(
+    rescue_opt: &RescueOpt,
+    platform_directory: &Path,
+    format: &str,
+    output: &Path,
+    early_config: &Path,
+    modules: &str,
+) -> io::Result<()> {
+    let mut command = Command::new(&rescue_opt.grub_mkimage);
+    command
+        .arg("-d")
+        .arg(platform_directory)
+        .arg("-O")
+        .arg(format)
+        .arg("-o")
+        .arg(output)
+        .arg("-p")
+        .arg("/boot/grub")
+        .arg("-c")
+        .arg(early_config)
+        .args(modules.split_whitespace());
+
+    if let Some(extra_modules) = &rescue_opt.modules {
+        command.args(extra_modules.split_whitespace());
+    }
+
+    let status = command.status().map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("cannot run \"{}\": {}", rescue_opt.grub_mkimage, error),
+        )
+    })?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} failed for platform {}",
+            rescue_opt.grub_mkimage, format
+        )));
+    }
+
+    Ok(())
+}
+
+/// Get the sorted names of the files of `directory` starting with one of the prefixes.
+fn find_files(directory: &Path, prefixes: &[&str]) -> io::Result<Vec<String>> {
+    let mut res = Vec::new();
+
+    if !directory.is_dir() {
+        return Ok(res);
+    }
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if prefixes.iter().any(|prefix| name.starts_with(prefix)) && entry.path().is_file() {
+            res.push(name);
+        }
+    }
+
+    res.sort();
+    Ok(res)
+}
+
+/// Generate a GRUB configuration booting the Linux kernels and loading the configurations found in the payload.
+fn generate_grub_config(input_files: &[PathBuf]) -> io::Result<String> {
+    let mut res =
+        String::from("# Generated by mkisofs-rs rescue\ninsmod all_video\nset timeout=5\n");
+
+    for input_file in input_files {
+        for kernel_directory in KERNEL_DIRECTORIES {
+            let directory = input_file.join(kernel_directory);
+            let iso_directory = if kernel_directory.is_empty() {
+                String::new()
+            } else {
+                format!("/{}", kernel_directory)
+            };
+
+            let initrds = find_files(&directory, &["initrd", "initramfs"])?;
+            for kernel in find_files(&directory, &["vmlinuz", "bzImage"])? {
+                // "vmlinuz-6.1.0" goes with "initrd.img-6.1.0" or "initramfs-6.1.0.img"
+                let version = kernel
+                    .trim_start_matches("vmlinuz")
+                    .trim_start_matches("bzImage");
+                let initrd = initrds
+                    .iter()
+                    .find(|initrd| {
+                        !version.is_empty()
+                            && (initrd.ends_with(version)
+                                || initrd.ends_with(&format!("{}.img", version)))
+                    })
+                    .or_else(|| initrds.first());
+
+                res.push_str(&format!(
+                    "\nmenuentry \"Linux {0}/{1}\" {{\n    linux {0}/{1}\n",
+                    iso_directory, kernel
+                ));
+                if let Some(initrd) = initrd {
+                    res.push_str(&format!("    initrd {}/{}\n", iso_directory, initrd));
+                }
+                res.push_str("}\n");
+            }
+        }
+
+        for config in PAYLOAD_CONFIGS.iter().skip(1) {
+            if input_file.join(config).is_file() {
+                res.push_str(&format!(
+                    "\nmenuentry \"Configuration /{0}\" {{\n    configfile /{0}\n}}\n",
+                    config
+                ));
+            }
+        }
+    }
+
+    res.push_str(concat!(
+        "\nif [ \"$grub_platform\" = \"pc\" ]; then\n",
+        "    menuentry \"Boot from the first hard disk\" {\n",
+        "        set root=(hd0)\n",
+        "        chainloader +1\n",
+        "    }\n",
+        "else\n",
+        "    menuentry \"UEFI firmware settings\" {\n",
+        "        fwsetup\n",
+        "    }\n",
+        "fi\n",
+        "\nmenuentry \"Reboot\" {\n    reboot\n}\n",
+        "\nmenuentry \"Power off\" {\n    halt\n}\n",
+    ));
+
+    Ok(res)
+}
+
+/// Populate the staging tree and return the mkisofs arguments needed to make it bootable.
+fn prepare_rescue_tree(
+    rescue_opt: &RescueOpt,
+    tree_directory: &Path,
+    work_directory: &Path,
+) -> io::Result<Vec<OsString>> {
+    let platform_directories = if rescue_opt.directory.is_empty() {
+        get_default_platform_directories()?
+    } else {
+        rescue_opt.directory.clone()
+    };
+
+    let grub_directory = tree_directory.join("boot").join("grub");
+    fs::create_dir_all(&grub_directory)?;
+    fs::create_dir_all(work_directory)?;
+
+    // The core images find the ISO filesystem by searching for this marker file
+    let marker_name = format!("mkisofs-rs-{:016x}.marker", utils::generate_random_u64());
+    fs::write(grub_directory.join(&marker_name), marker_name.as_bytes())?;
+
+    let early_config = work_directory.join("early.cfg");
+    fs::write(
+        &early_config,
+        format!(
+            "search --no-floppy --file --set=root /boot/grub/{}\nset prefix=($root)/boot/grub\n",
+            marker_name
+        ),
+    )?;
+
+    let mut args: Vec<OsString> = Vec::new();
+    let mut efi_files: Vec<(String, Vec<u8>)> = Vec::new();
+
+    for platform_directory in &platform_directories {
+        let platform = get_platform_name(platform_directory)?;
+        copy_modules(platform_directory, &grub_directory.join(&platform))?;
+
+        if platform == BIOS_PLATFORM {
+            let core_image = work_directory.join("core.img");
+            run_grub_mkimage(
+                rescue_opt,
+                platform_directory,
+                &platform,
+                &core_image,
+                &early_config,
+                BIOS_MODULES,
+            )?;
+
+            // The El Torito image is the CD-ROM boot sector followed by the core image
+            let mut eltorito_image = fs::read(platform_directory.join("cdboot.img"))?;
+            eltorito_image.append(&mut fs::read(&core_image)?);
+            fs::write(
+                grub_directory.join(&platform).join("eltorito.img"),
+                eltorito_image,
+            )?;
+
+            args.push(OsString::from("--grub2-mbr"));
+            args.push(platform_directory.join("boot_hybrid.img").into_os_string());
+            args.push(OsString::from("-b"));
+            args.push(OsString::from("boot/grub/i386-pc/eltorito.img"));
+            args.push(OsString::from("--no-emul-boot"));
+            args.push(OsString::from("--boot-load-size"));
+            args.push(OsString::from("4"));
+            args.push(OsString::from("--boot-info-table"));
+            args.push(OsString::from("--grub2-boot-info"));
+            args.push(OsString::from("--protective-msdos-label"));
+        } else {
+            let image_name = get_efi_image_name(&platform).unwrap();
+            let efi_image = work_directory.join(image_name);
+            run_grub_mkimage(
+                rescue_opt,
+                platform_directory,
+                &platform,
+                &efi_image,
+                &early_config,
+                EFI_MODULES,
+            )?;
+
+            efi_files.push((format!("efi/boot/{}", image_name), fs::read(&efi_image)?));
+        }
+    }
+
+    if !efi_files.is_empty() {
+        fs::write(
+            tree_directory.join("efi.img"),
+            fat::create_fat_image(efi_files)?,
+        )?;
+
+        args.push(OsString::from("--efi-boot-part"));
+        args.push(OsString::from("-e"));
+        args.push(OsString::from("efi.img"));
+    }
+
+    // Only generate a configuration if the payload doesn't provide one
+    let has_config = rescue_opt
+        .input_files
+        .iter()
+        .any(|path| path.join(PAYLOAD_CONFIGS[0]).is_file());
+    if !has_config {
+        fs::write(
+            grub_directory.join("grub.cfg"),
+            generate_grub_config(&rescue_opt.input_files)?,
+        )?;
+    }
+
+    Ok(args)
+}
+
+/// Create a BIOS and UEFI bootable GRUB rescue image from GRUB platform directories and a payload tree, like grub-mkrescue.
+pub fn create_rescue_iso(rescue_opt: &RescueOpt) -> io::Result<()> {
+    let staging_directory = std::env::temp_dir().join(format!(
+        "mkisofs-rs-rescue-{:016x}",
+        utils::generate_random_u64()
+    ));
+    let tree_directory = staging_directory.join("tree");
+    let work_directory = staging_directory.join("work");
+
+    let res =
+        prepare_rescue_tree(rescue_opt, &tree_directory, &work_directory).and_then(|boot_args| {
+            let mut args: Vec<OsString> = vec![
+                OsString::from("mkisofs-rs"),
+                OsString::from("-o"),
+                OsString::from(&rescue_opt.output),
+            ];
+            args.extend(boot_args);
+            args.push(tree_directory.clone().into_os_string());
+            args.extend(
+                rescue_opt
+                    .input_files
+                    .iter()
+                    .map(|path| path.clone().into_os_string()),
+            );
+
+            let mut opt = Opt::from_iter(args);
+            iso::create_iso(&mut opt)
+        });
+
+    // Always clean up the staging directory
+    let _ = fs::remove_dir_all(&staging_directory);
+
+    res
+}
@@ -0,0 +1,170 @@
+use crate::iso::appended_partition::AppendedPartition;
+use crate::iso::utils::{LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{BigEndian, WriteBytesExt};
+
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+
+pub const SUN_PARTITION_COUNT: usize = 8;
+
+/// The Sun disk label geometry uses a single head of 640 sectors per cylinder.
+pub const SECTORS_PER_CYLINDER: u32 = 640;
+
+/// Count of LB in a cylinder, boot images need to start on a cylinder boundary.
+pub const CYLINDER_LB_COUNT: u32 = SECTORS_PER_CYLINDER * SECTOR_SIZE / LOGIC_SIZE_U32;
+
+pub const DEFAULT_LABEL: &str = "CD-ROM Disc with Sun sparc boot created by mkisofs-rs";
+
+const VTOC_VERSION: u32 = 0x1;
+const VTOC_SANITY: u32 = 0x600D_DEEE;
+const SUN_LABEL_MAGIC: u16 = 0xDABE;
+
+// Partition flags
+const READ_ONLY_FLAG: u16 = 0x10;
+
+#[derive(Debug, Clone)]
+pub struct SunPartition {
+    pub start_sector: u32,
+    pub sector_count: u32,
+}
+
+/// Write the Sun disk label in the first 512 bytes of the system area starting at `label_pos`.
+pub fn write_sun_label<T>(
+    output_writter: &mut T,
+    label_pos: u64,
+    label: &str,
+    partitions: &[Option<SunPartition>; SUN_PARTITION_COUNT],
+    disk_sector_count: u32,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let current_pos = output_writter.stream_position()?;
+
+    let mut buff: Vec<u8> = Vec::new();
+
+    // ASCII label
+    let mut label_data: [u8; 0x80] = [0x0; 0x80];
+    let len = std::cmp::min(label.len(), label_data.len() - 1);
+    label_data[..len].copy_from_slice(&label.as_bytes()[..len]);
+    buff.write_all(&label_data)?;
+
+    // VTOC
+    buff.write_u32::<BigEndian>(VTOC_VERSION)?;
+
+    // Volume name
+    let volume_name: [u8; 0x8] = [0x0; 0x8];
+    buff.write_all(&volume_name)?;
+
+    buff.write_u16::<BigEndian>(SUN_PARTITION_COUNT as u16)?;
+
+    // Partitions tag and flags
+    for partition in partitions {
+        buff.write_u16::<BigEndian>(0x0)?;
+        match partition {
+            Some(_) => buff.write_u16::<BigEndian>(READ_ONLY_FLAG)?,
+            None => buff.write_u16::<BigEndian>(0x0)?,
+        }
+    }
+
+    // Padding + boot informations
+    let boot_info: [u8; 0xE] = [0x0; 0xE];
+    buff.write_all(&boot_info)?;
+
+    buff.write_u32::<BigEndian>(VTOC_SANITY)?;
+
+    // Reserved + timestamps
+    let reserved: [u8; 0x48] = [0x0; 0x48];
+    buff.write_all(&reserved)?;
+
+    // Write and read reinstruct + spare
+    let spare: [u8; 0x9C] = [0x0; 0x9C];
+    buff.write_all(&spare)?;
+
+    let cylinder_count = disk_sector_count.div_ceil(SECTORS_PER_CYLINDER) as u16;
+
+    // Rotation speed
+    buff.write_u16::<BigEndian>(350)?;
+
+    // Physical cylinder count
+    buff.write_u16::<BigEndian>(cylinder_count)?;
+
+    // Spare sectors per cylinder and obsolete fields
+    let obsolete: [u8; 0x6] = [0x0; 0x6];
+    buff.write_all(&obsolete)?;
+
+    // Interleave factor
+    buff.write_u16::<BigEndian>(0x1)?;
+
+    // Data cylinder count
+    buff.write_u16::<BigEndian>(cylinder_count)?;
+
+    // Alternate cylinder count
+    buff.write_u16::<BigEndian>(0x0)?;
+
+    // Head count
+    buff.write_u16::<BigEndian>(0x1)?;
+
+    // Sectors per head
+    buff.write_u16::<BigEndian>(SECTORS_PER_CYLINDER as u16)?;
+
+    // Obsolete
+    buff.write_u32::<BigEndian>(0x0)?;
+
+    for partition in partitions {
+        match partition {
+            Some(partition) => {
+                buff.write_u32::<BigEndian>(partition.start_sector / SECTORS_PER_CYLINDER)?;
+                buff.write_u32::<BigEndian>(partition.sector_count)?;
+            }
+            None => buff.write_u64::<BigEndian>(0x0)?,
+        }
+    }
+
+    buff.write_u16::<BigEndian>(SUN_LABEL_MAGIC)?;
+
+    // The XOR of all the 16-bit words of the label must be 0
+    let checksum = buff.chunks(2).fold(0u16, |checksum, word| {
+        checksum ^ u16::from_be_bytes([word[0], word[1]])
+    });
+    buff.write_u16::<BigEndian>(checksum)?;
+
+    output_writter.seek(SeekFrom::Start(label_pos))?;
+    output_writter.write_all(&buff)?;
+
+    output_writter.seek(SeekFrom::Start(current_pos))?;
+
+    Ok(())
+}
+
+/// Parse the comma separated boot image list given to --sparc-boot, the boot images are described by the partitions 1 to 7.
+pub fn parse_sparc_boot_images(value: &str) -> std::io::Result<Vec<AppendedPartition>> {
+    let paths: Vec<&str> = value.split(',').collect();
+    if paths.len() >= SUN_PARTITION_COUNT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "too many SPARC boot images (at most {})",
+                SUN_PARTITION_COUNT - 1
+            ),
+        ));
+    }
+
+    let mut res: Vec<AppendedPartition> = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        if path.is_empty() {
+            continue;
+        }
+
+        res.push(AppendedPartition::new(
+            (index + 1) as u8,
+            0x0,
+            PathBuf::from(path),
+        )?);
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,1024 @@
+use crate::iso::directory_entry::DirectoryEntry;
+use crate::iso::file_entry::FileEntry;
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use chrono::prelude::*;
+
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+/// BEA01, NSR02 and TEA01 following the Volume Descriptor Set Terminator (ECMA-167 2/8.3).
+pub const RECOGNITION_SEQUENCE_LB_COUNT: u32 = 3;
+
+/// Minimum extent of a Volume Descriptor Sequence (UDF 1.02 2.2.3.1)
+pub const VOLUME_DESCRIPTOR_SEQUENCE_LB_COUNT: u32 = 16;
+
+/// Logical Volume Integrity Descriptor followed by a Terminating Descriptor
+pub const INTEGRITY_SEQUENCE_LB_COUNT: u32 = 2;
+
+/// The first Anchor Volume Descriptor Pointer is always at sector 256 (ECMA-167 3/8.4.2.1).
+pub const ANCHOR_LBA: u32 = 256;
+
+const DESCRIPTOR_VERSION: u16 = 2;
+const UDF_REVISION: u16 = 0x0102;
+const VOLUME_IDENTIFIER: &str = "ISOIMAGE";
+const IMPLEMENTATION_IDENTIFIER: &str = "*mkisofs-rs";
+
+const PRIMARY_VOLUME_DESCRIPTOR: u16 = 1;
+const ANCHOR_VOLUME_DESCRIPTOR_POINTER: u16 = 2;
+const IMPLEMENTATION_USE_VOLUME_DESCRIPTOR: u16 = 4;
+const PARTITION_DESCRIPTOR: u16 = 5;
+const LOGICAL_VOLUME_DESCRIPTOR: u16 = 6;
+const UNALLOCATED_SPACE_DESCRIPTOR: u16 = 7;
+const TERMINATING_DESCRIPTOR: u16 = 8;
+const LOGICAL_VOLUME_INTEGRITY_DESCRIPTOR: u16 = 9;
+const FILE_SET_DESCRIPTOR: u16 = 256;
+const FILE_IDENTIFIER_DESCRIPTOR: u16 = 257;
+const FILE_ENTRY: u16 = 261;
+
+const FILE_TYPE_DIRECTORY: u8 = 4;
+const FILE_TYPE_REGULAR: u8 = 5;
+
+/// Size of a File Entry before its allocation descriptors
+const FILE_ENTRY_HEADER_SIZE: usize = 0xB0;
+const SHORT_AD_SIZE: usize = 0x8;
+
+/// Largest extent length of a short_ad that is a multiple of the block size (ECMA-167 4/14.14.1.1)
+const MAX_EXTENT_SIZE: u64 = 0x3FFF_F800;
+
+/// The Unique IDs 1 to 15 are reserved, the root directory has 0 (UDF 1.02 3.2.1.1)
+const FIRST_UNIQUE_ID: u64 = 16;
+
+#[derive(Debug, Clone, Default)]
+pub struct UdfLayout {
+    pub main_sequence_lba: u32,
+    pub reserve_sequence_lba: u32,
+    pub integrity_sequence_lba: u32,
+
+    /// LBA of the start of the UDF partition (holding the File Set Descriptor, then the UDF entries and the files)
+    pub partition_lba: u32,
+
+    /// Size of the File Set Descriptor and the UDF entries in LB
+    pub metadata_lb_count: u32,
+
+    /// LBA of the last Anchor Volume Descriptor Pointer, right after the UDF partition
+    pub last_anchor_lba: u32,
+}
+
+impl UdfLayout {
+    fn get_partition_lb_count(&self) -> u32 {
+        self.last_anchor_lba - self.partition_lba
+    }
+}
+
+/// Directory of the UDF tree with the location of its entries in the partition.
+struct UdfDirectory<'a> {
+    entry: &'a DirectoryEntry,
+    unique_id: u64,
+    icb_block: u32,
+    fid_block: u32,
+    fid_size: u32,
+    files: Vec<UdfFile<'a>>,
+    directories: Vec<UdfDirectory<'a>>,
+}
+
+struct UdfFile<'a> {
+    entry: &'a FileEntry,
+    unique_id: u64,
+    icb_block: u32,
+}
+
+#[derive(Debug, Default)]
+struct UdfInfo {
+    file_count: u32,
+    directory_count: u32,
+    next_unique_id: u64,
+    next_block: u32,
+}
+
+fn crc_itu_t(data: &[u8]) -> u16 {
+    let mut crc = 0x0u16;
+
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Fill the descriptor tag of the descriptor (ECMA-167 3/7.2), the buffer starting with 16 bytes for it.
+fn write_tag(descriptor: &mut [u8], tag_identifier: u16, location: u32) {
+    let crc = crc_itu_t(&descriptor[0x10..]);
+    let crc_length = (descriptor.len() - 0x10) as u16;
+
+    descriptor[0x0..0x2].copy_from_slice(&tag_identifier.to_le_bytes());
+    descriptor[0x2..0x4].copy_from_slice(&DESCRIPTOR_VERSION.to_le_bytes());
+    descriptor[0x4] = 0x0;
+    descriptor[0x5] = 0x0;
+
+    // Tag serial number
+    descriptor[0x6..0x8].copy_from_slice(&0x0u16.to_le_bytes());
+    descriptor[0x8..0xA].copy_from_slice(&crc.to_le_bytes());
+    descriptor[0xA..0xC].copy_from_slice(&crc_length.to_le_bytes());
+    descriptor[0xC..0x10].copy_from_slice(&location.to_le_bytes());
+
+    // Checksum of the tag, without itself
+    descriptor[0x4] = descriptor[..0x10]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+}
+
+fn new_descriptor() -> Vec<u8> {
+    vec![0u8; 0x10]
+}
+
+/// Encode a name as OSTA Compressed Unicode (UDF 1.02 2.1.1), with 8-bit characters when possible.
+fn encode_name(value: &str, max_size: usize) -> Vec<u8> {
+    if value.is_empty() {
+        return Vec::new();
+    }
+
+    let mut res: Vec<u8> = Vec::new();
+    if value.chars().all(|c| (c as u32) <= 0xFF) {
+        res.push(8);
+        res.extend(value.chars().map(|c| c as u8).take(max_size - 1));
+    } else {
+        res.push(16);
+        for c in value.encode_utf16().take((max_size - 1) / 2) {
+            res.extend_from_slice(&c.to_be_bytes());
+        }
+    }
+
+    res
+}
+
+fn write_dstring<T>(output_writter: &mut T, value: &str, size: usize) -> std::io::Result<()>
+where
+    T: Write,
+{
+    let mut dstring = encode_name(value, size - 1);
+    let used_size = dstring.len() as u8;
+    dstring.resize(size - 1, 0x0);
+    output_writter.write_all(&dstring)?;
+    output_writter.write_u8(used_size)?;
+
+    Ok(())
+}
+
+/// OSTA CS0 charspec (UDF 1.02 2.1.2)
+fn write_charspec<T>(output_writter: &mut T) -> std::io::Result<()>
+where
+    T: Write,
+{
+    let mut information: [u8; 63] = [0x0; 63];
+    information[..23].copy_from_slice(b"OSTA Compressed Unicode");
+
+    output_writter.write_u8(0x0)?;
+    output_writter.write_all(&information)?;
+
+    Ok(())
+}
+
+fn write_regid<T>(output_writter: &mut T, identifier: &str, suffix: &[u8]) -> std::io::Result<()>
+where
+    T: Write,
+{
+    let mut raw_identifier: [u8; 23] = [0x0; 23];
+    raw_identifier[..identifier.len()].copy_from_slice(identifier.as_bytes());
+    let mut raw_suffix: [u8; 8] = [0x0; 8];
+    raw_suffix[..suffix.len()].copy_from_slice(suffix);
+
+    // Flags
+    output_writter.write_u8(0x0)?;
+    output_writter.write_all(&raw_identifier)?;
+    output_writter.write_all(&raw_suffix)?;
+
+    Ok(())
+}
+
+fn write_implementation_regid<T>(output_writter: &mut T) -> std::io::Result<()>
+where
+    T: Write,
+{
+    write_regid(output_writter, IMPLEMENTATION_IDENTIFIER, &[])
+}
+
+/// Regid of the UDF entities, suffixed by the UDF revision (UDF 1.02 2.1.4.2)
+fn write_udf_regid<T>(output_writter: &mut T, identifier: &str) -> std::io::Result<()>
+where
+    T: Write,
+{
+    write_regid(output_writter, identifier, &UDF_REVISION.to_le_bytes())
+}
+
+fn write_timestamp<T>(output_writter: &mut T, datetime: &DateTime<Utc>) -> std::io::Result<()>
+where
+    T: Write,
+{
+    // Local time (type 1) in UTC
+    output_writter.write_u16::<LittleEndian>(0x1000)?;
+    output_writter.write_i16::<LittleEndian>(datetime.year() as i16)?;
+    output_writter.write_u8(datetime.month() as u8)?;
+    output_writter.write_u8(datetime.day() as u8)?;
+    output_writter.write_u8(datetime.hour() as u8)?;
+    output_writter.write_u8(datetime.minute() as u8)?;
+    output_writter.write_u8(datetime.second() as u8)?;
+
+    // Centiseconds, hundreds of microseconds and microseconds
+    let microseconds = datetime.timestamp_subsec_micros() % 1_000_000;
+    output_writter.write_u8((microseconds / 10_000) as u8)?;
+    output_writter.write_u8((microseconds / 100 % 100) as u8)?;
+    output_writter.write_u8((microseconds % 100) as u8)?;
+
+    Ok(())
+}
+
+fn write_extent_ad<T>(output_writter: &mut T, length: u32, location: u32) -> std::io::Result<()>
+where
+    T: Write,
+{
+    output_writter.write_u32::<LittleEndian>(length)?;
+    output_writter.write_u32::<LittleEndian>(location)?;
+
+    Ok(())
+}
+
+/// long_ad of a block of the partition, carrying the UDF Unique ID in its implementation use (UDF 1.02 2.3.10.1)
+fn write_long_ad<T>(
+    output_writter: &mut T,
+    length: u32,
+    block: u32,
+    unique_id: u64,
+) -> std::io::Result<()>
+where
+    T: Write,
+{
+    output_writter.write_u32::<LittleEndian>(length)?;
+    output_writter.write_u32::<LittleEndian>(block)?;
+
+    // Partition reference number
+    output_writter.write_u16::<LittleEndian>(0x0)?;
+
+    // Flags + Unique ID
+    output_writter.write_u16::<LittleEndian>(0x0)?;
+    output_writter.write_u32::<LittleEndian>(unique_id as u32)?;
+
+    Ok(())
+}
+
+fn get_file_identifier_size(name: &str) -> u32 {
+    let identifier_size = encode_name(name, 0xFF).len() as u32;
+
+    // Padded to 4 bytes (ECMA-167 4/14.4.9)
+    (0x26 + identifier_size).div_ceil(4) * 4
+}
+
+fn get_visible_files(directory: &DirectoryEntry) -> impl Iterator<Item = &FileEntry> {
+    directory.files_childs.iter().filter(|file| !file.hidden)
+}
+
+/// Assign the partition blocks of the File Entry and the File Identifiers of the directory, then of its content.
+fn create_directory<'a>(directory: &'a DirectoryEntry, info: &mut UdfInfo) -> UdfDirectory<'a> {
+    let unique_id = if info.directory_count == 0 {
+        0
+    } else {
+        info.next_unique_id += 1;
+        info.next_unique_id - 1
+    };
+    info.directory_count += 1;
+
+    // Parent directory identifier, then the subdirectories and the files
+    let fid_size = get_file_identifier_size("")
+        + directory
+            .dir_childs
+            .iter()
+            .map(|child| get_file_identifier_size(&child.get_file_name()))
+            .sum::<u32>()
+        + get_visible_files(directory)
+            .map(|file| get_file_identifier_size(&file.get_file_name()))
+            .sum::<u32>();
+
+    let icb_block = info.next_block;
+    let fid_block = icb_block + 1;
+    info.next_block = fid_block + fid_size.div_ceil(LOGIC_SIZE_U32);
+
+    let mut files = Vec::new();
+    for file in get_visible_files(directory) {
+        files.push(UdfFile {
+            entry: file,
+            unique_id: info.next_unique_id,
+            icb_block: info.next_block,
+        });
+        info.next_unique_id += 1;
+        info.next_block += 1;
+        info.file_count += 1;
+    }
+
+    let directories = directory
+        .dir_childs
+        .iter()
+        .map(|child| create_directory(child, info))
+        .collect();
+
+    UdfDirectory {
+        entry: directory,
+        unique_id,
+        icb_block,
+        fid_block,
+        fid_size,
+        files,
+        directories,
+    }
+}
+
+fn create_tree(tree: &DirectoryEntry) -> (UdfDirectory<'_>, UdfInfo) {
+    let mut info = UdfInfo {
+        next_unique_id: FIRST_UNIQUE_ID,
+        // The File Set Descriptor and its Terminating Descriptor start the partition
+        next_block: 2,
+        ..Default::default()
+    };
+
+    let root = create_directory(tree, &mut info);
+    (root, info)
+}
+
+pub fn get_metadata_lb_count(tree: &DirectoryEntry) -> u32 {
+    create_tree(tree).1.next_block
+}
+
+fn copy_descriptor(metadata: &mut [u8], block: u32, descriptor: &[u8]) {
+    let offset = block as usize * LOGIC_SIZE;
+    metadata[offset..offset + descriptor.len()].copy_from_slice(descriptor);
+}
+
+fn create_file_entry(
+    block: u32,
+    file_type: u8,
+    link_count: u16,
+    unique_id: u64,
+    size: u64,
+    extents: &[(u32, u32)],
+    recording_time: &DateTime<Utc>,
+) -> std::io::Result<Vec<u8>> {
+    let allocation_descriptors_size = extents.len() * SHORT_AD_SIZE;
+    if FILE_ENTRY_HEADER_SIZE + allocation_descriptors_size > LOGIC_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "file too big for a UDF file entry!",
+        ));
+    }
+
+    let mut descriptor = new_descriptor();
+
+    // ICB tag: strategy 4, one entry, short_ad allocation descriptors
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u16::<LittleEndian>(0x4)?;
+    descriptor.write_u16::<LittleEndian>(0x0)?;
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+    descriptor.write_u8(0x0)?;
+    descriptor.write_u8(file_type)?;
+    descriptor.write_all(&[0x0; 6])?;
+    descriptor.write_u16::<LittleEndian>(0x0)?;
+
+    // uid + gid (root)
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+
+    // Permissions: r-xr-xr-x for directories, r--r--r-- for files
+    let permissions = if file_type == FILE_TYPE_DIRECTORY {
+        0x14A5
+    } else {
+        0x1084
+    };
+    descriptor.write_u32::<LittleEndian>(permissions)?;
+    descriptor.write_u16::<LittleEndian>(link_count)?;
+
+    // Record format, record display attributes and record length
+    descriptor.write_u8(0x0)?;
+    descriptor.write_u8(0x0)?;
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+
+    descriptor.write_u64::<LittleEndian>(size)?;
+    descriptor.write_u64::<LittleEndian>(size.div_ceil(LOGIC_SIZE as u64))?;
+
+    // Access, modification and attribute dates
+    for _ in 0..3 {
+        write_timestamp(&mut descriptor, recording_time)?;
+    }
+
+    // Checkpoint
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+
+    // Extended attribute ICB
+    descriptor.write_all(&[0x0; 0x10])?;
+
+    write_implementation_regid(&mut descriptor)?;
+    descriptor.write_u64::<LittleEndian>(unique_id)?;
+
+    // Length of extended attributes and of allocation descriptors
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u32::<LittleEndian>(allocation_descriptors_size as u32)?;
+
+    for (length, block) in extents {
+        descriptor.write_u32::<LittleEndian>(*length)?;
+        descriptor.write_u32::<LittleEndian>(*block)?;
+    }
+
+    write_tag(&mut descriptor, FILE_ENTRY, block);
+
+    Ok(descriptor)
+}
+
+fn write_file_identifier(
+    buffer: &mut Vec<u8>,
+    first_block: u32,
+    characteristics: u8,
+    name: &str,
+    icb_block: u32,
+    unique_id: u64,
+) -> std::io::Result<()> {
+    let identifier = encode_name(name, 0xFF);
+    let block = first_block + buffer.len() as u32 / LOGIC_SIZE_U32;
+
+    let mut descriptor = new_descriptor();
+
+    // File version number
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+    descriptor.write_u8(characteristics)?;
+    descriptor.write_u8(identifier.len() as u8)?;
+    write_long_ad(&mut descriptor, LOGIC_SIZE_U32, icb_block, unique_id)?;
+
+    // Length of implementation use
+    descriptor.write_u16::<LittleEndian>(0x0)?;
+    descriptor.write_all(&identifier)?;
+
+    descriptor.resize(get_file_identifier_size(name) as usize, 0x0);
+
+    write_tag(&mut descriptor, FILE_IDENTIFIER_DESCRIPTOR, block);
+    buffer.write_all(&descriptor)?;
+
+    Ok(())
+}
+
+fn write_directory(
+    metadata: &mut [u8],
+    directory: &UdfDirectory<'_>,
+    parent: (u32, u64),
+    partition_lba: u32,
+    recording_time: &DateTime<Utc>,
+) -> std::io::Result<()> {
+    // Parent directory, the root being its own parent
+    let mut identifiers: Vec<u8> = Vec::new();
+    write_file_identifier(
+        &mut identifiers,
+        directory.fid_block,
+        0xA,
+        "",
+        parent.0,
+        parent.1,
+    )?;
+
+    for child in &directory.directories {
+        write_file_identifier(
+            &mut identifiers,
+            directory.fid_block,
+            0x2,
+            &child.entry.get_file_name(),
+            child.icb_block,
+            child.unique_id,
+        )?;
+    }
+
+    for file in &directory.files {
+        write_file_identifier(
+            &mut identifiers,
+            directory.fid_block,
+            0x0,
+            &file.entry.get_file_name(),
+            file.icb_block,
+            file.unique_id,
+        )?;
+    }
+    assert!(identifiers.len() as u32 == directory.fid_size);
+
+    copy_descriptor(metadata, directory.fid_block, &identifiers);
+
+    let descriptor = create_file_entry(
+        directory.icb_block,
+        FILE_TYPE_DIRECTORY,
+        1 + directory.directories.len() as u16,
+        directory.unique_id,
+        u64::from(directory.fid_size),
+        &[(directory.fid_size, directory.fid_block)],
+        recording_time,
+    )?;
+    copy_descriptor(metadata, directory.icb_block, &descriptor);
+
+    for file in &directory.files {
+        // The data is the extent of the ISO 9660 file, split in extents of at most 1 GiB
+        let size = file.entry.size as u64;
+        let mut extents = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let length = std::cmp::min(size - offset, MAX_EXTENT_SIZE);
+            let block = file.entry.lba - partition_lba + (offset / LOGIC_SIZE as u64) as u32;
+            extents.push((length as u32, block));
+            offset += length;
+        }
+
+        let descriptor = create_file_entry(
+            file.icb_block,
+            FILE_TYPE_REGULAR,
+            1,
+            file.unique_id,
+            size,
+            &extents,
+            recording_time,
+        )?;
+        copy_descriptor(metadata, file.icb_block, &descriptor);
+    }
+
+    for child in &directory.directories {
+        write_directory(
+            metadata,
+            child,
+            (directory.icb_block, directory.unique_id),
+            partition_lba,
+            recording_time,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_file_set_descriptor(
+    metadata: &mut [u8],
+    root: &UdfDirectory<'_>,
+    recording_time: &DateTime<Utc>,
+) -> std::io::Result<()> {
+    let mut descriptor = new_descriptor();
+    write_timestamp(&mut descriptor, recording_time)?;
+
+    // Interchange level + maximum interchange level
+    descriptor.write_u16::<LittleEndian>(0x3)?;
+    descriptor.write_u16::<LittleEndian>(0x3)?;
+
+    // Character set list + maximum character set list (CS0)
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+
+    // File set number + file set descriptor number
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+
+    write_charspec(&mut descriptor)?;
+    write_dstring(&mut descriptor, VOLUME_IDENTIFIER, 128)?;
+    write_charspec(&mut descriptor)?;
+    write_dstring(&mut descriptor, VOLUME_IDENTIFIER, 32)?;
+
+    // Copyright and abstract file identifiers
+    descriptor.write_all(&[0x0; 32])?;
+    descriptor.write_all(&[0x0; 32])?;
+
+    write_long_ad(
+        &mut descriptor,
+        LOGIC_SIZE_U32,
+        root.icb_block,
+        root.unique_id,
+    )?;
+    write_udf_regid(&mut descriptor, "*OSTA UDF Compliant")?;
+
+    // Next extent, system stream directory ICB and reserved
+    descriptor.write_all(&[0x0; 0x40])?;
+
+    write_tag(&mut descriptor, FILE_SET_DESCRIPTOR, 0);
+    copy_descriptor(metadata, 0, &descriptor);
+
+    let mut terminator = new_descriptor();
+    terminator.resize(0x200, 0x0);
+    write_tag(&mut terminator, TERMINATING_DESCRIPTOR, 1);
+    copy_descriptor(metadata, 1, &terminator);
+
+    Ok(())
+}
+
+fn write_volume_descriptor_sequence(
+    sequence: &mut Vec<u8>,
+    start_lba: u32,
+    layout: &UdfLayout,
+    volume_set_identifier: &str,
+    recording_time: &DateTime<Utc>,
+) -> std::io::Result<()> {
+    let mut descriptors: Vec<(u16, Vec<u8>)> = Vec::new();
+
+    // Primary Volume Descriptor
+    let mut descriptor = new_descriptor();
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    write_dstring(&mut descriptor, VOLUME_IDENTIFIER, 32)?;
+
+    // Volume sequence number + maximum volume sequence number
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+
+    // Interchange level + maximum interchange level
+    descriptor.write_u16::<LittleEndian>(0x2)?;
+    descriptor.write_u16::<LittleEndian>(0x2)?;
+
+    // Character set list + maximum character set list (CS0)
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+
+    write_dstring(&mut descriptor, volume_set_identifier, 128)?;
+    write_charspec(&mut descriptor)?;
+    write_charspec(&mut descriptor)?;
+
+    // Volume abstract + volume copyright notice
+    write_extent_ad(&mut descriptor, 0x0, 0x0)?;
+    write_extent_ad(&mut descriptor, 0x0, 0x0)?;
+
+    // Application identifier
+    write_regid(&mut descriptor, "", &[])?;
+    write_timestamp(&mut descriptor, recording_time)?;
+    write_implementation_regid(&mut descriptor)?;
+
+    // Implementation use, predecessor volume descriptor sequence location, flags and reserved
+    descriptor.resize(0x200, 0x0);
+    descriptors.push((PRIMARY_VOLUME_DESCRIPTOR, descriptor));
+
+    // Implementation Use Volume Descriptor (UDF 1.02 2.2.7)
+    let mut descriptor = new_descriptor();
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    write_udf_regid(&mut descriptor, "*UDF LV Info")?;
+    write_charspec(&mut descriptor)?;
+    write_dstring(&mut descriptor, VOLUME_IDENTIFIER, 128)?;
+
+    // LV info 1, 2 and 3
+    descriptor.write_all(&[0x0; 36 * 3])?;
+    write_implementation_regid(&mut descriptor)?;
+    descriptor.resize(0x200, 0x0);
+    descriptors.push((IMPLEMENTATION_USE_VOLUME_DESCRIPTOR, descriptor));
+
+    // Partition Descriptor
+    let mut descriptor = new_descriptor();
+    descriptor.write_u32::<LittleEndian>(0x2)?;
+
+    // Flags (allocated) + partition number
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+    descriptor.write_u16::<LittleEndian>(0x0)?;
+    write_regid(&mut descriptor, "+NSR02", &[])?;
+
+    // Partition contents use (no space bitmap or table, the partition is read only)
+    descriptor.write_all(&[0x0; 128])?;
+
+    // Access type (read only)
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    descriptor.write_u32::<LittleEndian>(layout.partition_lba)?;
+    descriptor.write_u32::<LittleEndian>(layout.get_partition_lb_count())?;
+    write_implementation_regid(&mut descriptor)?;
+    descriptor.resize(0x200, 0x0);
+    descriptors.push((PARTITION_DESCRIPTOR, descriptor));
+
+    // Logical Volume Descriptor
+    let mut descriptor = new_descriptor();
+    descriptor.write_u32::<LittleEndian>(0x3)?;
+    write_charspec(&mut descriptor)?;
+    write_dstring(&mut descriptor, VOLUME_IDENTIFIER, 128)?;
+    descriptor.write_u32::<LittleEndian>(LOGIC_SIZE_U32)?;
+    write_udf_regid(&mut descriptor, "*OSTA UDF Compliant")?;
+
+    // Logical volume contents use: the File Set Descriptor at the start of the partition
+    write_long_ad(&mut descriptor, LOGIC_SIZE_U32, 0x0, 0x0)?;
+
+    // Map table length + number of partition maps
+    descriptor.write_u32::<LittleEndian>(0x6)?;
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    write_implementation_regid(&mut descriptor)?;
+    descriptor.write_all(&[0x0; 128])?;
+    write_extent_ad(
+        &mut descriptor,
+        INTEGRITY_SEQUENCE_LB_COUNT * LOGIC_SIZE_U32,
+        layout.integrity_sequence_lba,
+    )?;
+
+    // Type 1 partition map of the partition 0 of this volume
+    descriptor.write_u8(0x1)?;
+    descriptor.write_u8(0x6)?;
+    descriptor.write_u16::<LittleEndian>(0x1)?;
+    descriptor.write_u16::<LittleEndian>(0x0)?;
+    descriptors.push((LOGICAL_VOLUME_DESCRIPTOR, descriptor));
+
+    // Unallocated Space Descriptor (no free space)
+    let mut descriptor = new_descriptor();
+    descriptor.write_u32::<LittleEndian>(0x4)?;
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptors.push((UNALLOCATED_SPACE_DESCRIPTOR, descriptor));
+
+    let mut descriptor = new_descriptor();
+    descriptor.resize(0x200, 0x0);
+    descriptors.push((TERMINATING_DESCRIPTOR, descriptor));
+
+    for (index, (tag_identifier, mut descriptor)) in descriptors.into_iter().enumerate() {
+        write_tag(&mut descriptor, tag_identifier, start_lba + index as u32);
+
+        // One descriptor per LB
+        descriptor.resize(LOGIC_SIZE, 0x0);
+        sequence.write_all(&descriptor)?;
+    }
+
+    Ok(())
+}
+
+fn write_integrity_sequence(
+    sequence: &mut Vec<u8>,
+    layout: &UdfLayout,
+    info: &UdfInfo,
+    recording_time: &DateTime<Utc>,
+) -> std::io::Result<()> {
+    let mut descriptor = new_descriptor();
+    write_timestamp(&mut descriptor, recording_time)?;
+
+    // Integrity type (close) + next integrity extent
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    write_extent_ad(&mut descriptor, 0x0, 0x0)?;
+
+    // Logical volume header descriptor: next Unique ID
+    descriptor.write_u64::<LittleEndian>(info.next_unique_id)?;
+    descriptor.write_all(&[0x0; 24])?;
+
+    // Number of partitions + length of implementation use
+    descriptor.write_u32::<LittleEndian>(0x1)?;
+    descriptor.write_u32::<LittleEndian>(0x2E)?;
+
+    // Free space table + size table
+    descriptor.write_u32::<LittleEndian>(0x0)?;
+    descriptor.write_u32::<LittleEndian>(layout.get_partition_lb_count())?;
+
+    // Implementation use (UDF 1.02 2.2.6.4)
+    write_implementation_regid(&mut descriptor)?;
+    descriptor.write_u32::<LittleEndian>(info.file_count)?;
+    descriptor.write_u32::<LittleEndian>(info.directory_count)?;
+
+    // Minimum UDF read revision, minimum and maximum UDF write revision
+    for _ in 0..3 {
+        descriptor.write_u16::<LittleEndian>(UDF_REVISION)?;
+    }
+
+    write_tag(
+        &mut descriptor,
+        LOGICAL_VOLUME_INTEGRITY_DESCRIPTOR,
+        layout.integrity_sequence_lba,
+    );
+    descriptor.resize(LOGIC_SIZE, 0x0);
+    sequence.write_all(&descriptor)?;
+
+    let mut terminator = new_descriptor();
+    terminator.resize(0x200, 0x0);
+    write_tag(
+        &mut terminator,
+        TERMINATING_DESCRIPTOR,
+        layout.integrity_sequence_lba + 1,
+    );
+    terminator.resize(LOGIC_SIZE, 0x0);
+    sequence.write_all(&terminator)?;
+
+    Ok(())
+}
+
+fn write_anchor<T>(output_writter: &mut T, layout: &UdfLayout, lba: u32) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let sequence_size = VOLUME_DESCRIPTOR_SEQUENCE_LB_COUNT * LOGIC_SIZE_U32;
+
+    let mut descriptor = new_descriptor();
+    write_extent_ad(&mut descriptor, sequence_size, layout.main_sequence_lba)?;
+    write_extent_ad(&mut descriptor, sequence_size, layout.reserve_sequence_lba)?;
+    descriptor.resize(0x200, 0x0);
+    write_tag(&mut descriptor, ANCHOR_VOLUME_DESCRIPTOR_POINTER, lba);
+    descriptor.resize(LOGIC_SIZE, 0x0);
+
+    output_writter.seek(SeekFrom::Start(u64::from(lba) * LOGIC_SIZE as u64))?;
+    output_writter.write_all(&descriptor)?;
+
+    Ok(())
+}
+
+/// Write the Volume Recognition Sequence announcing the UDF volume, right after the ISO 9660 volume descriptors.
+pub fn write_volume_recognition_sequence<T>(output_writter: &mut T) -> std::io::Result<()>
+where
+    T: Write,
+{
+    for identifier in [b"BEA01", b"NSR02", b"TEA01"] {
+        output_writter.write_u8(0x0)?;
+        output_writter.write_all(identifier)?;
+        output_writter.write_u8(0x1)?;
+
+        let empty_data: [u8; 2041] = [0; 2041];
+        output_writter.write_all(&empty_data)?;
+    }
+
+    Ok(())
+}
+
+/// Write the UDF volume and file structures, the UDF files sharing the extents of the ISO 9660 files.
+pub fn write_udf<T>(
+    output_writter: &mut T,
+    tree: &DirectoryEntry,
+    layout: &UdfLayout,
+) -> std::io::Result<()>
+where
+    T: Write + Seek,
+{
+    let old_pos = output_writter.stream_position()?;
+
+    let recording_time: DateTime<Utc> = Utc::now();
+
+    // The first 16 characters of the volume set identifier should be unique (UDF 1.02 2.2.2.5)
+    let volume_set_identifier =
+        format!("{:016X}{}", utils::generate_random_u64(), VOLUME_IDENTIFIER);
+
+    let (root, info) = create_tree(tree);
+    assert!(info.next_block == layout.metadata_lb_count);
+
+    for start_lba in [layout.main_sequence_lba, layout.reserve_sequence_lba] {
+        let mut sequence: Vec<u8> = Vec::new();
+        write_volume_descriptor_sequence(
+            &mut sequence,
+            start_lba,
+            layout,
+            &volume_set_identifier,
+            &recording_time,
+        )?;
+
+        output_writter.seek(SeekFrom::Start(u64::from(start_lba) * LOGIC_SIZE as u64))?;
+        output_writter.write_all(&sequence)?;
+    }
+
+    let mut sequence: Vec<u8> = Vec::new();
+    write_integrity_sequence(&mut sequence, layout, &info, &recording_time)?;
+    output_writter.seek(SeekFrom::Start(
+        u64::from(layout.integrity_sequence_lba) * LOGIC_SIZE as u64,
+    ))?;
+    output_writter.write_all(&sequence)?;
+
+    write_anchor(output_writter, layout, ANCHOR_LBA)?;
+    write_anchor(output_writter, layout, layout.last_anchor_lba)?;
+
+    // File Set Descriptor, directories and file entries at the start of the partition
+    let mut metadata = vec![0u8; layout.metadata_lb_count as usize * LOGIC_SIZE];
+    write_file_set_descriptor(&mut metadata, &root, &recording_time)?;
+    write_directory(
+        &mut metadata,
+        &root,
+        (root.icb_block, root.unique_id),
+        layout.partition_lba,
+        &recording_time,
+    )?;
+
+    output_writter.seek(SeekFrom::Start(
+        u64::from(layout.partition_lba) * LOGIC_SIZE as u64,
+    ))?;
+    output_writter.write_all(&metadata)?;
+
+    output_writter.seek(SeekFrom::Start(old_pos))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::iso;
+    use crate::iso::option::Opt;
+
+    use byteorder::ByteOrder;
+    use structopt::StructOpt;
+
+    use std::fs;
+
+    /// Check the checksum, CRC and location of a descriptor tag, returning its tag identifier.
+    fn check_tag(descriptor: &[u8], location: u32) -> u16 {
+        let checksum = descriptor[..0x10]
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != 0x4)
+            .fold(0u8, |sum, (_, byte)| sum.wrapping_add(*byte));
+        assert_eq!(descriptor[0x4], checksum);
+
+        let crc_length = usize::from(LittleEndian::read_u16(&descriptor[0xA..]));
+        assert_eq!(
+            LittleEndian::read_u16(&descriptor[0x8..]),
+            crc_itu_t(&descriptor[0x10..0x10 + crc_length])
+        );
+        assert_eq!(
+            LittleEndian::read_u16(&descriptor[0x2..]),
+            DESCRIPTOR_VERSION
+        );
+        assert_eq!(LittleEndian::read_u32(&descriptor[0xC..]), location);
+
+        LittleEndian::read_u16(&descriptor[0x0..])
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc_itu_t(b""), 0x0);
+        assert_eq!(crc_itu_t(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn tags() {
+        let mut descriptor = new_descriptor();
+        descriptor.extend_from_slice(b"some descriptor content");
+        write_tag(&mut descriptor, FILE_ENTRY, 0x1234);
+
+        assert_eq!(check_tag(&descriptor, 0x1234), FILE_ENTRY);
+        assert_eq!(
+            usize::from(LittleEndian::read_u16(&descriptor[0xA..])),
+            descriptor.len() - 0x10
+        );
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(encode_name("", 0x20), b"");
+        assert_eq!(encode_name("abc", 0x20), b"\x08abc");
+        assert_eq!(encode_name("é", 0x20), b"\x08\xE9");
+        assert_eq!(encode_name("ω", 0x20), b"\x10\x03\xC9");
+        assert_eq!(encode_name("abcdef", 4), b"\x08abc");
+        assert_eq!(encode_name("ωωω", 6), b"\x10\x03\xC9\x03\xC9");
+    }
+
+    #[test]
+    fn volume_descriptors() {
+        let test_directory = utils::create_test_directory("udf");
+        let source_directory = test_directory.join("source");
+        let image = test_directory.join("test.iso");
+        fs::create_dir_all(source_directory.join("directory")).unwrap();
+        fs::write(source_directory.join("directory/file.txt"), b"content").unwrap();
+
+        let mut opt = Opt::from_iter(vec![
+            "mkisofs-rs",
+            "-o",
+            image.to_str().unwrap(),
+            "--udf",
+            source_directory.to_str().unwrap(),
+        ]);
+        iso::create_iso(&mut opt).unwrap();
+
+        let data = fs::read(&image).unwrap();
+        let get_block =
+            |lba: u32| &data[lba as usize * LOGIC_SIZE..(lba as usize + 1) * LOGIC_SIZE];
+
+        // The Volume Recognition Sequence follows the ISO 9660 volume descriptors
+        let bea_lba = (0x10..ANCHOR_LBA)
+            .find(|lba| &get_block(*lba)[0x1..0x6] == b"BEA01")
+            .unwrap();
+        assert_eq!(get_block(bea_lba - 1)[0x0], 0xFF);
+        assert_eq!(&get_block(bea_lba + 1)[0x1..0x6], b"NSR02");
+        assert_eq!(&get_block(bea_lba + 2)[0x1..0x6], b"TEA01");
+
+        let anchor = get_block(ANCHOR_LBA);
+        assert_eq!(
+            check_tag(anchor, ANCHOR_LBA),
+            ANCHOR_VOLUME_DESCRIPTOR_POINTER
+        );
+
+        // Main then reserve Volume Descriptor Sequences
+        for extent in [&anchor[0x10..0x18], &anchor[0x18..0x20]] {
+            let sequence_lba = LittleEndian::read_u32(&extent[0x4..]);
+            assert_eq!(
+                LittleEndian::read_u32(&extent[0x0..]),
+                VOLUME_DESCRIPTOR_SEQUENCE_LB_COUNT * LOGIC_SIZE_U32
+            );
+
+            let tag_identifiers: Vec<u16> = (sequence_lba..)
+                .map(|lba| check_tag(get_block(lba), lba))
+                .take_while(|tag_identifier| *tag_identifier != TERMINATING_DESCRIPTOR)
+                .collect();
+            assert_eq!(
+                tag_identifiers,
+                [
+                    PRIMARY_VOLUME_DESCRIPTOR,
+                    IMPLEMENTATION_USE_VOLUME_DESCRIPTOR,
+                    PARTITION_DESCRIPTOR,
+                    LOGICAL_VOLUME_DESCRIPTOR,
+                    UNALLOCATED_SPACE_DESCRIPTOR
+                ]
+            );
+        }
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+}
@@ -1,5 +1,9 @@
 use byteorder::WriteBytesExt;
-use std::io::Write;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Seek, SeekFrom, Write};
+use std::time::SystemTime;
 
 pub const LOGIC_SIZE: usize = 0x800;
 pub const LOGIC_SIZE_I64: i64 = 0x800;
@@ -11,6 +15,14 @@ pub fn align_up(value: i32, padding: i32) -> i32 {
     (value + (padding - 1)) & -padding
 }
 
+pub fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 pub fn convert_name(value: &str) -> Vec<u8> {
     let res: Vec<&str> = value.split('.').collect();
 
@@ -36,6 +48,73 @@ pub fn convert_name(value: &str) -> Vec<u8> {
     result.into_bytes()
 }
 
+/// Get the count of 512-byte sectors covered by the given count of LB.
+pub fn get_sector_count(lb_count: u32) -> u64 {
+    u64::from(lb_count) * LOGIC_SIZE as u64 / u64::from(SECTOR_SIZE)
+}
+
+/// Make sure a count or address of 512-byte sectors fits in a 32-bit field of the given disk label.
+pub fn check_sector_field(sector_count: u64, label_name: &str) -> std::io::Result<u32> {
+    u32::try_from(sector_count).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "the image is too big to be described by the {}!",
+                label_name
+            ),
+        )
+    })
+}
+
+/// Get the count of 512-byte sectors covered by the given count of LB, to be stored in a 32-bit field of a disk label.
+pub fn get_sector_field(lb_count: u32, label_name: &str) -> std::io::Result<u32> {
+    check_sector_field(get_sector_count(lb_count), label_name)
+}
+
+/// Compare two file identifiers (ISO 9660 bytes or Joliet UCS-2 code units) in the order of their directory records.
+///
+/// The names then the extensions are compared as if padded with spaces, the versions in descending order (ECMA-119 9.3).
+pub fn compare_identifiers<T>(a: &[T], b: &[T]) -> Ordering
+where
+    T: Copy + Ord + From<u8> + Into<u32>,
+{
+    let split_identifier = |identifier: &[T]| -> (Vec<T>, Vec<T>, u32) {
+        let mut parts = identifier.splitn(2, |unit| *unit == T::from(b';'));
+        let name_and_extension = parts.next().unwrap_or_default();
+        let version = parts
+            .next()
+            .unwrap_or_default()
+            .iter()
+            .fold(0u32, |acc, unit| {
+                acc.saturating_mul(10)
+                    .saturating_add((*unit).into().wrapping_sub(u32::from(b'0')))
+            });
+
+        let mut parts = name_and_extension.splitn(2, |unit| *unit == T::from(b'.'));
+        let name = parts.next().unwrap_or_default().to_vec();
+        let extension = parts.next().unwrap_or_default().to_vec();
+
+        (name, extension, version)
+    };
+
+    let compare_padded = |a: &[T], b: &[T]| -> Ordering {
+        let get_unit =
+            |value: &[T], index: usize| value.get(index).copied().unwrap_or(T::from(b' '));
+
+        (0..std::cmp::max(a.len(), b.len()))
+            .map(|index| get_unit(a, index).cmp(&get_unit(b, index)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    };
+
+    let (a_name, a_extension, a_version) = split_identifier(a);
+    let (b_name, b_extension, b_version) = split_identifier(b);
+
+    compare_padded(&a_name, &b_name)
+        .then_with(|| compare_padded(&a_extension, &b_extension))
+        .then_with(|| b_version.cmp(&a_version))
+}
+
 pub fn get_entry_size(
     base_size: u32,
     file_name: &str,
@@ -117,9 +196,233 @@ where
     Ok(())
 }
 
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+pub fn generate_random_u64() -> u64 {
+    // RandomState is seeded randomly for every instance, mix it with the current time to be safe
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(duration) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(duration.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// Create an empty directory holding the files of a test.
+#[cfg(test)]
+pub fn create_test_directory(name: &str) -> std::path::PathBuf {
+    let res = std::env::temp_dir().join(format!(
+        "mkisofs-rs-test-{}-{:016x}",
+        name,
+        generate_random_u64()
+    ));
+    std::fs::create_dir_all(&res).unwrap();
+    res
+}
+
+pub fn generate_random_u32() -> u32 {
+    generate_random_u64() as u32
+}
+
+/// Generate a random (version 4) GUID in its on-disk representation.
+pub fn generate_guid() -> [u8; 16] {
+    let mut guid: [u8; 16] = [0x0; 16];
+    guid[..8].copy_from_slice(&generate_random_u64().to_le_bytes());
+    guid[8..].copy_from_slice(&generate_random_u64().to_le_bytes());
+
+    // Version 4 (stored in the little endian third field)
+    guid[7] = (guid[7] & 0x0F) | 0x40;
+
+    // Variant 1
+    guid[8] = (guid[8] & 0x3F) | 0x80;
+
+    guid
+}
+
+/// Convert a GUID string ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B") to its mixed endian on-disk representation.
+pub fn parse_guid(value: &str) -> Option<[u8; 16]> {
+    let hex: String = value.chars().filter(|c| *c != '-').collect();
+    if hex.len() != 32 {
+        return None;
+    }
+
+    let mut raw: [u8; 16] = [0x0; 16];
+    for (i, byte) in raw.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    let mut guid = raw;
+    guid[0..4].reverse();
+    guid[4..6].reverse();
+    guid[6..8].reverse();
+
+    Some(guid)
+}
+
+/// Convert a GUID in its mixed endian on-disk representation to a string ("C12A7328-F81F-11D2-BA4B-00A0C93EC93B").
+pub fn format_guid(guid: &[u8; 16]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:04X}-{:012X}",
+        u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        u16::from_be_bytes([guid[8], guid[9]]),
+        guid[10..]
+            .iter()
+            .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+    )
+}
+
+/// Writer translating positions so that its position 0 is located at `origin` in the inner writer.
+pub struct OffsetWriter<'a, T> {
+    inner: &'a mut T,
+    origin: i64,
+}
+
+impl<'a, T> OffsetWriter<'a, T>
+where
+    T: Write + Seek,
+{
+    pub fn new(inner: &'a mut T, origin: i64) -> Self {
+        OffsetWriter { inner, origin }
+    }
+}
+
+impl<T> Write for OffsetWriter<'_, T>
+where
+    T: Write + Seek,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T> Seek for OffsetWriter<'_, T>
+where
+    T: Write + Seek,
+{
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let inner_pos = match pos {
+            SeekFrom::Start(offset) => {
+                let inner_offset = offset as i64 + self.origin;
+                if inner_offset < 0 {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "invalid seek before the origin of the writer",
+                    ));
+                }
+                self.inner.seek(SeekFrom::Start(inner_offset as u64))?
+            }
+            _ => self.inner.seek(pos)?,
+        };
+
+        Ok((inner_pos as i64 - self.origin) as u64)
+    }
+}
+
 macro_rules! write_bothendian {
     ($($writer:ident . $write_fn:ident($value:expr)?;)*) => {
         $($writer.$write_fn::<LittleEndian>($value)?;)*
         $($writer.$write_fn::<BigEndian>($value)?;)*
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_guids() {
+        assert_eq!(
+            parse_guid("C12A7328-F81F-11D2-BA4B-00A0C93EC93B"),
+            Some([
+                0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E,
+                0xC9, 0x3B
+            ])
+        );
+        assert_eq!(
+            parse_guid("c12a7328f81f11d2ba4b00a0c93ec93b"),
+            parse_guid("C12A7328-F81F-11D2-BA4B-00A0C93EC93B")
+        );
+
+        for value in [
+            "",
+            "C12A7328-F81F-11D2-BA4B-00A0C93EC93",
+            "G12A7328-F81F-11D2-BA4B-00A0C93EC93B",
+        ] {
+            assert_eq!(parse_guid(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn random_guids() {
+        let guid = generate_guid();
+        assert_eq!(guid[7] & 0xF0, 0x40);
+        assert_eq!(guid[8] & 0xC0, 0x80);
+    }
+
+    #[test]
+    fn format_guids() {
+        for value in [
+            "C12A7328-F81F-11D2-BA4B-00A0C93EC93B",
+            "EBD0A0A2-B9E5-4433-87C0-68B6B72699C7",
+            "00000000-0000-0000-0000-000000000000",
+        ] {
+            assert_eq!(format_guid(&parse_guid(value).unwrap()), value);
+        }
+
+        let guid = generate_guid();
+        assert_eq!(parse_guid(&format_guid(&guid)), Some(guid));
+    }
+
+    #[test]
+    fn identifier_order() {
+        let ordered_identifiers: [&[u8]; 7] = [
+            b"\0",
+            b"\x01",
+            b"A.TXT;1",
+            b"A-B.TXT;1",
+            b"AB;2",
+            b"AB;1",
+            b"AB.C;1",
+        ];
+
+        for (index, a) in ordered_identifiers.iter().enumerate() {
+            for (other_index, b) in ordered_identifiers.iter().enumerate() {
+                assert_eq!(
+                    compare_identifiers(a, b),
+                    index.cmp(&other_index),
+                    "{:?} {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+
+        // Joliet identifiers are compared by UCS-2 code units
+        let to_ucs2 = |value: &str| value.encode_utf16().collect::<Vec<u16>>();
+        assert_eq!(
+            compare_identifiers(&to_ucs2("a.txt;10"), &to_ucs2("a.txt;9")),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_identifiers(&to_ucs2("é.txt;1"), &to_ucs2("z.txt;1")),
+            Ordering::Greater
+        );
+    }
+}
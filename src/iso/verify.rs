@@ -0,0 +1,908 @@
+use crate::iso::option::VerifyOpt;
+use crate::iso::reader::{DirectoryRecord, GptInfo, IsoReader, Namespace, VolumeInfo};
+use crate::iso::utils;
+use crate::iso::utils::{LOGIC_SIZE, LOGIC_SIZE_U32, SECTOR_SIZE};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+
+/// Maximum number of SUSP continuation areas followed for a single directory record.
+const MAX_CONTINUATION_AREAS: usize = 64;
+
+/// A directory found while walking the tree, used to check the path table.
+struct DirectoryInfo {
+    lba: u32,
+    parent_lba: u32,
+    identifier: Vec<u8>,
+}
+
+/// An entry of a path table (ECMA-119 9.4).
+#[derive(PartialEq, Eq)]
+struct PathTableRecord {
+    lba: u32,
+    parent_index: u16,
+    identifier: Vec<u8>,
+}
+
+fn parse_path_table<Order: ByteOrder>(data: &[u8]) -> Option<Vec<PathTableRecord>> {
+    let mut res = Vec::new();
+
+    let mut offset = 0;
+    while offset < data.len() {
+        let identifier_len = usize::from(data[offset]);
+        if identifier_len == 0 || offset + 8 + identifier_len > data.len() {
+            return None;
+        }
+
+        res.push(PathTableRecord {
+            lba: Order::read_u32(&data[offset + 0x2..]),
+            parent_index: Order::read_u16(&data[offset + 0x6..]),
+            identifier: data[offset + 0x8..offset + 0x8 + identifier_len].to_vec(),
+        });
+
+        // Identifiers are padded to an even length
+        offset += 8 + identifier_len + identifier_len % 2;
+    }
+
+    Some(res)
+}
+
+/// Get the expected length of a SUSP or RRIP entry, `None` if it has a variable length.
+fn get_susp_entry_len(signature: &[u8; 2], data: &[u8]) -> Option<usize> {
+    match signature {
+        b"SP" => Some(7),
+        b"CE" => Some(28),
+        b"ST" | b"RE" => Some(4),
+        b"PN" => Some(20),
+        b"CL" | b"PL" => Some(12),
+        b"SF" => Some(21),
+        b"RR" | b"ES" => Some(5),
+        b"TF" if !data.is_empty() => {
+            let flags = data[0];
+            let timestamp_size = if flags & 0x80 != 0 { 17 } else { 7 };
+            Some(5 + (flags & 0x7F).count_ones() as usize * timestamp_size)
+        }
+        b"ER" if data.len() >= 4 => {
+            Some(8 + usize::from(data[0]) + usize::from(data[1]) + usize::from(data[2]))
+        }
+        _ => None,
+    }
+}
+
+/// Check the components of a SL entry, they must fill the entry exactly.
+fn check_symlink_components(data: &[u8]) -> bool {
+    let mut offset = 1;
+    while offset + 2 <= data.len() {
+        offset += 2 + usize::from(data[offset + 1]);
+    }
+
+    offset == data.len()
+}
+
+struct Verifier<R> {
+    reader: IsoReader<R>,
+    volume_space_size: u32,
+    violations: Vec<(String, String)>,
+}
+
+impl<R> Verifier<R>
+where
+    R: io::Read + io::Seek,
+{
+    fn report(&mut self, location: String, message: String) {
+        self.violations.push((location, message));
+    }
+
+    fn is_extent_in_volume(&self, lba: u32, size: u32) -> bool {
+        u64::from(lba) + u64::from(size.div_ceil(LOGIC_SIZE_U32))
+            <= u64::from(self.volume_space_size)
+    }
+
+    fn verify_volume_descriptors(&mut self) -> io::Result<()> {
+        let image_size = self.reader.get_image_size()?;
+
+        let primary = match self.reader.get_volume(Namespace::Iso) {
+            Some(primary) => primary.clone(),
+            None => {
+                self.report(
+                    String::from("volume descriptor set"),
+                    String::from("no Primary Volume Descriptor"),
+                );
+                return Ok(());
+            }
+        };
+
+        if primary.logical_block_size as usize != LOGIC_SIZE {
+            self.report(
+                String::from("Primary Volume Descriptor"),
+                format!(
+                    "logical block size is {} instead of {}",
+                    primary.logical_block_size, LOGIC_SIZE
+                ),
+            );
+        }
+
+        if u64::from(primary.volume_space_size) * LOGIC_SIZE as u64 > image_size {
+            self.report(
+                String::from("Primary Volume Descriptor"),
+                format!(
+                    "volume space size of {} blocks exceeds the image size of {} bytes",
+                    primary.volume_space_size, image_size
+                ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check the System Use field of a directory record, following its continuation areas.
+    fn verify_system_use(
+        &mut self,
+        record: &DirectoryRecord,
+        skip: usize,
+        location: &str,
+        is_root: bool,
+    ) -> io::Result<()> {
+        let mut area = record.system_use.get(skip..).unwrap_or(&[]).to_vec();
+        let mut area_location = String::from(location);
+
+        if is_root && !area.starts_with(b"SP") {
+            self.report(
+                area_location.clone(),
+                String::from("root directory \".\" record doesn't start with a SP entry"),
+            );
+        }
+
+        for _ in 0..MAX_CONTINUATION_AREAS {
+            let mut continuation = None;
+
+            let mut offset = 0;
+            while offset + 4 <= area.len() {
+                let signature = [area[offset], area[offset + 1]];
+                let entry_len = usize::from(area[offset + 2]);
+
+                // Padding at the end of the System Use field
+                if signature[0] == 0x0 {
+                    break;
+                }
+
+                if entry_len < 4 || offset + entry_len > area.len() {
+                    self.report(
+                        format!("{} offset {}", area_location, offset),
+                        format!(
+                            "SUSP entry \"{}\" has a length of {} bytes overflowing its area",
+                            String::from_utf8_lossy(&signature),
+                            entry_len
+                        ),
+                    );
+                    break;
+                }
+
+                let data = &area[offset + 4..offset + entry_len];
+                let entry_location = format!(
+                    "{} entry \"{}\" at offset {}",
+                    area_location,
+                    String::from_utf8_lossy(&signature),
+                    offset
+                );
+
+                let is_valid = match &signature {
+                    b"PX" => entry_len == 36 || entry_len == 44,
+                    b"NM" | b"SL" => entry_len >= 5,
+                    _ => get_susp_entry_len(&signature, data).is_none_or(|len| len == entry_len),
+                };
+                if !is_valid {
+                    self.report(
+                        entry_location.clone(),
+                        format!("invalid length {}", entry_len),
+                    );
+                }
+
+                if &signature == b"SL" && entry_len >= 5 && !check_symlink_components(data) {
+                    self.report(
+                        entry_location.clone(),
+                        String::from("symbolic link components don't fill the entry"),
+                    );
+                }
+
+                if &signature == b"SP"
+                    && (!is_root || offset != 0 || data.get(0..2) != Some(&[0xBE, 0xEF]))
+                {
+                    self.report(
+                        entry_location.clone(),
+                        String::from(
+                            "SP entry must only be at the start of the root directory \".\" record",
+                        ),
+                    );
+                }
+
+                if &signature == b"CE" && is_valid {
+                    let lba = LittleEndian::read_u32(&data[0x0..]);
+                    let area_offset = LittleEndian::read_u32(&data[0x8..]);
+                    let size = LittleEndian::read_u32(&data[0x10..]);
+
+                    if !self.is_extent_in_volume(lba, area_offset.saturating_add(size)) {
+                        self.report(
+                            entry_location.clone(),
+                            format!("continuation area at LBA {} is outside of the volume", lba),
+                        );
+                    } else {
+                        continuation = Some((lba, area_offset, size));
+                    }
+                }
+
+                if &signature == b"ST" {
+                    break;
+                }
+
+                offset += entry_len;
+            }
+
+            match continuation {
+                Some((lba, area_offset, size)) => {
+                    area = self.reader.read_data(
+                        u64::from(lba) * LOGIC_SIZE as u64 + u64::from(area_offset),
+                        size as usize,
+                    )?;
+                    area_location = format!("{} (continuation area at LBA {})", location, lba);
+                }
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Walk a directory tree and check its records, returning the directories found.
+    fn verify_directory_tree(
+        &mut self,
+        root: &DirectoryRecord,
+        namespace: Namespace,
+        tree_name: &str,
+        susp_skip: Option<usize>,
+    ) -> io::Result<Vec<DirectoryInfo>> {
+        let mut res = Vec::new();
+        let mut visited = HashSet::new();
+
+        let mut queue = VecDeque::new();
+        queue.push_back((root.lba, root.size, root.lba, vec![0x0], String::from("/")));
+
+        while let Some((lba, size, parent_lba, identifier, path)) = queue.pop_front() {
+            let location = format!("{} directory {} (LBA {})", tree_name, path, lba);
+
+            if !visited.insert(lba) {
+                self.report(
+                    location,
+                    String::from("directory is referenced more than once"),
+                );
+                continue;
+            }
+
+            if !self.is_extent_in_volume(lba, size) {
+                self.report(
+                    location,
+                    String::from("directory extent is outside of the volume"),
+                );
+                continue;
+            }
+
+            res.push(DirectoryInfo {
+                lba,
+                parent_lba,
+                identifier,
+            });
+
+            let data = self
+                .reader
+                .read_blocks(lba, size.div_ceil(LOGIC_SIZE_U32))?;
+            let data = &data[..size as usize];
+
+            let mut index = 0;
+            let mut offset = 0;
+            let mut previous_identifier: Option<Vec<u16>> = None;
+            while offset < data.len() {
+                let record_location = format!("{} offset {}", location, offset);
+
+                // The rest of the logical block is unused
+                if data[offset] == 0x0 {
+                    offset = (offset / LOGIC_SIZE + 1) * LOGIC_SIZE;
+                    continue;
+                }
+
+                let record_len = usize::from(data[offset]);
+                let block_end = (offset / LOGIC_SIZE + 1) * LOGIC_SIZE;
+                if offset + record_len > block_end {
+                    self.report(
+                        record_location,
+                        String::from("directory record crosses a logical block boundary"),
+                    );
+                    offset = block_end;
+                    continue;
+                }
+
+                let record = match DirectoryRecord::parse(&data[offset..block_end.min(data.len())])
+                {
+                    Some((record, _)) => record,
+                    None => {
+                        self.report(record_location, String::from("invalid directory record"));
+                        offset = block_end;
+                        continue;
+                    }
+                };
+                offset += record_len;
+
+                let is_directory = record.flags & 0x2 != 0;
+                match index {
+                    0 => {
+                        if record.identifier != [0x0] {
+                            self.report(
+                                record_location.clone(),
+                                String::from("first record isn't \".\""),
+                            );
+                        } else if record.lba != lba || record.size != size {
+                            self.report(
+                                record_location.clone(),
+                                format!(
+                                    "\".\" points to LBA {} ({} bytes) instead of LBA {} ({} bytes)",
+                                    record.lba, record.size, lba, size
+                                ),
+                            );
+                        }
+                    }
+                    1 => {
+                        if record.identifier != [0x1] {
+                            self.report(
+                                record_location.clone(),
+                                String::from("second record isn't \"..\""),
+                            );
+                        } else if record.lba != parent_lba {
+                            self.report(
+                                record_location.clone(),
+                                format!(
+                                    "\"..\" points to LBA {} instead of LBA {}",
+                                    record.lba, parent_lba
+                                ),
+                            );
+                        }
+                    }
+                    _ => {
+                        if record.identifier == [0x0] || record.identifier == [0x1] {
+                            self.report(
+                                record_location.clone(),
+                                String::from(
+                                    "\".\" or \"..\" record after the start of the directory",
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                if index < 2 && !is_directory {
+                    self.report(
+                        record_location.clone(),
+                        String::from("\".\" or \"..\" record without the directory flag"),
+                    );
+                }
+
+                if record.size != 0 && !self.is_extent_in_volume(record.lba, record.size) {
+                    self.report(
+                        record_location.clone(),
+                        format!(
+                            "extent at LBA {} ({} bytes) is outside of the volume",
+                            record.lba, record.size
+                        ),
+                    );
+                }
+
+                if let Some(skip) = susp_skip {
+                    let is_root = index == 0 && path == "/";
+                    self.verify_system_use(
+                        &record,
+                        if is_root { 0 } else { skip },
+                        &record_location,
+                        is_root,
+                    )?;
+                }
+
+                if index >= 2 {
+                    // Joliet identifiers are UCS-2 big endian
+                    let identifier: Vec<u16> = match namespace {
+                        Namespace::Joliet => record
+                            .identifier
+                            .chunks_exact(2)
+                            .map(BigEndian::read_u16)
+                            .collect(),
+                        _ => record
+                            .identifier
+                            .iter()
+                            .map(|byte| u16::from(*byte))
+                            .collect(),
+                    };
+
+                    if let Some(previous_identifier) = &previous_identifier {
+                        if utils::compare_identifiers(previous_identifier, &identifier)
+                            == Ordering::Greater
+                        {
+                            self.report(
+                                record_location.clone(),
+                                format!(
+                                    "record \"{}\" is sorted after \"{}\" (ECMA-119 9.3)",
+                                    String::from_utf16_lossy(&identifier),
+                                    String::from_utf16_lossy(previous_identifier)
+                                ),
+                            );
+                        }
+                    }
+
+                    previous_identifier = Some(identifier);
+                }
+
+                if index >= 2 && is_directory {
+                    let name = String::from_utf8_lossy(&record.identifier);
+                    queue.push_back((
+                        record.lba,
+                        record.size,
+                        lba,
+                        record.identifier.clone(),
+                        format!("{}{}/", path, name),
+                    ));
+                }
+
+                index += 1;
+            }
+
+            if index < 2 {
+                self.report(
+                    location,
+                    String::from("directory doesn't have \".\" and \"..\" records"),
+                );
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Check that the path tables describe the same hierarchy as the directory records.
+    fn verify_path_tables(
+        &mut self,
+        volume: &VolumeInfo,
+        directories: &[DirectoryInfo],
+        tree_name: &str,
+    ) -> io::Result<()> {
+        let size = volume.path_table_size;
+        let mut tables = Vec::new();
+
+        for (name, lba, is_little_endian) in [
+            ("type L", volume.l_path_table_lba, true),
+            ("type M", volume.m_path_table_lba, false),
+        ] {
+            let location = format!("{} {} path table (LBA {})", tree_name, name, lba);
+            if !self.is_extent_in_volume(lba, size) {
+                self.report(
+                    location,
+                    String::from("path table is outside of the volume"),
+                );
+                continue;
+            }
+
+            let data = self
+                .reader
+                .read_data(u64::from(lba) * LOGIC_SIZE as u64, size as usize)?;
+            let table = if is_little_endian {
+                parse_path_table::<LittleEndian>(&data)
+            } else {
+                parse_path_table::<BigEndian>(&data)
+            };
+
+            match table {
+                Some(table) => tables.push((location, table)),
+                None => self.report(location, String::from("invalid path table record")),
+            }
+        }
+
+        if tables.len() == 2 && tables[0].1 != tables[1].1 {
+            self.report(
+                format!("{} path tables", tree_name),
+                String::from("type L and type M path tables differ"),
+            );
+        }
+
+        let (location, table) = match tables.first() {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        let location = location.clone();
+
+        if table.len() != directories.len() {
+            self.report(
+                location.clone(),
+                format!(
+                    "{} records for {} directories",
+                    table.len(),
+                    directories.len()
+                ),
+            );
+        }
+
+        let expected: HashSet<(u32, u32, &[u8])> = directories
+            .iter()
+            .map(|directory| {
+                (
+                    directory.lba,
+                    directory.parent_lba,
+                    &directory.identifier[..],
+                )
+            })
+            .collect();
+
+        let mut previous_parent_index = 1;
+        let mut violations = Vec::new();
+        for (index, record) in table.iter().enumerate() {
+            let record_location = format!("{} record {}", location, index + 1);
+
+            // Records are sorted by level then by parent, so parents come first
+            let parent_index = usize::from(record.parent_index);
+            if parent_index == 0
+                || parent_index > index + 1
+                || record.parent_index < previous_parent_index
+            {
+                violations.push((
+                    record_location,
+                    format!(
+                        "parent directory number {} is out of order",
+                        record.parent_index
+                    ),
+                ));
+                continue;
+            }
+            previous_parent_index = record.parent_index;
+
+            if index == 0 && (record.identifier != [0x0] || record.parent_index != 1) {
+                violations.push((
+                    record_location,
+                    String::from("first record isn't the root directory"),
+                ));
+                continue;
+            }
+
+            let parent_lba = table[parent_index - 1].lba;
+            if !expected.contains(&(record.lba, parent_lba, &record.identifier[..])) {
+                violations.push((
+                    record_location,
+                    format!(
+                        "directory \"{}\" at LBA {} isn't in the directory hierarchy",
+                        String::from_utf8_lossy(&record.identifier),
+                        record.lba
+                    ),
+                ));
+            }
+        }
+
+        for (location, message) in violations {
+            self.report(location, message);
+        }
+
+        Ok(())
+    }
+
+    fn verify_tree(&mut self, namespace: Namespace, tree_name: &str) -> io::Result<()> {
+        let volume = match self.reader.get_volume(namespace) {
+            Some(volume) => volume.clone(),
+            None => return Ok(()),
+        };
+
+        let susp_skip = match namespace {
+            Namespace::Iso => self.reader.get_susp_skip(),
+            _ => None,
+        };
+
+        let directories =
+            self.verify_directory_tree(&volume.root_directory, namespace, tree_name, susp_skip)?;
+        self.verify_path_tables(&volume, &directories, tree_name)
+    }
+
+    fn verify_boot_catalog(&mut self) -> io::Result<()> {
+        let catalog = match self.reader.read_boot_catalog() {
+            Ok(Some(catalog)) => catalog,
+            Ok(None) => return Ok(()),
+            Err(error) => {
+                self.report(String::from("El Torito boot catalog"), error.to_string());
+                return Ok(());
+            }
+        };
+
+        let location = format!("El Torito boot catalog (LBA {})", catalog.lba);
+        if !catalog.checksum_valid {
+            self.report(
+                location.clone(),
+                String::from("invalid validation entry checksum"),
+            );
+        }
+
+        for (index, entry) in catalog.entries.iter().enumerate() {
+            // Virtual sectors are 512 bytes
+            let size = u32::from(entry.sector_count) * SECTOR_SIZE;
+            if !self.is_extent_in_volume(entry.lba, size) {
+                self.report(
+                    format!("{} entry {}", location, index),
+                    format!("boot image at LBA {} is outside of the volume", entry.lba),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify_gpt_header(&mut self, gpt_info: &GptInfo, location: &str, sector_count: u64) {
+        if !gpt_info.header_crc32_valid {
+            self.report(String::from(location), String::from("invalid header CRC32"));
+        }
+
+        if !gpt_info.entries_crc32_valid {
+            self.report(
+                String::from(location),
+                String::from("invalid partition entries CRC32"),
+            );
+        }
+
+        if gpt_info.backup_sector >= sector_count {
+            self.report(
+                String::from(location),
+                format!(
+                    "alternate header sector {} is beyond the image size of {} sectors",
+                    gpt_info.backup_sector, sector_count
+                ),
+            );
+        }
+    }
+
+    fn verify_partition_tables(&mut self) -> io::Result<()> {
+        let sector_count = self.reader.get_image_size()? / u64::from(SECTOR_SIZE);
+
+        let mbr = self.reader.read_mbr()?;
+        if let Some(mbr) = &mbr {
+            for (index, partition) in mbr.partitions.iter().enumerate() {
+                if let Some(partition) = partition {
+                    let end = u64::from(partition.start_sector) + u64::from(partition.sector_count);
+                    if end > sector_count && partition.partition_type != 0xEE {
+                        self.report(
+                            format!("MBR partition {}", index + 1),
+                            format!(
+                                "ends at sector {} beyond the image size of {} sectors",
+                                end, sector_count
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        let gpt = match self.reader.read_gpt_at(1) {
+            Ok(Some(gpt)) => gpt,
+            Ok(None) => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                self.report(
+                    String::from("primary GPT header (sector 1)"),
+                    err.to_string(),
+                );
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        };
+
+        let has_protective_partition = mbr
+            .as_ref()
+            .map(|mbr| {
+                mbr.partitions
+                    .iter()
+                    .flatten()
+                    .any(|partition| partition.partition_type == 0xEE)
+            })
+            .unwrap_or(false);
+        if !has_protective_partition {
+            self.report(
+                String::from("MBR"),
+                String::from("GPT present without a protective (0xEE) MBR partition"),
+            );
+        }
+
+        self.verify_gpt_header(&gpt, "primary GPT header (sector 1)", sector_count);
+
+        let backup = if gpt.backup_sector < sector_count {
+            match self.reader.read_gpt_at(gpt.backup_sector) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    self.report(
+                        format!("backup GPT header (sector {})", gpt.backup_sector),
+                        err.to_string(),
+                    );
+                    return Ok(());
+                }
+                res => res?,
+            }
+        } else {
+            None
+        };
+
+        match backup {
+            Some(backup) => {
+                let location = format!("backup GPT header (sector {})", gpt.backup_sector);
+                self.verify_gpt_header(&backup, &location, sector_count);
+
+                if backup.backup_sector != 1 {
+                    self.report(
+                        location.clone(),
+                        format!(
+                            "points to sector {} instead of the primary header",
+                            backup.backup_sector
+                        ),
+                    );
+                }
+
+                let get_layout = |gpt_info: &GptInfo| -> Vec<(u32, [u8; 16], u64, u64)> {
+                    gpt_info
+                        .partitions
+                        .iter()
+                        .map(|(index, partition)| {
+                            (
+                                *index,
+                                partition.type_guid,
+                                partition.start_sector,
+                                partition.end_sector,
+                            )
+                        })
+                        .collect()
+                };
+                if get_layout(&backup) != get_layout(&gpt) {
+                    self.report(
+                        location,
+                        String::from("partition entries differ from the primary GPT"),
+                    );
+                }
+            }
+            None => self.report(
+                String::from("backup GPT header"),
+                format!("missing at sector {}", gpt.backup_sector),
+            ),
+        }
+
+        for (index, partition) in &gpt.partitions {
+            if partition.start_sector > partition.end_sector
+                || partition.start_sector < gpt.first_usable_sector
+                || partition.end_sector > gpt.last_usable_sector
+            {
+                self.report(
+                    format!("GPT partition {}", index),
+                    format!(
+                        "sectors {} to {} are outside of the usable sectors {} to {}",
+                        partition.start_sector,
+                        partition.end_sector,
+                        gpt.first_usable_sector,
+                        gpt.last_usable_sector
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the ECMA-119, SUSP/RRIP, El Torito and partition table invariants of an ISO image.
+///
+/// The violations found are printed, returns whether the image is free of them.
+pub fn verify_iso(verify_opt: &VerifyOpt) -> io::Result<bool> {
+    let file = File::open(&verify_opt.input)?;
+    let reader = IsoReader::new(BufReader::new(file))?;
+
+    let mut verifier = Verifier {
+        volume_space_size: reader
+            .get_volume(Namespace::Iso)
+            .map_or(0, |volume| volume.volume_space_size),
+        reader,
+        violations: Vec::new(),
+    };
+
+    verifier.verify_volume_descriptors()?;
+    verifier.verify_tree(Namespace::Iso, "ISO 9660")?;
+    verifier.verify_tree(Namespace::Joliet, "Joliet")?;
+    verifier.verify_boot_catalog()?;
+    verifier.verify_partition_tables()?;
+
+    for (location, message) in &verifier.violations {
+        println!("{}: {}", location, message);
+    }
+
+    if !verifier.violations.is_empty() {
+        println!("{} violations found", verifier.violations.len());
+        return Ok(false);
+    }
+
+    println!("no violation found");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::iso;
+    use crate::iso::option::Opt;
+
+    use structopt::StructOpt;
+
+    use std::fs;
+
+    #[test]
+    fn verify_created_images() {
+        let test_directory = utils::create_test_directory("verify");
+        let source_directory = test_directory.join("source");
+        let image = test_directory.join("test.iso");
+
+        // Enough directories for the path tables to use several LB each
+        for index in 0..200 {
+            fs::create_dir_all(source_directory.join(format!("directory_{}/sub", index))).unwrap();
+        }
+        fs::write(
+            source_directory.join("boot.bin"),
+            vec![0x90; 4 * LOGIC_SIZE],
+        )
+        .unwrap();
+        fs::write(source_directory.join("efi.img"), vec![0x0; 0x10000]).unwrap();
+
+        let source = source_directory.to_str().unwrap();
+        let arguments = [
+            vec!["mkisofs-rs", "-o", image.to_str().unwrap(), source],
+            vec![
+                "mkisofs-rs",
+                "-o",
+                image.to_str().unwrap(),
+                "-b",
+                "boot.bin",
+                "--no-emul-boot",
+                "--boot-info-table",
+                "-e",
+                "efi.img",
+                "--isohybrid-gpt-basdat",
+                "--udf",
+                source,
+            ],
+        ];
+
+        for arguments in arguments {
+            let mut opt = Opt::from_iter(arguments);
+            iso::create_iso(&mut opt).unwrap();
+
+            let verify_opt = VerifyOpt {
+                input: image.clone(),
+            };
+            assert!(verify_iso(&verify_opt).unwrap());
+
+            let mut reader = IsoReader::new(File::open(&image).unwrap()).unwrap();
+            let volume = reader.get_volume(Namespace::Iso).unwrap().clone();
+            assert!(volume.path_table_size > LOGIC_SIZE_U32);
+            assert_eq!(
+                volume.m_path_table_lba,
+                volume.l_path_table_lba + volume.path_table_size.div_ceil(LOGIC_SIZE_U32)
+            );
+            let root = reader.get_root(Namespace::Iso).unwrap();
+            let entries = reader.read_directory(&root, Namespace::Iso).unwrap();
+            assert_eq!(
+                entries.iter().filter(|entry| entry.is_directory()).count(),
+                200
+            );
+
+            // Break the type M path table
+            let mut data = fs::read(&image).unwrap();
+            data[volume.m_path_table_lba as usize * LOGIC_SIZE + 2] ^= 0xFF;
+            fs::write(&image, data).unwrap();
+            assert!(!verify_iso(&verify_opt).unwrap());
+        }
+
+        fs::remove_dir_all(test_directory).unwrap();
+    }
+}
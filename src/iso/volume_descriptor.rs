@@ -10,7 +10,7 @@ use std::io::prelude::*;
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum VolumeDescriptor {
-    Boot,
+    Boot { catalog_path: String },
     Primary,
     Supplementary,
     Volume,
@@ -20,7 +20,7 @@ pub enum VolumeDescriptor {
 impl VolumeDescriptor {
     fn get_type_id(&self) -> u8 {
         match self {
-            VolumeDescriptor::Boot => 0,
+            VolumeDescriptor::Boot { .. } => 0,
             VolumeDescriptor::Primary => 1,
             VolumeDescriptor::Supplementary => 2,
             VolumeDescriptor::Volume => 3,
@@ -52,11 +52,11 @@ impl VolumeDescriptor {
         self.write_volume_header(output_writter)?;
 
         match self {
-            VolumeDescriptor::Boot => {
+            VolumeDescriptor::Boot { catalog_path } => {
                 // TODO: write it correctly
                 output_writter.write_all(b"EL TORITO SPECIFICATION")?;
 
-                let catalog_file: &FileEntry = root_dir.get_file("boot.catalog").unwrap();
+                let catalog_file: &FileEntry = root_dir.get_file(catalog_path).unwrap();
 
                 let empty_data: [u8; 0x29] = [0; 0x29];
                 output_writter.write_all(&empty_data)?;
@@ -105,7 +105,7 @@ impl VolumeDescriptor {
 
                 // path table location (in lba)
                 let path_table_lba_le = path_table_start_lba; // System Area + Primary + End
-                let path_table_lba_be = path_table_start_lba + 2; // System Area + Primary + End + Path Table LE + Spacing
+                let path_table_lba_be = path_table_start_lba + root_dir.get_path_table_lb_count(); // After the Path Table LE
 
                 output_writter.write_u32::<LittleEndian>(path_table_lba_le)?;
                 output_writter.write_u32::<LittleEndian>(0)?;
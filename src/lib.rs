@@ -0,0 +1,6 @@
+extern crate byteorder;
+extern crate chrono;
+
+extern crate structopt;
+
+pub mod iso;
@@ -1,15 +1,28 @@
-extern crate byteorder;
-extern crate chrono;
-
+extern crate mkisofs_rs;
 extern crate structopt;
 
 use structopt::StructOpt;
 
-mod iso;
-
-use crate::iso::option::Opt;
+use mkisofs_rs::iso;
+use mkisofs_rs::iso::option::{Command, Opt};
 
 fn main() {
     let mut opt = Opt::from_args();
-    iso::create_iso(&mut opt).unwrap();
+    match &opt.command {
+        Some(Command::Rescue(rescue_opt)) => iso::rescue::create_rescue_iso(rescue_opt).unwrap(),
+        Some(Command::Info(info_opt)) => iso::info::print_info(info_opt).unwrap(),
+        Some(Command::Extract(extract_opt)) => {
+            iso::extract::extract_iso(&extract_opt.input, &extract_opt.output, &extract_opt.paths)
+                .unwrap()
+        }
+        Some(Command::Ls(ls_opt)) => iso::ls::list_iso(ls_opt).unwrap(),
+        Some(Command::Verify(verify_opt)) => {
+            if !iso::verify::verify_iso(verify_opt).unwrap() {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Diff(diff_opt)) => iso::diff::diff_iso(diff_opt).unwrap(),
+        Some(Command::Edit(edit_opt)) => iso::edit::edit_iso(edit_opt).unwrap(),
+        None => iso::create_iso(&mut opt).unwrap(),
+    }
 }